@@ -1,13 +1,17 @@
-macro_rules! GIT_DIRECTORY {
-    () => {
-        ".git"
-    };
-}
+pub const GIT_DIRECTORY: &str = ".git";
 
-pub const GIT_DIRECTORY: &str = GIT_DIRECTORY!();
-pub const HEAD_PATH: &str = concat!(GIT_DIRECTORY!(), "/HEAD");
-pub const REFS_DIRECTORY: &str = concat!(GIT_DIRECTORY!(), "/refs");
-pub const OBJECTS_DIRECTORY: &str = concat!(GIT_DIRECTORY!(), "/objects");
-pub const GIT_INDEX_PATH: &str = concat!(GIT_DIRECTORY!(), "/index");
+// 以下は git directory からの相対パス
+pub const HEAD_PATH: &str = "HEAD";
+pub const REFS_DIRECTORY: &str = "refs";
+pub const OBJECTS_DIRECTORY: &str = "objects";
+pub const PACK_DIRECTORY: &str = "objects/pack";
+pub const LOGS_DIRECTORY: &str = "logs";
+pub const GIT_INDEX_PATH: &str = "index";
+pub const CONFIG_PATH: &str = "config";
+pub const MERGE_HEAD_PATH: &str = "MERGE_HEAD";
+pub const MERGE_MSG_PATH: &str = "MERGE_MSG";
+pub const MERGE_MODE_PATH: &str = "MERGE_MODE";
+pub const ORIG_HEAD_PATH: &str = "ORIG_HEAD";
+pub const PACKED_REFS_PATH: &str = "packed-refs";
 
 pub const DEFAULT_BRANCH: &str = "main";
@@ -1,5 +1,21 @@
+pub mod config;
+pub mod date;
+pub mod diff;
 pub mod head;
+pub mod ignore;
 pub mod index;
+pub mod lock;
+pub mod merge;
 pub mod object;
+pub mod packed_refs;
+pub mod pathspec;
+pub mod ref_transaction;
+pub mod reflog;
 pub mod refs;
+pub mod repository;
+pub mod rev_walk;
+pub mod revision;
+pub mod status;
 pub mod tree;
+pub mod wildmatch;
+pub mod worktree;
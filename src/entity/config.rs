@@ -0,0 +1,723 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    consts::CONFIG_PATH,
+    entity::{head::Head, lock::LockFile, repository::Repository},
+};
+
+/// include が循環していても止まるように深さを制限する (git と同じ)
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// `section.subsection.key` の形の名前
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ConfigName {
+    /// 小文字に揃える
+    pub section: String,
+    /// 大文字小文字を区別する
+    pub subsection: Option<String>,
+    /// 小文字に揃える
+    pub key: String,
+}
+impl ConfigName {
+    pub fn parse(name: &str) -> Result<Self> {
+        let (section, rest) = name
+            .split_once('.')
+            .ok_or(anyhow!("key does not contain a section: {}", name))?;
+        let (subsection, key) = match rest.rsplit_once('.') {
+            Some((subsection, key)) => (Some(subsection.to_string()), key),
+            None => (None, rest),
+        };
+
+        if section.is_empty() || !section.chars().all(is_section_char) {
+            bail!("invalid key: {}", name);
+        }
+        if !is_valid_key(key) {
+            bail!("invalid key: {}", name);
+        }
+
+        Ok(Self {
+            section: section.to_lowercase(),
+            subsection,
+            key: key.to_lowercase(),
+        })
+    }
+}
+impl std::fmt::Display for ConfigName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.subsection {
+            Some(subsection) => write!(f, "{}.{}.{}", self.section, subsection, self.key),
+            None => write!(f, "{}.{}", self.section, self.key),
+        }
+    }
+}
+
+fn is_section_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '.'
+}
+
+fn is_valid_key(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ConfigEntry {
+    pub name: ConfigName,
+    /// `key` だけで `=` がない場合は None (真偽値の true として扱われる)
+    pub value: Option<String>,
+}
+
+/// ファイル中の位置を含めた構文要素
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Item {
+    Section {
+        section: String,
+        subsection: Option<String>,
+        line: usize,
+    },
+    Entry {
+        entry: ConfigEntry,
+        lines: Range<usize>,
+        /// 最初の行で key が始まる位置 (文字数)。section の見出しと同じ行にあれば 0 ではない
+        column: usize,
+    },
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+    file_name: &'a str,
+}
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self) -> anyhow::Error {
+        anyhow!(
+            "bad config line {} in file {}",
+            self.line + 1,
+            self.file_name
+        )
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(c) = self.next() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    fn parse(mut self) -> Result<Vec<Item>> {
+        let mut items = Vec::new();
+        let mut current: Option<(String, Option<String>)> = None;
+
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.next();
+            } else if c == '#' || c == ';' {
+                self.skip_line();
+            } else if c == '[' {
+                let line = self.line;
+                let (section, subsection) = self.parse_section()?;
+                current = Some((section.clone(), subsection.clone()));
+                items.push(Item::Section {
+                    section,
+                    subsection,
+                    line,
+                });
+            } else if c.is_ascii_alphabetic() {
+                let start = self.line;
+                let column = self.chars[..self.position]
+                    .iter()
+                    .rev()
+                    .take_while(|&&c| c != '\n')
+                    .count();
+                let Some((section, subsection)) = current.clone() else {
+                    return Err(self.error());
+                };
+                let (key, value) = self.parse_variable()?;
+                // 改行まで読んでいれば line は次の行を指している
+                let end = match self.position.checked_sub(1).map(|i| self.chars[i]) {
+                    Some('\n') => self.line,
+                    _ => self.line + 1,
+                };
+                items.push(Item::Entry {
+                    entry: ConfigEntry {
+                        name: ConfigName {
+                            section,
+                            subsection,
+                            key,
+                        },
+                        value,
+                    },
+                    lines: start..end,
+                    column,
+                });
+            } else {
+                return Err(self.error());
+            }
+        }
+
+        Ok(items)
+    }
+
+    fn parse_section(&mut self) -> Result<(String, Option<String>)> {
+        self.next();
+
+        let mut name = String::new();
+        while let Some(c) = self.peek().filter(|&c| is_section_char(c)) {
+            name.push(c);
+            self.next();
+        }
+
+        match self.next() {
+            Some(']') => {
+                // 古い `[section.subsection]` の形式では subsection も小文字にする
+                match name.split_once('.') {
+                    Some((section, subsection)) => {
+                        Ok((section.to_lowercase(), Some(subsection.to_lowercase())))
+                    }
+                    None => Ok((name.to_lowercase(), None)),
+                }
+            }
+            Some(c) if c.is_whitespace() && c != '\n' => {
+                while self.peek().is_some_and(|c| c.is_whitespace() && c != '\n') {
+                    self.next();
+                }
+                if self.next() != Some('"') {
+                    return Err(self.error());
+                }
+
+                let mut subsection = String::new();
+                loop {
+                    match self.next() {
+                        Some('"') => break,
+                        Some('\\') => subsection.push(self.next().ok_or(self.error())?),
+                        Some('\n') | None => return Err(self.error()),
+                        Some(c) => subsection.push(c),
+                    }
+                }
+                if self.next() != Some(']') {
+                    return Err(self.error());
+                }
+
+                Ok((name.to_lowercase(), Some(subsection)))
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    fn parse_variable(&mut self) -> Result<(String, Option<String>)> {
+        let mut key = String::new();
+        while let Some(c) = self
+            .peek()
+            .filter(|&c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            key.push(c);
+            self.next();
+        }
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.next();
+        }
+
+        match self.peek() {
+            Some('=') => {
+                self.next();
+                Ok((key.to_lowercase(), Some(self.parse_value()?)))
+            }
+            Some('\n') | Some('#') | Some(';') | None => {
+                self.skip_line();
+                Ok((key.to_lowercase(), None))
+            }
+            Some('\r') if self.chars.get(self.position + 1) == Some(&'\n') => {
+                self.skip_line();
+                Ok((key.to_lowercase(), None))
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    /// 引用符の外の空白は 1 つずつ空白として残し、前後の空白は捨てる
+    fn parse_value(&mut self) -> Result<String> {
+        let mut value = String::new();
+        let mut quoted = false;
+        let mut comment = false;
+        let mut spaces = 0;
+
+        loop {
+            let Some(c) = self.next() else {
+                if quoted {
+                    return Err(self.error());
+                }
+                break;
+            };
+            if c == '\n' {
+                if quoted {
+                    return Err(self.error());
+                }
+                break;
+            }
+            if comment {
+                continue;
+            }
+            if !quoted && (c == ';' || c == '#') {
+                comment = true;
+                continue;
+            }
+            if c.is_whitespace() && !quoted {
+                if !value.is_empty() {
+                    spaces += 1;
+                }
+                continue;
+            }
+
+            for _ in 0..spaces {
+                value.push(' ');
+            }
+            spaces = 0;
+
+            match c {
+                '\\' => match self.next() {
+                    // 行の継続
+                    Some('\n') => {}
+                    Some('t') => value.push('\t'),
+                    Some('b') => value.push('\u{8}'),
+                    Some('n') => value.push('\n'),
+                    Some(c @ ('\\' | '"')) => value.push(c),
+                    _ => return Err(self.error()),
+                },
+                '"' => quoted = !quoted,
+                c => value.push(c),
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+fn parse_items(content: &str, file_name: &str) -> Result<Vec<Item>> {
+    Parser {
+        chars: content.chars().collect(),
+        position: 0,
+        line: 0,
+        file_name,
+    }
+    .parse()
+}
+
+pub fn home_directory() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+pub fn expand_home(path: &str) -> Option<PathBuf> {
+    match path.strip_prefix("~/") {
+        Some(rest) => home_directory().map(|home| home.join(rest)),
+        None => Some(PathBuf::from(path)),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConfigScope {
+    System,
+    Global,
+    Local,
+}
+impl ConfigScope {
+    /// 読み込む順 (後のものが優先される) に並んだ設定ファイル
+    fn paths(&self, repo: Option<&Repository>) -> Vec<PathBuf> {
+        match self {
+            Self::System => {
+                if std::env::var_os("GIT_CONFIG_NOSYSTEM").is_some() {
+                    return Vec::new();
+                }
+                match std::env::var_os("GIT_CONFIG_SYSTEM") {
+                    Some(path) => vec![PathBuf::from(path)],
+                    None => vec![PathBuf::from("/etc/gitconfig")],
+                }
+            }
+            Self::Global => {
+                if let Some(path) = std::env::var_os("GIT_CONFIG_GLOBAL") {
+                    return vec![PathBuf::from(path)];
+                }
+
+                let xdg = match std::env::var_os("XDG_CONFIG_HOME").filter(|xdg| !xdg.is_empty()) {
+                    Some(xdg) => Some(PathBuf::from(xdg).join("git/config")),
+                    None => home_directory().map(|home| home.join(".config/git/config")),
+                };
+                let home = home_directory().map(|home| home.join(".gitconfig"));
+
+                xdg.into_iter().chain(home).collect()
+            }
+            Self::Local => repo
+                .map(|repo| repo.git_path(CONFIG_PATH))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// 書き込み先。global は `~/.gitconfig` がなく XDG の方だけあればそちらに書く
+    pub fn write_path(&self, repo: Option<&Repository>) -> Result<PathBuf> {
+        let paths = self.paths(repo);
+        let path = match self {
+            Self::Global => paths
+                .iter()
+                .rev()
+                .find(|path| path.exists())
+                .or(paths.last()),
+            _ => paths.last(),
+        };
+
+        path.cloned()
+            .ok_or(anyhow!("unable to determine the config file to write"))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Config {
+    pub entries: Vec<ConfigEntry>,
+}
+impl Config {
+    /// system, global, local の順に読んで重ねる (repository の外なら local はない)
+    pub fn read(repo: Option<&Repository>) -> Result<Self> {
+        let mut config = Self::default();
+        for scope in [ConfigScope::System, ConfigScope::Global, ConfigScope::Local] {
+            config
+                .entries
+                .extend(Self::read_scope(repo, scope)?.entries);
+        }
+
+        Ok(config)
+    }
+
+    pub fn read_scope(repo: Option<&Repository>, scope: ConfigScope) -> Result<Self> {
+        let mut config = Self::default();
+        for path in scope.paths(repo) {
+            config.read_file_into(repo, &path, 0)?;
+        }
+
+        Ok(config)
+    }
+
+    /// ファイルがなければ空の設定として扱う
+    pub fn read_file(repo: Option<&Repository>, path: &Path) -> Result<Self> {
+        let mut config = Self::default();
+        config.read_file_into(repo, path, 0)?;
+
+        Ok(config)
+    }
+
+    fn read_file_into(
+        &mut self,
+        repo: Option<&Repository>,
+        path: &Path,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            bail!("exceeded maximum include depth ({})", MAX_INCLUDE_DEPTH);
+        }
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return Ok(());
+        };
+
+        for item in parse_items(&content, &path.to_string_lossy())? {
+            let Item::Entry { entry, .. } = item else {
+                continue;
+            };
+
+            let include = entry.name.key == "path"
+                && match (entry.name.section.as_str(), &entry.name.subsection) {
+                    ("include", None) => true,
+                    ("includeif", Some(condition)) => {
+                        repo.is_some_and(|repo| include_condition(repo, condition))
+                    }
+                    _ => false,
+                };
+            let include_path = include
+                .then(|| entry.value.as_deref().and_then(expand_home))
+                .flatten();
+            self.entries.push(entry);
+
+            // include したファイルの内容は include した位置に挟み込む
+            if let Some(include_path) = include_path {
+                let include_path = match path.parent() {
+                    Some(parent) if include_path.is_relative() => parent.join(include_path),
+                    _ => include_path,
+                };
+                self.read_file_into(repo, &include_path, depth + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_all(&self, name: &ConfigName) -> Vec<&ConfigEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| &entry.name == name)
+            .collect()
+    }
+
+    /// 最後に現れた値を返す (`=` のない値は空文字列)
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let name = ConfigName::parse(name).ok()?;
+        self.get_all(&name)
+            .last()
+            .map(|entry| entry.value.as_deref().unwrap_or(""))
+    }
+}
+
+/// config の真偽値として真か (`=` のない値も真)
+pub fn is_true(value: &str) -> bool {
+    matches!(
+        value.to_lowercase().as_str(),
+        "" | "true" | "yes" | "on" | "1"
+    )
+}
+
+/// `includeIf "gitdir:..."` と `includeIf "onbranch:..."` の条件を評価する
+fn include_condition(repo: &Repository, condition: &str) -> bool {
+    if let Some(pattern) = condition.strip_prefix("onbranch:") {
+        let Ok(Head::Ref(ref_)) = Head::read(repo) else {
+            return false;
+        };
+        let branch = ref_.to_string();
+        let Some(branch) = branch.strip_prefix("refs/heads/") else {
+            return false;
+        };
+
+        return match pattern.strip_suffix('/') {
+            Some(prefix) => branch.starts_with(&format!("{}/", prefix)),
+            None => branch == pattern,
+        };
+    }
+
+    let (pattern, ignore_case) = match (
+        condition.strip_prefix("gitdir:"),
+        condition.strip_prefix("gitdir/i:"),
+    ) {
+        (Some(pattern), _) => (pattern, false),
+        (_, Some(pattern)) => (pattern, true),
+        _ => return false,
+    };
+    let Some(pattern) = expand_home(pattern) else {
+        return false;
+    };
+    let git_directory = &repo.git_dir;
+
+    let normalize = |s: String| if ignore_case { s.to_lowercase() } else { s };
+    let pattern = normalize(pattern.to_string_lossy().to_string());
+    let git_directory = normalize(git_directory.to_string_lossy().to_string());
+
+    // 末尾の `/` は以下すべてにマッチする
+    match pattern.strip_suffix('/') {
+        Some(prefix) => git_directory.starts_with(&format!("{}/", prefix)),
+        None => git_directory == pattern || git_directory == format!("{}/.git", pattern),
+    }
+}
+
+/// 書き出すときに引用符やエスケープが必要なら付ける
+fn quote_value(value: &str) -> String {
+    let needs_quote = value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || value.contains(['#', ';']);
+
+    let escaped = value
+        .chars()
+        .map(|c| match c {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            '\n' => "\\n".to_string(),
+            '\t' => "\\t".to_string(),
+            c => c.to_string(),
+        })
+        .collect::<String>();
+
+    if needs_quote {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// ファイルの書式を保ったまま値を設定する (value が None なら消す)
+pub fn write_value(path: &Path, name: &ConfigName, value: Option<&str>) -> Result<()> {
+    let mut lock = LockFile::acquire(path)?;
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    let items = parse_items(&content, &path.to_string_lossy())?;
+    let mut lines = content.lines().map(str::to_string).collect::<Vec<_>>();
+
+    let matched = items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Entry {
+                entry,
+                lines,
+                column,
+            } if &entry.name == name => Some((lines.clone(), *column)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if matched.len() > 1 {
+        bail!("{} has multiple values", name);
+    }
+
+    let new_line = value.map(|value| format!("\t{} = {}", name.key, quote_value(value)));
+    match (matched.first(), new_line) {
+        (Some((range, column)), new_line) => {
+            // `[section] key = value` のように見出しと同じ行にあれば見出しは残す
+            let header = lines[range.start]
+                .chars()
+                .take(*column)
+                .collect::<String>()
+                .trim_end()
+                .to_string();
+            let header = (!header.trim().is_empty()).then_some(header);
+            lines.splice(range.clone(), header.into_iter().chain(new_line));
+        }
+        (None, None) => bail!("key '{}' not found", name),
+        (None, Some(new_line)) => {
+            // 同じ section があればその最後の entry の後ろに、なければ section ごと末尾に追加する
+            let mut insert_at = None;
+            let mut in_section = false;
+            for item in &items {
+                match item {
+                    Item::Section {
+                        section,
+                        subsection,
+                        line,
+                    } => {
+                        in_section = section == &name.section && subsection == &name.subsection;
+                        if in_section {
+                            insert_at = Some(line + 1);
+                        }
+                    }
+                    Item::Entry { lines, .. } if in_section => insert_at = Some(lines.end),
+                    Item::Entry { .. } => {}
+                }
+            }
+
+            match insert_at {
+                Some(insert_at) => lines.insert(insert_at, new_line),
+                None => {
+                    let header = match &name.subsection {
+                        Some(subsection) => format!(
+                            "[{} \"{}\"]",
+                            name.section,
+                            subsection.replace('\\', "\\\\").replace('"', "\\\"")
+                        ),
+                        None => format!("[{}]", name.section),
+                    };
+                    lines.push(header);
+                    lines.push(new_line);
+                }
+            }
+        }
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    lock.write(content.as_bytes())?;
+    lock.commit()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Vec<ConfigEntry> {
+        parse_items(content, "test")
+            .unwrap()
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::Entry { entry, .. } => Some(entry),
+                Item::Section { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let content = r#"
+# comment
+[User]
+	name = "Foo  Bar" ; comment
+	EMAIL=foo@example.com
+[remote "Origin"]
+	url = a\
+b
+	fetch = x   y	 # comment
+[core.Editor]
+	bare
+"#;
+        let entries = parse(content);
+        let names_values = entries
+            .iter()
+            .map(|entry| (entry.name.to_string(), entry.value.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            names_values,
+            vec![
+                ("user.name".to_string(), Some("Foo  Bar".to_string())),
+                (
+                    "user.email".to_string(),
+                    Some("foo@example.com".to_string())
+                ),
+                ("remote.Origin.url".to_string(), Some("ab".to_string())),
+                ("remote.Origin.fetch".to_string(), Some("x   y".to_string())),
+                ("core.editor.bare".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(parse_items("key = value\n", "test").is_err());
+        assert!(parse_items("[section]\nkey = \"value\n", "test").is_err());
+    }
+
+    #[test]
+    fn test_write_value_on_section_line() {
+        let path = std::env::temp_dir().join(format!("ssgit-config-{}", std::process::id()));
+        std::fs::write(&path, "[core]\n\tbare = false\n[user] name = x\n").unwrap();
+
+        let name = ConfigName::parse("user.name").unwrap();
+        write_value(&path, &name, Some("y")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[core]\n\tbare = false\n[user]\n\tname = y\n"
+        );
+
+        std::fs::write(&path, "[user] name = x\n").unwrap();
+        write_value(&path, &name, None).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[user]\n");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_quote_value() {
+        assert_eq!(quote_value("plain"), "plain");
+        assert_eq!(quote_value(" a#b"), "\" a#b\"");
+        assert_eq!(quote_value("a\"b\\"), "a\\\"b\\\\");
+    }
+}
@@ -0,0 +1,162 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+/// git の日付指定をパースする
+///
+/// `[@]<unix> [+-HHMM]`, RFC 2822, ISO 8601 (RFC 3339), `YYYY-MM-DD[ HH:MM[:SS]]`, `N <unit> ago` を受け付ける
+/// タイムゾーンの指定がなければローカルのものとして扱う
+pub fn parse_date(s: &str) -> Result<DateTime<FixedOffset>> {
+    let s = s.trim();
+
+    if let Some(date) = parse_raw(s) {
+        return date;
+    }
+
+    if let Ok(date) = DateTime::parse_from_rfc2822(s) {
+        return Ok(date);
+    }
+    if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+        return Ok(date);
+    }
+    for format in [
+        "%Y-%m-%d %H:%M:%S %z",
+        "%Y-%m-%dT%H:%M:%S %z",
+        "%a %b %e %H:%M:%S %Y %z",
+    ] {
+        if let Ok(date) = DateTime::parse_from_str(s, format) {
+            return Ok(date);
+        }
+    }
+    if let Some(date) = s.strip_suffix('Z') {
+        if let Ok(date) = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S") {
+            return Ok(date.and_utc().fixed_offset());
+        }
+    }
+
+    for format in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y.%m.%d %H:%M:%S",
+        "%Y.%m.%d %H:%M",
+        "%a %b %e %H:%M:%S %Y",
+    ] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(s, format) {
+            return local(date);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return local(date.and_hms_opt(0, 0, 0).unwrap());
+    }
+
+    if let Some(relative) = s.strip_suffix(" ago") {
+        let (count, unit) = relative
+            .split_once(' ')
+            .ok_or(anyhow!("Invalid date {}", s))?;
+        let count = i64::from_str(count)?;
+        let duration = match unit.trim_end_matches('s') {
+            "second" => Duration::seconds(count),
+            "minute" => Duration::minutes(count),
+            "hour" => Duration::hours(count),
+            "day" => Duration::days(count),
+            "week" => Duration::weeks(count),
+            "month" => Duration::days(count * 30),
+            "year" => Duration::days(count * 365),
+            _ => bail!("Invalid date {}", s),
+        };
+
+        let now = Local::now();
+        return Ok((now - duration).with_timezone(now.offset()));
+    }
+
+    bail!("Invalid date {}", s);
+}
+
+/// git の内部形式 `<unix> <+-HHMM>` と `@<unix>` をパースする
+fn parse_raw(s: &str) -> Option<Result<DateTime<FixedOffset>>> {
+    let (timestamp, offset) = match s.split_once(' ') {
+        Some((timestamp, offset)) => (timestamp, Some(parse_offset(offset)?)),
+        None => (s.strip_prefix('@')?, None),
+    };
+    let timestamp = i64::from_str(timestamp.strip_prefix('@').unwrap_or(timestamp)).ok()?;
+
+    let date = match offset {
+        Some(offset) => offset.timestamp_opt(timestamp, 0).single(),
+        None => Local
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .map(|date| date.with_timezone(date.offset())),
+    };
+
+    Some(date.ok_or(anyhow!("Invalid timestamp {}", timestamp)))
+}
+
+/// `+0900` の形式のタイムゾーンをパースする
+pub fn parse_offset(s: &str) -> Option<FixedOffset> {
+    if s.len() != 5 || !s[1..].bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let sign = match &s[..1] {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let hours = i32::from_str(&s[1..3]).ok()?;
+    let minutes = i32::from_str(&s[3..]).ok()?;
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// `+0900` の形式で書き出す
+pub fn format_offset(offset: &FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+fn local(date: NaiveDateTime) -> Result<DateTime<FixedOffset>> {
+    let date = Local
+        .from_local_datetime(&date)
+        .earliest()
+        .ok_or(anyhow!("Invalid local time {}", date))?;
+
+    Ok(date.with_timezone(date.offset()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date_with_offset() {
+        let expected = "2005-04-07T22:13:13+02:00";
+        for s in [
+            "1112904793 +0200",
+            "@1112904793 +0200",
+            "Thu, 07 Apr 2005 22:13:13 +0200",
+            "2005-04-07T22:13:13+02:00",
+            "2005-04-07 22:13:13 +0200",
+            "Thu Apr 7 22:13:13 2005 +0200",
+        ] {
+            assert_eq!(parse_date(s).unwrap().to_rfc3339(), expected, "{}", s);
+        }
+
+        assert_eq!(
+            parse_date("2005-04-07T20:13:13Z").unwrap().timestamp(),
+            1112904793
+        );
+        assert_eq!(parse_date("@1112904793").unwrap().timestamp(), 1112904793);
+    }
+
+    #[test]
+    fn test_offset() {
+        let offset = parse_offset("-0530").unwrap();
+        assert_eq!(offset.local_minus_utc(), -(5 * 3600 + 30 * 60));
+        assert_eq!(format_offset(&offset), "-0530");
+        assert_eq!(parse_offset("+09:00"), None);
+    }
+}
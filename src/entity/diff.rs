@@ -0,0 +1,798 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::entity::{
+    object::{hash::Hash, mode::BlobType},
+    worktree::TreeFiles,
+};
+
+/// この長さまでに NUL を含むものはバイナリとみなす (git と同じ)
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Algorithm {
+    #[default]
+    Myers,
+    Patience,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+pub fn is_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_CHECK_LENGTH)].contains(&0)
+}
+
+/// 改行を含めた行に分割する
+pub fn split_lines(content: &[u8]) -> Vec<&[u8]> {
+    content.split_inclusive(|&b| b == b'\n').collect()
+}
+
+pub fn diff_lines(old: &[&[u8]], new: &[&[u8]], algorithm: Algorithm) -> Vec<Edit> {
+    let mut edits = Vec::new();
+
+    match algorithm {
+        Algorithm::Myers => myers(old, new, 0, 0, &mut edits),
+        Algorithm::Patience => patience(old, new, 0, 0, &mut edits),
+    }
+
+    compact(old, new, &edits)
+}
+
+/// Myers の O(ND) アルゴリズム。offset は edits に書き込む行番号のずれ
+///
+/// xdiff と同じく最短経路の中間点で分割していくので、使うメモリは O(N+M) で済む
+fn myers(
+    old: &[&[u8]],
+    new: &[&[u8]],
+    old_offset: usize,
+    new_offset: usize,
+    edits: &mut Vec<Edit>,
+) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    for i in 0..prefix {
+        edits.push(Edit::Equal(old_offset + i, new_offset + i));
+    }
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    let old_start = old_offset + prefix;
+    let new_start = new_offset + prefix;
+
+    if old_middle.is_empty() {
+        edits.extend((0..new_middle.len()).map(|j| Edit::Insert(new_start + j)));
+    } else if new_middle.is_empty() {
+        edits.extend((0..old_middle.len()).map(|i| Edit::Delete(old_start + i)));
+    } else {
+        let (x, y) = middle_point(old_middle, new_middle);
+        myers(
+            &old_middle[..x],
+            &new_middle[..y],
+            old_start,
+            new_start,
+            edits,
+        );
+        myers(
+            &old_middle[x..],
+            &new_middle[y..],
+            old_start + x,
+            new_start + y,
+            edits,
+        );
+    }
+
+    for i in 0..suffix {
+        edits.push(Edit::Equal(
+            old_offset + old.len() - suffix + i,
+            new_offset + new.len() - suffix + i,
+        ));
+    }
+}
+
+/// 先頭と末尾から同時に経路を伸ばし、重なった点を返す。そこまでとそこからの編集距離はどちらも全体より短い
+///
+/// 両端が一致しない空でない列を渡すこと
+fn middle_point(old: &[&[u8]], new: &[&[u8]]) -> (usize, usize) {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+
+    // forward[k] は先頭から対角線 k (x - y = k) 上で到達できる最も大きい x、
+    // backward[k] は末尾から到達できる最も小さい x
+    let offset = max + delta.abs() + 2;
+    let index = |k: isize| (k + offset) as usize;
+    let mut forward = vec![0isize; 2 * offset as usize + 1];
+    let mut backward = vec![n; 2 * offset as usize + 1];
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]) {
+                forward[index(k + 1)]
+            } else {
+                forward[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[index(k)] = x;
+
+            if delta % 2 != 0
+                && (delta - (d - 1)..=delta + (d - 1)).contains(&k)
+                && x >= backward[index(k)]
+            {
+                return (x as usize, y as usize);
+            }
+        }
+
+        for k in (delta - d..=delta + d).step_by(2) {
+            let mut x = if k == delta + d
+                || (k != delta - d && backward[index(k - 1)] < backward[index(k + 1)] - 1)
+            {
+                backward[index(k - 1)]
+            } else {
+                backward[index(k + 1)] - 1
+            };
+            let mut y = x - k;
+            while x > 0 && y > 0 && old[x as usize - 1] == new[y as usize - 1] {
+                x -= 1;
+                y -= 1;
+            }
+            backward[index(k)] = x;
+
+            if delta % 2 == 0 && (-d..=d).contains(&k) && forward[index(k)] >= x {
+                return (x as usize, y as usize);
+            }
+        }
+    }
+
+    unreachable!("the forward and backward paths always meet")
+}
+
+/// Patience diff。両方で一度ずつしか現れない行を手がかりに分割し、残りは Myers で埋める
+fn patience(
+    old: &[&[u8]],
+    new: &[&[u8]],
+    old_offset: usize,
+    new_offset: usize,
+    edits: &mut Vec<Edit>,
+) {
+    // 先頭と末尾の共通部分は先に取り除く
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    for i in 0..prefix {
+        edits.push(Edit::Equal(old_offset + i, new_offset + i));
+    }
+    let old_rest = &old[prefix..];
+    let new_rest = &new[prefix..];
+    let suffix = old_rest
+        .iter()
+        .rev()
+        .zip(new_rest.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old_rest[..old_rest.len() - suffix];
+    let new_middle = &new_rest[..new_rest.len() - suffix];
+    let old_offset = old_offset + prefix;
+    let new_offset = new_offset + prefix;
+
+    let anchors = unique_common_lines(old_middle, new_middle);
+    if anchors.is_empty() {
+        myers(old_middle, new_middle, old_offset, new_offset, edits);
+    } else {
+        let (mut old_start, mut new_start) = (0, 0);
+        for (old_index, new_index) in anchors {
+            patience(
+                &old_middle[old_start..old_index],
+                &new_middle[new_start..new_index],
+                old_offset + old_start,
+                new_offset + new_start,
+                edits,
+            );
+            edits.push(Edit::Equal(old_offset + old_index, new_offset + new_index));
+            old_start = old_index + 1;
+            new_start = new_index + 1;
+        }
+        patience(
+            &old_middle[old_start..],
+            &new_middle[new_start..],
+            old_offset + old_start,
+            new_offset + new_start,
+            edits,
+        );
+    }
+
+    let old_end = old_offset + old_middle.len();
+    let new_end = new_offset + new_middle.len();
+    for i in 0..suffix {
+        edits.push(Edit::Equal(old_end + i, new_end + i));
+    }
+}
+
+/// 両方に一度ずつ現れる行のうち、順序を保った最長の組 (patience sorting)
+fn unique_common_lines(old: &[&[u8]], new: &[&[u8]]) -> Vec<(usize, usize)> {
+    let mut counts: HashMap<&[u8], (usize, usize, usize)> = HashMap::new();
+    for (i, line) in old.iter().enumerate() {
+        let entry = counts.entry(line).or_insert((0, 0, 0));
+        entry.0 += 1;
+        entry.2 = i;
+    }
+    for line in new {
+        if let Some(entry) = counts.get_mut(line) {
+            entry.1 += 1;
+        }
+    }
+
+    let pairs = new
+        .iter()
+        .enumerate()
+        .filter_map(|(j, line)| match counts.get(line) {
+            Some(&(1, 1, i)) => Some((i, j)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // old 側の位置について最長増加部分列を求める
+    let mut piles: Vec<usize> = Vec::new();
+    let mut previous = vec![None; pairs.len()];
+    for (position, &(i, _)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|&top| pairs[top].0 < i);
+        if pile > 0 {
+            previous[position] = Some(piles[pile - 1]);
+        }
+        if pile == piles.len() {
+            piles.push(position);
+        } else {
+            piles[pile] = position;
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut current = piles.last().copied();
+    while let Some(position) = current {
+        result.push(pairs[position]);
+        current = previous[position];
+    }
+    result.reverse();
+
+    result
+}
+
+/// 変更行のまとまり `start..end` (空のこともある)
+struct Group {
+    start: usize,
+    end: usize,
+}
+
+/// 片方のファイルの各行が変更されたかどうか
+struct Changes<'a> {
+    lines: &'a [&'a [u8]],
+    changed: Vec<bool>,
+}
+impl Changes<'_> {
+    fn first_group(&self) -> Group {
+        let end = self.changed.iter().take_while(|&&changed| changed).count();
+        Group { start: 0, end }
+    }
+
+    fn next_group(&self, group: &mut Group) -> bool {
+        if group.end == self.lines.len() {
+            return false;
+        }
+
+        group.start = group.end + 1;
+        group.end = group.start;
+        while group.end < self.lines.len() && self.changed[group.end] {
+            group.end += 1;
+        }
+        true
+    }
+
+    fn previous_group(&self, group: &mut Group) -> bool {
+        if group.start == 0 {
+            return false;
+        }
+
+        group.end = group.start - 1;
+        group.start = group.end;
+        while group.start > 0 && self.changed[group.start - 1] {
+            group.start -= 1;
+        }
+        true
+    }
+
+    fn slide_down(&mut self, group: &mut Group) -> bool {
+        if group.end >= self.lines.len() || self.lines[group.start] != self.lines[group.end] {
+            return false;
+        }
+
+        self.changed[group.start] = false;
+        self.changed[group.end] = true;
+        group.start += 1;
+        group.end += 1;
+        while group.end < self.lines.len() && self.changed[group.end] {
+            group.end += 1;
+        }
+        true
+    }
+
+    fn slide_up(&mut self, group: &mut Group) -> bool {
+        if group.start == 0 || self.lines[group.start - 1] != self.lines[group.end - 1] {
+            return false;
+        }
+
+        group.start -= 1;
+        group.end -= 1;
+        self.changed[group.start] = true;
+        self.changed[group.end] = false;
+        while group.start > 0 && self.changed[group.start - 1] {
+            group.start -= 1;
+        }
+        true
+    }
+}
+
+/// 同じ内容で位置だけずらせる変更を git (xdl_change_compact) と同じ位置に揃える
+fn compact(old: &[&[u8]], new: &[&[u8]], edits: &[Edit]) -> Vec<Edit> {
+    let mut old_changes = Changes {
+        lines: old,
+        changed: vec![false; old.len()],
+    };
+    let mut new_changes = Changes {
+        lines: new,
+        changed: vec![false; new.len()],
+    };
+    for edit in edits {
+        match *edit {
+            Edit::Delete(i) => old_changes.changed[i] = true,
+            Edit::Insert(j) => new_changes.changed[j] = true,
+            Edit::Equal(_, _) => {}
+        }
+    }
+
+    // 揃えられなければ元の edits をそのまま使う
+    if compact_side(&mut old_changes, &new_changes).is_none()
+        || compact_side(&mut new_changes, &old_changes).is_none()
+    {
+        return edits.to_vec();
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut result = Vec::with_capacity(edits.len());
+    while i < old.len() || j < new.len() {
+        if i < old.len() && old_changes.changed[i] {
+            result.push(Edit::Delete(i));
+            i += 1;
+        } else if j < new.len() && new_changes.changed[j] {
+            result.push(Edit::Insert(j));
+            j += 1;
+        } else {
+            result.push(Edit::Equal(i, j));
+            i += 1;
+            j += 1;
+        }
+    }
+
+    result
+}
+
+const INDENT_HEURISTIC_MAX_SLIDING: usize = 100;
+
+/// 揃えられなければ None を返す (edits が食い違っていない限り起こらない)
+fn compact_side(side: &mut Changes, other: &Changes) -> Option<()> {
+    let mut group = side.first_group();
+    let mut other_group = other.first_group();
+
+    loop {
+        if group.end != group.start {
+            let mut group_size;
+            let mut earliest_end;
+            let mut end_matching_other;
+            loop {
+                group_size = group.end - group.start;
+                end_matching_other = false;
+
+                // 上に寄せられるだけ寄せてから下に寄せる (途中で隣の変更と繋がれば最初から)
+                while side.slide_up(&mut group) {
+                    expect_moved(other.previous_group(&mut other_group))?;
+                }
+                earliest_end = group.end;
+                if other_group.end > other_group.start {
+                    end_matching_other = true;
+                }
+
+                while side.slide_down(&mut group) {
+                    expect_moved(other.next_group(&mut other_group))?;
+                    if other_group.end > other_group.start {
+                        end_matching_other = true;
+                    }
+                }
+
+                if group_size == group.end - group.start {
+                    break;
+                }
+            }
+
+            if group.end == earliest_end {
+                // 動かせない
+            } else if end_matching_other {
+                // 相手側の変更と並ぶ位置まで戻す
+                while other_group.end == other_group.start {
+                    expect_moved(side.slide_up(&mut group))?;
+                    expect_moved(other.previous_group(&mut other_group))?;
+                }
+            } else {
+                let shift_start = earliest_end
+                    .max((group.end + 1).saturating_sub(group_size + 2))
+                    .max(group.end.saturating_sub(INDENT_HEURISTIC_MAX_SLIDING));
+
+                let mut best: Option<(usize, SplitScore)> = None;
+                for shift in shift_start..=group.end {
+                    let mut score = SplitScore::default();
+                    score.add(&SplitMeasurement::new(side.lines, shift));
+                    score.add(&SplitMeasurement::new(side.lines, shift - group_size));
+
+                    if best
+                        .as_ref()
+                        .is_none_or(|(_, best_score)| score.cmp(best_score) <= 0)
+                    {
+                        best = Some((shift, score));
+                    }
+                }
+
+                let (best_shift, _) = best.expect("at least one shift is scored");
+                while group.end > best_shift {
+                    expect_moved(side.slide_up(&mut group))?;
+                    expect_moved(other.previous_group(&mut other_group))?;
+                }
+            }
+        }
+
+        if !side.next_group(&mut group) {
+            break;
+        }
+        expect_moved(other.next_group(&mut other_group))?;
+    }
+
+    Some(())
+}
+
+/// xdiff では BUG() になる食い違い。debug ビルドでだけ止める
+fn expect_moved(moved: bool) -> Option<()> {
+    debug_assert!(moved, "groups of both sides are out of sync");
+    moved.then_some(())
+}
+
+const MAX_INDENT: usize = 200;
+const MAX_BLANKS: usize = 20;
+
+/// 空白だけの行は None
+fn indent_of(line: &[u8]) -> Option<usize> {
+    let mut indent = 0;
+    for &b in line {
+        if !b.is_ascii_whitespace() && b != 0x0b {
+            return Some(indent);
+        }
+        match b {
+            b' ' => indent += 1,
+            b'\t' => indent += 8 - indent % 8,
+            _ => {}
+        }
+        if indent >= MAX_INDENT {
+            return Some(MAX_INDENT);
+        }
+    }
+
+    None
+}
+
+/// 変更のまとまりを split 行目の直前で区切ったときの周囲の様子 (git の indent heuristic)
+struct SplitMeasurement {
+    end_of_file: bool,
+    indent: Option<usize>,
+    pre_blank: usize,
+    pre_indent: Option<usize>,
+    post_blank: usize,
+    post_indent: Option<usize>,
+}
+impl SplitMeasurement {
+    fn new(lines: &[&[u8]], split: usize) -> Self {
+        let end_of_file = split >= lines.len();
+        let indent = lines.get(split).and_then(|line| indent_of(line));
+
+        let mut pre_blank = 0;
+        let mut pre_indent = None;
+        for line in lines[..split.min(lines.len())].iter().rev() {
+            pre_indent = indent_of(line);
+            if pre_indent.is_some() {
+                break;
+            }
+            pre_blank += 1;
+            if pre_blank == MAX_BLANKS {
+                pre_indent = Some(0);
+                break;
+            }
+        }
+
+        let mut post_blank = 0;
+        let mut post_indent = None;
+        for line in lines.iter().skip(split + 1) {
+            post_indent = indent_of(line);
+            if post_indent.is_some() {
+                break;
+            }
+            post_blank += 1;
+            if post_blank == MAX_BLANKS {
+                post_indent = Some(0);
+                break;
+            }
+        }
+
+        Self {
+            end_of_file,
+            indent,
+            pre_blank,
+            pre_indent,
+            post_blank,
+            post_indent,
+        }
+    }
+}
+
+const START_OF_FILE_PENALTY: i32 = 1;
+const END_OF_FILE_PENALTY: i32 = 21;
+const TOTAL_BLANK_WEIGHT: i32 = -30;
+const POST_BLANK_WEIGHT: i32 = 6;
+const RELATIVE_INDENT_PENALTY: i32 = -4;
+const RELATIVE_INDENT_WITH_BLANK_PENALTY: i32 = 10;
+const RELATIVE_OUTDENT_PENALTY: i32 = 24;
+const RELATIVE_OUTDENT_WITH_BLANK_PENALTY: i32 = 17;
+const RELATIVE_DEDENT_PENALTY: i32 = 23;
+const RELATIVE_DEDENT_WITH_BLANK_PENALTY: i32 = 17;
+const INDENT_WEIGHT: i32 = 60;
+
+#[derive(Debug, Default)]
+struct SplitScore {
+    effective_indent: i32,
+    penalty: i32,
+}
+impl SplitScore {
+    fn add(&mut self, measurement: &SplitMeasurement) {
+        if measurement.pre_indent.is_none() && measurement.pre_blank == 0 {
+            self.penalty += START_OF_FILE_PENALTY;
+        }
+        if measurement.end_of_file {
+            self.penalty += END_OF_FILE_PENALTY;
+        }
+
+        let post_blank = match measurement.indent {
+            Some(_) => 0,
+            None => 1 + measurement.post_blank as i32,
+        };
+        let total_blank = measurement.pre_blank as i32 + post_blank;
+        self.penalty += TOTAL_BLANK_WEIGHT * total_blank;
+        self.penalty += POST_BLANK_WEIGHT * post_blank;
+
+        let indent = measurement.indent.or(measurement.post_indent);
+        let any_blanks = total_blank != 0;
+        self.effective_indent += indent.map_or(-1, |indent| indent as i32);
+
+        let (Some(indent), Some(pre_indent)) = (indent, measurement.pre_indent) else {
+            return;
+        };
+        if indent > pre_indent {
+            self.penalty += if any_blanks {
+                RELATIVE_INDENT_WITH_BLANK_PENALTY
+            } else {
+                RELATIVE_INDENT_PENALTY
+            };
+        } else if indent < pre_indent {
+            let outdent = measurement
+                .post_indent
+                .is_some_and(|post_indent| post_indent > indent);
+            self.penalty += match (outdent, any_blanks) {
+                (true, true) => RELATIVE_OUTDENT_WITH_BLANK_PENALTY,
+                (true, false) => RELATIVE_OUTDENT_PENALTY,
+                (false, true) => RELATIVE_DEDENT_WITH_BLANK_PENALTY,
+                (false, false) => RELATIVE_DEDENT_PENALTY,
+            };
+        }
+    }
+
+    /// 負なら self の方が良い区切り
+    fn cmp(&self, other: &Self) -> i32 {
+        INDENT_WEIGHT * (self.effective_indent.cmp(&other.effective_indent) as i32)
+            + (self.penalty - other.penalty)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_length: usize,
+    pub new_start: usize,
+    pub new_length: usize,
+    pub edits: Vec<Edit>,
+}
+impl Hunk {
+    fn format_range(start: usize, length: usize) -> String {
+        match length {
+            0 => format!("{},0", start),
+            1 => format!("{}", start + 1),
+            _ => format!("{},{}", start + 1, length),
+        }
+    }
+
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{} +{} @@",
+            Self::format_range(self.old_start, self.old_length),
+            Self::format_range(self.new_start, self.new_length)
+        )
+    }
+}
+
+/// 変更の前後 context 行を含めて hunk にまとめる
+pub fn hunks(edits: &[Edit], context: usize) -> Vec<Hunk> {
+    let changes = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(_, _)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for i in changes {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(edits.len());
+
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let count_old = |edits: &[Edit]| {
+        edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Insert(_)))
+            .count()
+    };
+    let count_new = |edits: &[Edit]| {
+        edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Delete(_)))
+            .count()
+    };
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| Hunk {
+            old_start: count_old(&edits[..start]),
+            old_length: count_old(&edits[start..end]),
+            new_start: count_new(&edits[..start]),
+            new_length: count_new(&edits[start..end]),
+            edits: edits[start..end].to_vec(),
+        })
+        .collect()
+}
+
+/// パスごとの変更前後の状態
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FileChange {
+    pub path: String,
+    pub old: Option<(BlobType, Hash)>,
+    pub new: Option<(BlobType, Hash)>,
+}
+
+/// 2 つの tree (を平坦にしたもの) の間で内容かモードが異なるパスを列挙する
+pub fn diff_files(old: &TreeFiles, new: &TreeFiles) -> Vec<FileChange> {
+    old.keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|path| {
+            let old = old.get(path);
+            let new = new.get(path);
+            (old != new).then(|| FileChange {
+                path: path.clone(),
+                old: old.cloned(),
+                new: new.cloned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(old: &[&[u8]], new: &[&[u8]], edits: &[Edit]) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let mut old_result = Vec::new();
+        let mut new_result = Vec::new();
+        for edit in edits {
+            match *edit {
+                Edit::Equal(i, j) => {
+                    assert_eq!(old[i], new[j]);
+                    old_result.push(old[i].to_vec());
+                    new_result.push(new[j].to_vec());
+                }
+                Edit::Delete(i) => old_result.push(old[i].to_vec()),
+                Edit::Insert(j) => new_result.push(new[j].to_vec()),
+            }
+        }
+        (old_result, new_result)
+    }
+
+    fn check(old: &str, new: &str, algorithm: Algorithm) -> Vec<Edit> {
+        let old_lines = split_lines(old.as_bytes());
+        let new_lines = split_lines(new.as_bytes());
+        let edits = diff_lines(&old_lines, &new_lines, algorithm);
+
+        let (old_result, new_result) = apply(&old_lines, &new_lines, &edits);
+        assert_eq!(old_result.concat(), old.as_bytes());
+        assert_eq!(new_result.concat(), new.as_bytes());
+
+        edits
+    }
+
+    #[test]
+    fn test_myers_minimal() {
+        let edits = check(
+            "a\nb\nc\na\nb\nb\na\n",
+            "c\nb\na\nb\na\nc\n",
+            Algorithm::Myers,
+        );
+
+        let changes = edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Equal(_, _)))
+            .count();
+        assert_eq!(changes, 5);
+    }
+
+    #[test]
+    fn test_diff_empty() {
+        for algorithm in [Algorithm::Myers, Algorithm::Patience] {
+            assert!(check("", "", algorithm).is_empty());
+            check("", "a\nb\n", algorithm);
+            check("a\nb\n", "", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_patience() {
+        let old = "fn a() {\n}\n\nfn b() {\n}\n";
+        let new = "fn b() {\n}\n\nfn a() {\n}\n\nfn c() {\n}\n";
+
+        check(old, new, Algorithm::Patience);
+    }
+
+    #[test]
+    fn test_hunks() {
+        let old = (1..=20)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let new = old
+            .replace("line 5\n", "line five\n")
+            .replace("line 18\n", "");
+        let old_lines = split_lines(old.as_bytes());
+        let new_lines = split_lines(new.as_bytes());
+        let edits = diff_lines(&old_lines, &new_lines, Algorithm::Myers);
+
+        let hunks = hunks(&edits, 3);
+
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].header(), "@@ -2,7 +2,7 @@");
+        assert_eq!(hunks[1].header(), "@@ -15,6 +15,5 @@");
+    }
+}
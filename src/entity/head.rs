@@ -1,10 +1,16 @@
 use std::{path::PathBuf, str::FromStr};
 
 use crate::consts::HEAD_PATH;
+use crate::error::{Error, Result};
 
-use super::{object::hash::Hash, refs::Ref};
-
-use anyhow::{anyhow, Result};
+use super::{
+    lock::LockFile,
+    object::{hash::Hash, GitObject, ObjectType},
+    ref_transaction::RefTransaction,
+    reflog::Reflog,
+    refs::Ref,
+    repository::Repository,
+};
 
 #[derive(Debug, Clone)]
 pub enum Head {
@@ -12,28 +18,70 @@ pub enum Head {
     Ref(Ref),
 }
 impl Head {
-    fn get_path() -> PathBuf {
-        PathBuf::from(HEAD_PATH)
+    fn get_path(repo: &Repository) -> PathBuf {
+        repo.git_path(HEAD_PATH)
     }
 
-    pub fn read() -> Result<Self> {
-        let content = std::fs::read_to_string(Self::get_path())?;
+    pub fn read(repo: &Repository) -> Result<Self> {
+        let content = std::fs::read_to_string(Self::get_path(repo))?;
         content.trim().parse()
     }
 
-    pub fn write(&self) -> Result<()> {
-        std::fs::write(Self::get_path(), self.to_string())?;
+    pub fn write(&self, repo: &Repository) -> Result<()> {
+        let mut lock = LockFile::acquire(&Self::get_path(repo))?;
+        lock.write(format!("{}\n", self).as_bytes())?;
+        lock.commit()
+    }
+
+    /// HEAD が branch を指していればその branch を、detached なら HEAD 自体を hash に進めて reflog に記録する
+    pub fn update(&self, repo: &Repository, hash: &Hash, message: &str) -> Result<()> {
+        let mut transaction = RefTransaction::new();
+        match self {
+            Head::Detached(_) => transaction.update(HEAD_PATH, hash.clone(), None, message),
+            Head::Ref(ref_) => transaction.update(&ref_.to_string(), hash.clone(), None, message),
+        }
+        transaction.commit(repo)
+    }
+
+    /// HEAD をこれに切り替え、前後の commit を HEAD の reflog に記録する
+    ///
+    /// commit のない branch に切り替えた場合は記録しない
+    pub fn switch(&self, repo: &Repository, message: &str) -> Result<()> {
+        let old = Head::read(repo)?.read_hash(repo)?;
+        self.write(repo)?;
+
+        if let Some(new) = self.read_hash(repo)? {
+            Reflog::record(repo, HEAD_PATH, old.as_ref(), Some(&new), message)?;
+        }
+
         Ok(())
     }
+
+    /// HEAD が指している commit の hash を返す (まだ commit がなければ None)
+    pub fn read_hash(&self, repo: &Repository) -> Result<Option<Hash>> {
+        match self {
+            Head::Detached(hash) => Ok(Some(hash.clone())),
+            Head::Ref(ref_) => ref_.read_commit_hash(repo),
+        }
+    }
+
+    pub fn read_tree_hash(&self, repo: &Repository) -> Result<Option<Hash>> {
+        let Some(hash) = self.read_hash(repo)? else {
+            return Ok(None);
+        };
+
+        let (hash, _) = GitObject::peel(repo, &hash, ObjectType::Tree)?;
+        Ok(Some(hash))
+    }
 }
 impl FromStr for Head {
-    type Err = anyhow::Error;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
         if s.starts_with("ref:") {
             let ref_name = s
                 .strip_prefix("ref: ")
-                .ok_or(anyhow!("Invalid ref: {}", s))?;
+                .ok_or(Error::InvalidRef(format!("Invalid ref: {}", s)))?;
             Ok(Head::Ref(ref_name.parse()?))
         } else {
             Ok(Head::Detached(s.parse()?))
@@ -0,0 +1,276 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    consts::GIT_DIRECTORY,
+    entity::{
+        config::{expand_home, home_directory, Config},
+        repository::Repository,
+        wildmatch::wildmatch,
+    },
+    error::Result,
+};
+
+const IGNORE_FILE_NAME: &str = ".gitignore";
+const EXCLUDE_PATH: &str = "info/exclude";
+
+/// ignore ファイルの 1 行
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IgnorePattern {
+    /// ファイルに書かれたままのパターン (`check-ignore -v` で表示する)
+    pub pattern: String,
+    glob: String,
+    pub negated: bool,
+    pub directory_only: bool,
+    /// `/` を含むパターンは ignore ファイルのあるディレクトリからのパスと比べる
+    anchored: bool,
+    /// ignore ファイルのあるディレクトリ (`dir/` の形、root や exclude ファイルなら空)
+    pub base: String,
+    pub source: String,
+    pub line_number: usize,
+}
+impl IgnorePattern {
+    /// 空行やコメントなら None
+    pub fn parse(line: &str, base: &str, source: &str, line_number: usize) -> Option<Self> {
+        let line = trim_trailing_spaces(line);
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, rest) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (directory_only, rest) = match rest.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let anchored = rest.contains('/');
+        let rest = rest.strip_prefix('/').unwrap_or(rest);
+        // `\#` や `\!` は先頭の 1 文字だけ特別扱いを外す
+        let glob = match rest.strip_prefix('\\') {
+            Some(escaped) if escaped.starts_with(['#', '!']) => escaped,
+            _ => rest,
+        };
+        if glob.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            pattern: line.to_string(),
+            glob: glob.to_string(),
+            negated,
+            directory_only,
+            anchored,
+            base: base.to_string(),
+            source: source.to_string(),
+            line_number,
+        })
+    }
+
+    /// 作業ツリーの root からのパスがこのパターンに一致するか (否定かどうかは見ない)
+    pub fn matches(&self, path: &str, is_directory: bool) -> bool {
+        if self.directory_only && !is_directory {
+            return false;
+        }
+        let Some(relative) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+
+        if self.anchored {
+            wildmatch(self.glob.as_bytes(), relative.as_bytes(), true)
+        } else {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            wildmatch(self.glob.as_bytes(), name.as_bytes(), true)
+        }
+    }
+}
+
+/// 1 つの ignore ファイルの中身
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct IgnoreFile {
+    pub patterns: Vec<IgnorePattern>,
+}
+impl IgnoreFile {
+    pub fn parse(content: &str, base: &str, source: &str) -> Self {
+        let patterns = content
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| IgnorePattern::parse(line, base, source, i + 1))
+            .collect();
+
+        Self { patterns }
+    }
+
+    /// ファイルがなければ空として扱う
+    pub fn read(path: &Path, base: &str, source: &str) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(content) => Ok(Self::parse(
+                &String::from_utf8_lossy(&content),
+                base,
+                source,
+            )),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// 後に書かれたパターンが優先される
+    fn last_match(&self, path: &str, is_directory: bool) -> Option<&IgnorePattern> {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| pattern.matches(path, is_directory))
+    }
+}
+
+/// `core.excludesFile`, `.git/info/exclude`, 各ディレクトリの `.gitignore` をまとめたもの
+#[derive(Debug)]
+pub struct Ignore {
+    work_tree: PathBuf,
+    /// 優先度の低い順
+    excludes: Vec<IgnoreFile>,
+    /// ディレクトリ (`dir/` の形、root は空) ごとの `.gitignore` を必要になった時に読む
+    directories: RefCell<HashMap<String, IgnoreFile>>,
+}
+impl Ignore {
+    pub fn read(repo: &Repository) -> Result<Self> {
+        let config = Config::read(Some(repo))?;
+        let excludes_file = match config.get("core.excludesFile") {
+            Some(path) => expand_home(path),
+            None => match std::env::var_os("XDG_CONFIG_HOME").filter(|xdg| !xdg.is_empty()) {
+                Some(xdg) => Some(PathBuf::from(xdg).join("git/ignore")),
+                None => home_directory().map(|home| home.join(".config/git/ignore")),
+            },
+        };
+
+        let mut excludes = Vec::new();
+        if let Some(path) = excludes_file {
+            excludes.push(IgnoreFile::read(&path, "", &path.to_string_lossy())?);
+        }
+        let exclude_source = format!("{}/{}", GIT_DIRECTORY, EXCLUDE_PATH);
+        excludes.push(IgnoreFile::read(
+            &repo.git_path(EXCLUDE_PATH),
+            "",
+            &exclude_source,
+        )?);
+
+        Ok(Self {
+            work_tree: repo.work_tree()?.to_path_buf(),
+            excludes,
+            directories: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// 無視されるかどうかを決めたパターンを返す (否定パターンなら無視されない)。
+    /// 親ディレクトリが無視されていれば中身も無視される
+    pub fn check(&self, path: &str, is_directory: bool) -> Result<Option<IgnorePattern>> {
+        let path = path.trim_end_matches('/');
+        for (i, _) in path.match_indices('/') {
+            if let Some(pattern) = self.check_one(&path[..i], true)? {
+                if !pattern.negated {
+                    return Ok(Some(pattern));
+                }
+            }
+        }
+
+        self.check_one(path, is_directory)
+    }
+
+    pub fn is_ignored(&self, path: &str, is_directory: bool) -> Result<bool> {
+        Ok(self
+            .check(path, is_directory)?
+            .is_some_and(|pattern| !pattern.negated))
+    }
+
+    /// 深いディレクトリの `.gitignore` ほど優先される
+    fn check_one(&self, path: &str, is_directory: bool) -> Result<Option<IgnorePattern>> {
+        let mut directories = vec![String::new()];
+        for (i, _) in path.match_indices('/') {
+            directories.push(format!("{}/", &path[..i]));
+        }
+
+        for directory in directories.iter().rev() {
+            self.load_directory(directory)?;
+            let loaded = self.directories.borrow();
+            if let Some(pattern) = loaded[directory].last_match(path, is_directory) {
+                return Ok(Some(pattern.clone()));
+            }
+        }
+
+        Ok(self
+            .excludes
+            .iter()
+            .rev()
+            .find_map(|file| file.last_match(path, is_directory))
+            .cloned())
+    }
+
+    fn load_directory(&self, directory: &str) -> Result<()> {
+        if self.directories.borrow().contains_key(directory) {
+            return Ok(());
+        }
+
+        let source = format!("{}{}", directory, IGNORE_FILE_NAME);
+        let file = IgnoreFile::read(&self.work_tree.join(&source), directory, &source)?;
+        self.directories
+            .borrow_mut()
+            .insert(directory.to_string(), file);
+
+        Ok(())
+    }
+}
+
+/// 行末の空白は `\` でエスケープされていなければ取り除く
+fn trim_trailing_spaces(line: &str) -> &str {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let mut end = line.len();
+    while line[..end].ends_with(' ') {
+        if line[..end - 1].ends_with('\\') {
+            break;
+        }
+        end -= 1;
+    }
+
+    &line[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(line: &str, base: &str) -> IgnorePattern {
+        IgnorePattern::parse(line, base, ".gitignore", 1).unwrap()
+    }
+
+    #[test]
+    fn test_pattern() {
+        assert!(pattern("*.log", "").matches("dir/debug.log", false));
+        assert!(pattern("/build", "").matches("build", true));
+        assert!(!pattern("/build", "").matches("dir/build", true));
+        assert!(pattern("doc/*.txt", "").matches("doc/a.txt", false));
+        assert!(!pattern("doc/*.txt", "").matches("x/doc/a.txt", false));
+        assert!(pattern("target/", "").matches("target", true));
+        assert!(!pattern("target/", "").matches("target", false));
+        assert!(pattern("/only", "sub/").matches("sub/only", false));
+        assert!(!pattern("/only", "sub/").matches("only", false));
+        assert!(pattern("!keep.log", "").negated);
+        assert!(pattern("\\!bang", "").matches("!bang", false));
+        assert!(pattern("trailing\\ ", "").matches("trailing ", false));
+        assert_eq!(IgnorePattern::parse("# comment", "", "", 1), None);
+        assert_eq!(IgnorePattern::parse("   ", "", "", 1), None);
+    }
+
+    #[test]
+    fn test_last_match_wins() {
+        let file = IgnoreFile::parse("*.log\n!keep.log\n", "", ".gitignore");
+        assert!(!file.last_match("a.log", false).unwrap().negated);
+        let keep = file.last_match("keep.log", false).unwrap();
+        assert!(keep.negated);
+        assert_eq!(keep.line_number, 2);
+        assert_eq!(file.last_match("a.txt", false), None);
+    }
+}
@@ -1,89 +1,268 @@
-use std::{collections::HashMap, os::unix::fs::MetadataExt, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    os::unix::fs::MetadataExt,
+};
 
 use crate::{
     consts::GIT_INDEX_PATH,
-    entity::object::{hash::Hash, mode::Mode},
+    entity::{
+        object::{hash::Hash, mode::Mode},
+        repository::Repository,
+    },
+    error::{Error, Result},
 };
-use anyhow::Result;
 use byteorder::{BigEndian, ByteOrder};
 
+const SIGNATURE: &[u8] = b"DIRC";
+const HEADER_LENGTH: usize = 12;
+const SHA1_LENGTH: usize = 20;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Index {
     pub version: u32,
     pub entries: Vec<IndexEntry>,
+    /// `TREE` 拡張 (entry から作った tree の hash のキャッシュ)
+    pub cache_tree: Option<CacheTree>,
+    /// `REUC` 拡張 (解消した衝突の元の stage)
+    pub resolve_undo: Vec<ResolveUndo>,
+    /// 解釈できないが無視してよい拡張は、entry を変えない限り署名と中身をそのまま書き戻す
+    pub unknown_extensions: Vec<([u8; 4], Vec<u8>)>,
+}
+impl Default for Index {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl Index {
     pub fn new() -> Self {
         Self {
             version: 2,
             entries: Vec::new(),
+            cache_tree: None,
+            resolve_undo: Vec::new(),
+            unknown_extensions: Vec::new(),
         }
     }
 
-    pub fn read() -> Result<Option<Self>> {
-        if !Path::new(GIT_INDEX_PATH).try_exists().unwrap_or(true) {
+    pub fn read(repo: &Repository) -> Result<Option<Self>> {
+        let path = repo.git_path(GIT_INDEX_PATH);
+        if !path.try_exists().unwrap_or(true) {
             return Ok(None);
         }
-        let bytes = std::fs::read(GIT_INDEX_PATH)?;
+        let bytes = std::fs::read(path)?;
         Self::from_raw(&bytes).map(Some)
     }
 
-    pub fn write(&self) -> Result<()> {
+    pub fn write(&self, repo: &Repository) -> Result<()> {
         let bytes = self.to_raw();
-        std::fs::write(GIT_INDEX_PATH, bytes)?;
+        std::fs::write(repo.git_path(GIT_INDEX_PATH), bytes)?;
         Ok(())
     }
 
     pub fn from_raw(bytes: &[u8]) -> Result<Self> {
-        let mut shown_index = 0;
-
-        assert_eq!(bytes[..4], b"DIRC"[..]);
-
+        if bytes.len() < HEADER_LENGTH + SHA1_LENGTH {
+            return Err(Error::CorruptIndex(
+                "index file smaller than expected".to_string(),
+            ));
+        }
+        if &bytes[..4] != SIGNATURE {
+            return Err(Error::CorruptIndex("bad signature in index".to_string()));
+        }
         let version = BigEndian::read_u32(&bytes[4..8]);
+        if !(2..=4).contains(&version) {
+            return Err(Error::CorruptIndex(format!(
+                "bad index version {}",
+                version
+            )));
+        }
 
-        let entry_count = BigEndian::read_u32(&bytes[8..12]) as usize;
+        // index.skipHash で書かれた index は checksum が 0 埋めになっている
+        let trailer_start = bytes.len() - SHA1_LENGTH;
+        let checksum = &bytes[trailer_start..];
+        if checksum.iter().any(|&b| b != 0)
+            && Hash::hash_bytes(&bytes[..trailer_start]).to_raw() != checksum
+        {
+            return Err(Error::CorruptIndex(
+                "index file corrupt (bad checksum)".to_string(),
+            ));
+        }
+        let bytes = &bytes[..trailer_start];
 
-        dbg!(version, entry_count);
+        let entry_count = BigEndian::read_u32(&bytes[8..12]) as usize;
+        let mut shown_index = HEADER_LENGTH;
 
         let mut entries = Vec::with_capacity(entry_count);
-
-        shown_index += 12;
-
+        let mut previous_name = String::new();
         for _ in 0..entry_count {
-            let entry = IndexEntry::from_raw(bytes, &mut shown_index)?;
+            let entry = IndexEntry::from_raw(bytes, &mut shown_index, version, &previous_name)?;
+            previous_name = entry.file_name.clone();
             entries.push(entry);
         }
 
-        Ok(Self { version, entries })
+        let mut index = Self {
+            version,
+            entries,
+            ..Self::new()
+        };
+
+        while shown_index < bytes.len() {
+            if shown_index + 8 > bytes.len() {
+                return Err(Error::CorruptIndex(
+                    "index file corrupt (truncated extension)".to_string(),
+                ));
+            }
+            let signature: [u8; 4] = bytes[shown_index..shown_index + 4].try_into()?;
+            let size = BigEndian::read_u32(&bytes[shown_index + 4..shown_index + 8]) as usize;
+            shown_index += 8;
+            let data = bytes
+                .get(shown_index..shown_index + size)
+                .ok_or(Error::CorruptIndex(
+                    "index file corrupt (truncated extension)".to_string(),
+                ))?;
+            shown_index += size;
+
+            match &signature {
+                b"TREE" => index.cache_tree = CacheTree::from_raw(data)?,
+                b"REUC" => index.resolve_undo = ResolveUndo::from_raw(data)?,
+                // entry の位置を記録したものなので書き直すときには使えない
+                b"EOIE" | b"IEOT" => {}
+                // 大文字で始まる拡張は読み飛ばしてよい
+                [b'A'..=b'Z', ..] => index.unknown_extensions.push((signature, data.to_vec())),
+                _ => {
+                    return Err(Error::CorruptIndex(format!(
+                        "index uses {} extension, which we do not understand",
+                        String::from_utf8_lossy(&signature)
+                    )))
+                }
+            }
+        }
+
+        Ok(index)
     }
 
     pub fn to_raw(&self) -> Vec<u8> {
+        // extended flags を使う entry があれば version 3 以上が必要
+        let version = if self.entries.iter().any(IndexEntry::is_extended) {
+            self.version.max(3)
+        } else {
+            self.version
+        };
+
         let mut bytes = Vec::new();
 
-        bytes.extend_from_slice(b"DIRC");
-        bytes.extend_from_slice(&self.version.to_be_bytes());
+        bytes.extend_from_slice(SIGNATURE);
+        bytes.extend_from_slice(&version.to_be_bytes());
         bytes.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
 
+        let mut previous_name = "";
         for entry in &self.entries {
-            bytes.extend_from_slice(&entry.to_raw());
+            bytes.extend_from_slice(&entry.to_raw(version, previous_name));
+            previous_name = &entry.file_name;
+        }
+
+        let mut extensions = Vec::new();
+        if let Some(cache_tree) = &self.cache_tree {
+            let mut data = Vec::new();
+            cache_tree.to_raw(&mut data);
+            extensions.push((*b"TREE", data));
+        }
+        if !self.resolve_undo.is_empty() {
+            extensions.push((*b"REUC", ResolveUndo::to_raw(&self.resolve_undo)));
+        }
+        extensions.extend(self.unknown_extensions.iter().cloned());
+        for (signature, data) in extensions {
+            bytes.extend_from_slice(&signature);
+            bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&data);
         }
 
+        let checksum = Hash::hash_bytes(&bytes).to_raw();
+        bytes.extend_from_slice(&checksum);
+
         bytes
     }
 
+    /// 同じパスの entry は stage に関わらず置き換える (stage 0 を入れれば衝突は解消される)
     pub fn insert(&mut self, entries: &[IndexEntry]) {
-        let mut hash_map = self.hash_map();
+        let file_names = entries
+            .iter()
+            .map(|entry| entry.file_name.as_str())
+            .collect::<HashSet<_>>();
 
         for entry in entries {
-            hash_map.insert(entry.file_name.as_str(), entry);
+            if entry.stage == 0 {
+                self.record_resolve_undo(&entry.file_name);
+            } else {
+                self.resolve_undo
+                    .retain(|resolve_undo| resolve_undo.path != entry.file_name);
+            }
+            self.invalidate(&entry.file_name);
         }
 
-        self.entries = hash_map.into_values().cloned().collect::<Vec<_>>();
+        let mut new_entries = self
+            .entries
+            .iter()
+            .filter(|entry| !file_names.contains(entry.file_name.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        new_entries.extend_from_slice(entries);
+
+        self.entries = new_entries;
         self.sort();
     }
 
     fn sort(&mut self) {
-        self.entries.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        self.entries
+            .sort_by(|a, b| (&a.file_name, a.stage).cmp(&(&b.file_name, b.stage)));
+    }
+
+    /// パスに対応する entry を stage に関わらず取り除く
+    pub fn remove(&mut self, file_name: &str) {
+        self.record_resolve_undo(file_name);
+        self.invalidate(file_name);
+        self.entries.retain(|entry| entry.file_name != file_name);
+    }
+
+    /// 衝突中の entry を取り除く前に、元の stage を REUC に残しておく
+    fn record_resolve_undo(&mut self, file_name: &str) {
+        let mut stages = [None, None, None];
+        for entry in &self.entries {
+            if entry.file_name == file_name && entry.stage != 0 {
+                stages[entry.stage as usize - 1] = Some((entry.mode, entry.hash.clone()));
+            }
+        }
+        if stages.iter().all(Option::is_none) {
+            return;
+        }
+
+        self.resolve_undo
+            .retain(|resolve_undo| resolve_undo.path != file_name);
+        self.resolve_undo.push(ResolveUndo {
+            path: file_name.to_string(),
+            stages,
+        });
+        self.resolve_undo.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    /// entry が変わるパスの TREE を無効にし、中身を更新できない拡張は捨てる
+    fn invalidate(&mut self, file_name: &str) {
+        if let Some(cache_tree) = &mut self.cache_tree {
+            cache_tree.invalidate(file_name);
+        }
+        self.unknown_extensions.clear();
+    }
+
+    /// stage 1-3 の entry を持つ (衝突が解消されていない) パス
+    pub fn conflicted_paths(&self) -> Vec<&str> {
+        let mut paths = self
+            .entries
+            .iter()
+            .filter(|entry| entry.stage != 0)
+            .map(|entry| entry.file_name.as_str())
+            .collect::<Vec<_>>();
+        paths.dedup();
+
+        paths
     }
 
     pub fn hash_map(&self) -> HashMap<&str, &IndexEntry> {
@@ -94,6 +273,17 @@ impl Index {
     }
 }
 
+/// flags のうち assume-valid を表す bit
+const ASSUME_VALID: u16 = 0x8000;
+/// flags のうち extended flags が続くことを表す bit (version 3 以上)
+const EXTENDED: u16 = 0x4000;
+/// flags のうちファイル名の長さを表す部分
+const NAME_MASK: u16 = 0x0fff;
+/// extended flags の skip-worktree
+const SKIP_WORKTREE: u16 = 0x4000;
+/// extended flags の intent-to-add (`git add -N`)
+const INTENT_TO_ADD: u16 = 0x2000;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IndexEntry {
     pub created_at: u32,
@@ -107,6 +297,12 @@ pub struct IndexEntry {
     pub group_id: u32,
     pub size: u32,
     pub hash: Hash,
+    /// 0 は通常、1, 2, 3 はマージの衝突中の base, ours, theirs
+    pub stage: u8,
+    /// 作業ツリーのファイルを見ずに変更がないものとして扱う
+    pub assume_valid: bool,
+    pub skip_worktree: bool,
+    pub intent_to_add: bool,
     pub file_name: String,
 }
 impl IndexEntry {
@@ -123,6 +319,10 @@ impl IndexEntry {
             group_id: 0,
             size: 0,
             hash,
+            stage: 0,
+            assume_valid: false,
+            skip_worktree: false,
+            intent_to_add: false,
             file_name: file_name.to_string(),
         }
     }
@@ -155,12 +355,31 @@ impl IndexEntry {
             group_id,
             size,
             hash,
+            stage: 0,
+            assume_valid: false,
+            skip_worktree: false,
+            intent_to_add: false,
             file_name: file_name.to_string(),
         })
     }
 
-    pub fn from_raw(bytes: &[u8], shown_index: &mut usize) -> Result<Self> {
+    fn is_extended(&self) -> bool {
+        self.skip_worktree || self.intent_to_add
+    }
+
+    /// version 4 ではファイル名を直前の entry との差分 (削る長さと付け足す部分) で表す
+    pub fn from_raw(
+        bytes: &[u8],
+        shown_index: &mut usize,
+        version: u32,
+        previous_name: &str,
+    ) -> Result<Self> {
         let bytes = &bytes[*shown_index..];
+        if bytes.len() < 62 {
+            return Err(Error::CorruptIndex(
+                "index file corrupt (truncated entry)".to_string(),
+            ));
+        }
         let created_at = BigEndian::read_u32(&bytes[..4]);
         let created_at_nsec = BigEndian::read_u32(&bytes[4..8]);
         let updated_at = BigEndian::read_u32(&bytes[8..12]);
@@ -173,14 +392,65 @@ impl IndexEntry {
         let size = BigEndian::read_u32(&bytes[36..40]);
         let hash = Hash::from_raw(&bytes[40..60])?;
 
-        let file_name_length = BigEndian::read_u16(&bytes[60..62]) as usize;
-        let file_name = std::str::from_utf8(&bytes[62..62 + file_name_length])?.to_string();
-
-        let entry_length = 62 + file_name_length;
-        let padding = 8 - (entry_length % 8);
-
-        let entry_length = entry_length + padding;
-        *shown_index += entry_length;
+        let flags = BigEndian::read_u16(&bytes[60..62]);
+        let stage = ((flags >> 12) & 0b11) as u8;
+        let mut position = 62;
+
+        let extended_flags = if flags & EXTENDED != 0 {
+            if version < 3 {
+                return Err(Error::CorruptIndex(format!(
+                    "index file corrupt (extended flags in version {})",
+                    version
+                )));
+            }
+            let extended_flags = BigEndian::read_u16(bytes.get(62..64).ok_or(
+                Error::CorruptIndex("index file corrupt (truncated entry)".to_string()),
+            )?);
+            position += 2;
+            extended_flags
+        } else {
+            0
+        };
+
+        let file_name = if version >= 4 {
+            let strip = read_varint(bytes, &mut position)?;
+            let prefix = previous_name
+                .len()
+                .checked_sub(strip)
+                .and_then(|length| previous_name.get(..length))
+                .ok_or(Error::CorruptIndex(
+                    "index file corrupt (bad path prefix)".to_string(),
+                ))?;
+            let suffix = read_until(bytes, &mut position, 0)?;
+
+            // version 4 では padding がない
+            *shown_index += position;
+            format!("{}{}", prefix, std::str::from_utf8(suffix)?)
+        } else {
+            let file_name_length = match (flags & NAME_MASK) as usize {
+                // 長すぎる名前は NUL 終端を探す
+                length if length == NAME_MASK as usize => bytes[position..]
+                    .iter()
+                    .position(|&b| b == 0)
+                    .ok_or(Error::CorruptIndex(
+                        "Unterminated file name in index".to_string(),
+                    ))?,
+                length => length,
+            };
+            let file_name =
+                bytes
+                    .get(position..position + file_name_length)
+                    .ok_or(Error::CorruptIndex(
+                        "index file corrupt (truncated entry)".to_string(),
+                    ))?;
+            let file_name = std::str::from_utf8(file_name)?.to_string();
+
+            let entry_length = position + file_name_length;
+            let padding = 8 - (entry_length % 8);
+
+            *shown_index += entry_length + padding;
+            file_name
+        };
 
         Ok(Self {
             created_at,
@@ -194,11 +464,15 @@ impl IndexEntry {
             group_id,
             size,
             hash,
+            stage,
+            assume_valid: flags & ASSUME_VALID != 0,
+            skip_worktree: extended_flags & SKIP_WORKTREE != 0,
+            intent_to_add: extended_flags & INTENT_TO_ADD != 0,
             file_name,
         })
     }
 
-    pub fn to_raw(&self) -> Vec<u8> {
+    pub fn to_raw(&self, version: u32, previous_name: &str) -> Vec<u8> {
         let mut bytes = Vec::new();
 
         bytes.extend_from_slice(&self.created_at.to_be_bytes());
@@ -212,12 +486,340 @@ impl IndexEntry {
         bytes.extend_from_slice(&self.group_id.to_be_bytes());
         bytes.extend_from_slice(&self.size.to_be_bytes());
         bytes.extend_from_slice(&self.hash.to_raw());
-        bytes.extend_from_slice(&(self.file_name.len() as u16).to_be_bytes());
-        bytes.extend_from_slice(self.file_name.as_bytes());
 
-        let padding = 8 - (bytes.len() % 8);
-        bytes.extend_from_slice(&vec![0; padding]);
+        let mut flags =
+            ((self.stage as u16) << 12) | (self.file_name.len().min(NAME_MASK as usize) as u16);
+        if self.assume_valid {
+            flags |= ASSUME_VALID;
+        }
+        if self.is_extended() {
+            flags |= EXTENDED;
+        }
+        bytes.extend_from_slice(&flags.to_be_bytes());
+        if self.is_extended() {
+            let mut extended_flags = 0u16;
+            if self.skip_worktree {
+                extended_flags |= SKIP_WORKTREE;
+            }
+            if self.intent_to_add {
+                extended_flags |= INTENT_TO_ADD;
+            }
+            bytes.extend_from_slice(&extended_flags.to_be_bytes());
+        }
+
+        if version >= 4 {
+            let common = previous_name
+                .bytes()
+                .zip(self.file_name.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            write_varint(&mut bytes, previous_name.len() - common);
+            bytes.extend_from_slice(&self.file_name.as_bytes()[common..]);
+            bytes.push(0);
+        } else {
+            bytes.extend_from_slice(self.file_name.as_bytes());
+
+            let padding = 8 - (bytes.len() % 8);
+            bytes.extend_from_slice(&vec![0; padding]);
+        }
 
         bytes
     }
 }
+
+/// `TREE` 拡張の 1 ディレクトリ分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheTree {
+    /// 親ディレクトリからの相対的な名前 (root は空)
+    pub name: String,
+    /// 配下の entry 数とその tree の hash (無効化されていれば None)
+    pub valid: Option<(usize, Hash)>,
+    pub children: Vec<CacheTree>,
+}
+impl CacheTree {
+    fn from_raw(bytes: &[u8]) -> Result<Option<Self>> {
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+
+        let mut shown_index = 0;
+        let cache_tree = Self::read_node(bytes, &mut shown_index)?;
+        if shown_index != bytes.len() {
+            return Err(Error::CorruptIndex(
+                "index file corrupt (bad TREE extension)".to_string(),
+            ));
+        }
+
+        Ok(Some(cache_tree))
+    }
+
+    fn read_node(bytes: &[u8], shown_index: &mut usize) -> Result<Self> {
+        let name = std::str::from_utf8(read_until(bytes, shown_index, 0)?)?.to_string();
+        let entry_count: i64 =
+            std::str::from_utf8(read_until(bytes, shown_index, b' ')?)?.parse()?;
+        let subtree_count: usize =
+            std::str::from_utf8(read_until(bytes, shown_index, b'\n')?)?.parse()?;
+
+        // 無効化されたものは entry 数が -1 になっていて hash を持たない
+        let valid =
+            if entry_count >= 0 {
+                let hash = bytes.get(*shown_index..*shown_index + SHA1_LENGTH).ok_or(
+                    Error::CorruptIndex("index file corrupt (bad TREE extension)".to_string()),
+                )?;
+                *shown_index += SHA1_LENGTH;
+                Some((entry_count as usize, Hash::from_raw(hash)?))
+            } else {
+                None
+            };
+
+        let children = (0..subtree_count)
+            .map(|_| Self::read_node(bytes, shown_index))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            name,
+            valid,
+            children,
+        })
+    }
+
+    fn to_raw(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes.push(0);
+        let entry_count = match &self.valid {
+            Some((entry_count, _)) => entry_count.to_string(),
+            None => "-1".to_string(),
+        };
+        bytes.extend_from_slice(format!("{} {}\n", entry_count, self.children.len()).as_bytes());
+        if let Some((_, hash)) = &self.valid {
+            bytes.extend_from_slice(&hash.to_raw());
+        }
+
+        for child in &self.children {
+            child.to_raw(bytes);
+        }
+    }
+
+    /// path を含むディレクトリのキャッシュを無効にする
+    fn invalidate(&mut self, path: &str) {
+        self.valid = None;
+
+        match path.split_once('/') {
+            Some((name, rest)) => {
+                if let Some(child) = self.children.iter_mut().find(|child| child.name == name) {
+                    child.invalidate(rest);
+                }
+            }
+            // ディレクトリがファイルで置き換えられた場合
+            None => self.children.retain(|child| child.name != path),
+        }
+    }
+}
+
+/// `REUC` 拡張の 1 パス分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveUndo {
+    pub path: String,
+    /// 衝突していたときの stage 1, 2, 3
+    pub stages: [Option<(Mode, Hash)>; 3],
+}
+impl ResolveUndo {
+    fn from_raw(bytes: &[u8]) -> Result<Vec<Self>> {
+        let mut ret = Vec::new();
+        let mut shown_index = 0;
+
+        while shown_index < bytes.len() {
+            let path = std::str::from_utf8(read_until(bytes, &mut shown_index, 0)?)?.to_string();
+
+            let mut modes = [0; 3];
+            for mode in modes.iter_mut() {
+                let raw = std::str::from_utf8(read_until(bytes, &mut shown_index, 0)?)?;
+                *mode = u32::from_str_radix(raw, 8)?;
+            }
+
+            let mut stages = [None, None, None];
+            for (stage, mode) in stages.iter_mut().zip(modes) {
+                if mode == 0 {
+                    continue;
+                }
+                let hash = bytes.get(shown_index..shown_index + SHA1_LENGTH).ok_or(
+                    Error::CorruptIndex("index file corrupt (bad REUC extension)".to_string()),
+                )?;
+                shown_index += SHA1_LENGTH;
+                *stage = Some((Mode::try_from(mode)?, Hash::from_raw(hash)?));
+            }
+
+            ret.push(Self { path, stages });
+        }
+
+        Ok(ret)
+    }
+
+    fn to_raw(resolve_undo: &[Self]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        for entry in resolve_undo {
+            bytes.extend_from_slice(entry.path.as_bytes());
+            bytes.push(0);
+            for stage in &entry.stages {
+                let mode = stage.as_ref().map_or(0, |(mode, _)| u32::from(*mode));
+                bytes.extend_from_slice(format!("{:o}", mode).as_bytes());
+                bytes.push(0);
+            }
+            for (_, hash) in entry.stages.iter().flatten() {
+                bytes.extend_from_slice(&hash.to_raw());
+            }
+        }
+
+        bytes
+    }
+}
+
+/// delimiter までを読み、delimiter の次に進める
+fn read_until<'a>(bytes: &'a [u8], shown_index: &mut usize, delimiter: u8) -> Result<&'a [u8]> {
+    let rest = bytes.get(*shown_index..).unwrap_or_default();
+    let length = rest
+        .iter()
+        .position(|&b| b == delimiter)
+        .ok_or(Error::CorruptIndex(
+            "index file corrupt (unterminated field)".to_string(),
+        ))?;
+    *shown_index += length + 1;
+
+    Ok(&rest[..length])
+}
+
+/// pack の OFS_DELTA と同じ可変長整数
+fn read_varint(bytes: &[u8], shown_index: &mut usize) -> Result<usize> {
+    let mut next = || {
+        let byte = *bytes.get(*shown_index).ok_or(Error::CorruptIndex(
+            "index file corrupt (truncated entry)".to_string(),
+        ))?;
+        *shown_index += 1;
+        Ok::<_, anyhow::Error>(byte)
+    };
+
+    let mut byte = next()?;
+    let mut value = (byte & 0x7f) as usize;
+    while byte & 0x80 != 0 {
+        byte = next()?;
+        value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+    }
+
+    Ok(value)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, value: usize) {
+    let mut value = value;
+    let mut encoded = vec![(value & 0x7f) as u8];
+    value >>= 7;
+
+    while value > 0 {
+        value -= 1;
+        encoded.push(0x80 | (value & 0x7f) as u8);
+        value >>= 7;
+    }
+    encoded.reverse();
+
+    bytes.extend(encoded);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::object::mode::BlobType;
+
+    fn entry(file_name: &str) -> IndexEntry {
+        IndexEntry::with_default(
+            Mode::Blob(BlobType::Plain),
+            Hash::hash_bytes(file_name.as_bytes()),
+            file_name,
+        )
+    }
+
+    fn index(version: u32) -> Index {
+        let mut index = Index::new();
+        index.version = version;
+        index.insert(&[
+            entry("a"),
+            entry("dir/file"),
+            entry("dir/file2"),
+            entry("dir/other/long-name"),
+            entry("z"),
+        ]);
+        index
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for version in 2..=4 {
+            let index = index(version);
+            assert_eq!(Index::from_raw(&index.to_raw()).unwrap(), index);
+        }
+    }
+
+    #[test]
+    fn test_extended_flags_and_extensions() {
+        let mut index = index(4);
+        index.entries[1].skip_worktree = true;
+        index.entries[2].intent_to_add = true;
+        index.entries[3].assume_valid = true;
+        index.cache_tree = Some(CacheTree {
+            name: "".to_string(),
+            valid: Some((5, Hash::hash_bytes(b"root"))),
+            children: vec![CacheTree {
+                name: "dir".to_string(),
+                valid: Some((3, Hash::hash_bytes(b"dir"))),
+                children: Vec::new(),
+            }],
+        });
+
+        let mut conflicted = entry("a");
+        conflicted.stage = 2;
+        index.insert(&[conflicted]);
+        index.insert(&[entry("a")]);
+        assert_eq!(index.resolve_undo.len(), 1);
+        assert!(index.resolve_undo[0].stages[1].is_some());
+        assert_eq!(index.cache_tree.as_ref().unwrap().valid, None);
+        index.unknown_extensions.push((*b"UNTR", vec![1, 2, 3]));
+
+        assert_eq!(Index::from_raw(&index.to_raw()).unwrap(), index);
+    }
+
+    #[test]
+    fn test_unknown_extensions_dropped_on_change() {
+        let mut index = index(2);
+        index.unknown_extensions.push((*b"UNTR", vec![1, 2, 3]));
+        index.unknown_extensions.push((*b"FSMN", vec![4, 5, 6]));
+        let mut read = Index::from_raw(&index.to_raw()).unwrap();
+        assert_eq!(read.unknown_extensions.len(), 2);
+
+        read.insert(&[entry("new")]);
+        assert!(read.unknown_extensions.is_empty());
+        assert!(Index::from_raw(&read.to_raw())
+            .unwrap()
+            .unknown_extensions
+            .is_empty());
+
+        let mut read = Index::from_raw(&index.to_raw()).unwrap();
+        read.remove("a");
+        assert!(read.unknown_extensions.is_empty());
+    }
+
+    #[test]
+    fn test_bad_checksum() {
+        let mut bytes = index(2).to_raw();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(Index::from_raw(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 255, 16511, 16512, 1 << 20] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value);
+            assert_eq!(read_varint(&bytes, &mut 0).unwrap(), value);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::error::{Error, Result};
+
+const LOCK_SUFFIX: &str = ".lock";
+
+/// `<path>.lock` を排他的に作り、書き終えたら元のパスに rename して置き換える
+///
+/// commit せずに drop したら lock ファイルを消して何も変更しない
+#[derive(Debug)]
+pub struct LockFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+    file: Option<File>,
+}
+impl LockFile {
+    /// 既に lock ファイルがあれば他のプロセスが更新中として LockContention を返す
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(LOCK_SUFFIX);
+        let lock_path = PathBuf::from(lock_path);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = match File::options()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(Error::LockContention(lock_path));
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            lock_path,
+            file: Some(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, content: &[u8]) -> Result<()> {
+        let file = self.file.as_mut().expect("lock file is open until commit");
+        file.write_all(content)?;
+
+        Ok(())
+    }
+
+    /// 書いた内容で元のファイルを置き換える
+    pub fn commit(mut self) -> Result<()> {
+        if let Some(file) = self.file.take() {
+            file.sync_all()?;
+        }
+        std::fs::rename(&self.lock_path, &self.path)?;
+
+        Ok(())
+    }
+
+    /// 元のファイルを消す (lock は消すまで持ったままにする)
+    pub fn delete(self) -> Result<()> {
+        if self.path.symlink_metadata().is_ok() {
+            std::fs::remove_file(&self.path)?;
+        }
+
+        Ok(())
+    }
+}
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        self.file.take();
+        let _ = std::fs::remove_file(&self.lock_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_file() {
+        let directory =
+            std::env::temp_dir().join(format!("ssgit-lock-test-{}", std::process::id()));
+        let path = directory.join("refs/heads/main");
+
+        let mut lock = LockFile::acquire(&path).unwrap();
+        assert!(matches!(
+            LockFile::acquire(&path),
+            Err(Error::LockContention(lock_path)) if lock_path == directory.join("refs/heads/main.lock")
+        ));
+        lock.write(b"content\n").unwrap();
+        lock.commit().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "content\n");
+
+        // commit しなければ元のファイルはそのまま残る
+        let mut lock = LockFile::acquire(&path).unwrap();
+        lock.write(b"discarded\n").unwrap();
+        drop(lock);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "content\n");
+        assert!(!directory.join("refs/heads/main.lock").exists());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,674 @@
+use std::{collections::BTreeSet, ops::Range, path::Path, str::FromStr};
+
+use anyhow::Result;
+
+use crate::{
+    consts::{MERGE_HEAD_PATH, MERGE_MODE_PATH, MERGE_MSG_PATH},
+    entity::{
+        diff::{self, Algorithm, Edit},
+        object::{hash::Hash, mode::BlobType, GitObject, ObjectType},
+        repository::Repository,
+        worktree::TreeFiles,
+    },
+};
+
+const MARKER_LENGTH: usize = 7;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ConflictStyle {
+    #[default]
+    Merge,
+    Diff3,
+}
+
+/// 衝突マーカーに添える名前
+#[derive(Debug, Clone)]
+pub struct Labels {
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ContentMerge {
+    pub content: Vec<u8>,
+    pub conflicted: bool,
+}
+
+/// base からの変更箇所 (base の範囲と、それに対応する相手側の範囲)
+#[derive(Debug, Clone)]
+struct Chunk {
+    base: Range<usize>,
+    side: Range<usize>,
+}
+
+fn changed_chunks(edits: &[Edit]) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let (mut base_position, mut side_position) = (0, 0);
+    let mut in_chunk = false;
+
+    for edit in edits {
+        match edit {
+            Edit::Equal(_, _) => {
+                base_position += 1;
+                side_position += 1;
+                in_chunk = false;
+                continue;
+            }
+            Edit::Delete(_) => base_position += 1,
+            Edit::Insert(_) => side_position += 1,
+        }
+
+        match chunks.last_mut() {
+            Some(chunk) if in_chunk => {
+                chunk.base.end = base_position;
+                chunk.side.end = side_position;
+            }
+            _ => {
+                let (base_start, side_start) = match edit {
+                    Edit::Delete(_) => (base_position - 1, side_position),
+                    _ => (base_position, side_position - 1),
+                };
+                chunks.push(Chunk {
+                    base: base_start..base_position,
+                    side: side_start..side_position,
+                });
+                in_chunk = true;
+            }
+        }
+    }
+
+    chunks
+}
+
+/// group に含まれる chunk から、base の lo..hi に対応する相手側の範囲を求める
+fn side_range(chunks: &[&Chunk], lo: usize, hi: usize, offset: isize) -> Range<usize> {
+    match (chunks.first(), chunks.last()) {
+        (Some(first), Some(last)) => {
+            (first.side.start - (first.base.start - lo))..(last.side.end + (hi - last.base.end))
+        }
+        _ => ((lo as isize + offset) as usize)..((hi as isize + offset) as usize),
+    }
+}
+
+/// 行単位の 3-way マージ。両方で重なる (接する) 変更は衝突として印を付ける
+pub fn merge_content(
+    base: &[u8],
+    ours: &[u8],
+    theirs: &[u8],
+    labels: &Labels,
+    style: ConflictStyle,
+) -> ContentMerge {
+    let base_lines = diff::split_lines(base);
+    let ours_lines = diff::split_lines(ours);
+    let theirs_lines = diff::split_lines(theirs);
+
+    let ours_chunks = changed_chunks(&diff::diff_lines(
+        &base_lines,
+        &ours_lines,
+        Algorithm::Myers,
+    ));
+    let theirs_chunks = changed_chunks(&diff::diff_lines(
+        &base_lines,
+        &theirs_lines,
+        Algorithm::Myers,
+    ));
+
+    let mut content = Vec::new();
+    let mut conflicted = false;
+    let (mut ours_index, mut theirs_index) = (0, 0);
+    let (mut ours_offset, mut theirs_offset) = (0isize, 0isize);
+    let mut position = 0;
+
+    loop {
+        // base 上で先に始まる方から group を作り、重なる chunk を取り込んでいく
+        let first = match (ours_chunks.get(ours_index), theirs_chunks.get(theirs_index)) {
+            (None, None) => break,
+            (Some(ours), Some(theirs)) => ours.base.start.min(theirs.base.start),
+            (Some(ours), None) => ours.base.start,
+            (None, Some(theirs)) => theirs.base.start,
+        };
+        let (lo, mut hi) = (first, first);
+        let (ours_start, theirs_start) = (ours_index, theirs_index);
+        loop {
+            if let Some(chunk) = ours_chunks
+                .get(ours_index)
+                .filter(|chunk| chunk.base.start <= hi)
+            {
+                hi = hi.max(chunk.base.end);
+                ours_index += 1;
+                continue;
+            }
+            if let Some(chunk) = theirs_chunks
+                .get(theirs_index)
+                .filter(|chunk| chunk.base.start <= hi)
+            {
+                hi = hi.max(chunk.base.end);
+                theirs_index += 1;
+                continue;
+            }
+            break;
+        }
+
+        let ours_group = ours_chunks[ours_start..ours_index]
+            .iter()
+            .collect::<Vec<_>>();
+        let theirs_group = theirs_chunks[theirs_start..theirs_index]
+            .iter()
+            .collect::<Vec<_>>();
+        let ours_range = side_range(&ours_group, lo, hi, ours_offset);
+        let theirs_range = side_range(&theirs_group, lo, hi, theirs_offset);
+        ours_offset = ours_range.end as isize - hi as isize;
+        theirs_offset = theirs_range.end as isize - hi as isize;
+
+        content.extend(base_lines[position..lo].concat());
+        position = hi;
+
+        let ours_part = &ours_lines[ours_range];
+        let theirs_part = &theirs_lines[theirs_range];
+        if theirs_group.is_empty() || ours_part == theirs_part {
+            content.extend(ours_part.concat());
+        } else if ours_group.is_empty() {
+            content.extend(theirs_part.concat());
+        } else {
+            conflicted = true;
+            match style {
+                ConflictStyle::Merge => {
+                    write_refined_conflict(&mut content, ours_part, theirs_part, labels)
+                }
+                ConflictStyle::Diff3 => write_conflict(
+                    &mut content,
+                    ours_part,
+                    Some(&base_lines[lo..hi]),
+                    theirs_part,
+                    labels,
+                ),
+            }
+        }
+    }
+    content.extend(base_lines[position..].concat());
+
+    ContentMerge {
+        content,
+        conflicted,
+    }
+}
+
+/// 衝突した範囲のうち両側で同じ行は衝突の外に出す
+fn write_refined_conflict(
+    content: &mut Vec<u8>,
+    ours: &[&[u8]],
+    theirs: &[&[u8]],
+    labels: &Labels,
+) {
+    let edits = diff::diff_lines(ours, theirs, Algorithm::Myers);
+
+    let mut index = 0;
+    while index < edits.len() {
+        if let Edit::Equal(i, _) = edits[index] {
+            content.extend_from_slice(ours[i]);
+            index += 1;
+            continue;
+        }
+
+        let (mut ours_part, mut theirs_part) = (Vec::new(), Vec::new());
+        while let Some(edit) = edits.get(index) {
+            match *edit {
+                Edit::Delete(i) => ours_part.push(ours[i]),
+                Edit::Insert(j) => theirs_part.push(theirs[j]),
+                Edit::Equal(_, _) => break,
+            }
+            index += 1;
+        }
+        write_conflict(content, &ours_part, None, &theirs_part, labels);
+    }
+}
+
+fn write_conflict(
+    content: &mut Vec<u8>,
+    ours: &[&[u8]],
+    base: Option<&[&[u8]]>,
+    theirs: &[&[u8]],
+    labels: &Labels,
+) {
+    let write_marker = |content: &mut Vec<u8>, marker: char, label: &str| {
+        content.extend(marker.to_string().repeat(MARKER_LENGTH).as_bytes());
+        if !label.is_empty() {
+            content.push(b' ');
+            content.extend(label.as_bytes());
+        }
+        content.push(b'\n');
+    };
+    // マーカーが行の途中から始まらないように末尾の改行を補う
+    let write_lines = |content: &mut Vec<u8>, lines: &[&[u8]]| {
+        content.extend(lines.concat());
+        if content.last().is_some_and(|&b| b != b'\n') {
+            content.push(b'\n');
+        }
+    };
+
+    write_marker(content, '<', &labels.ours);
+    write_lines(content, ours);
+    if let Some(base) = base {
+        write_marker(content, '|', &labels.base);
+        write_lines(content, base);
+    }
+    write_marker(content, '=', "");
+    write_lines(content, theirs);
+    write_marker(content, '>', &labels.theirs);
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConflictReason {
+    /// 両方で内容が変更された
+    Content,
+    /// base になく、両方で別の内容が追加された
+    AddAdd,
+    /// 片方で削除され、もう片方で変更された
+    ModifyDelete { deleted_in_ours: bool },
+    /// 片方のファイルと同じパスに、もう片方でディレクトリができた (ファイルは別名で残す)
+    DirectoryFile { file_in_ours: bool },
+}
+
+/// 自動で解決できなかったパス。index には base, ours, theirs を stage 1, 2, 3 として残す
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Conflict {
+    pub path: String,
+    pub reason: ConflictReason,
+    pub base: Option<(BlobType, Hash)>,
+    pub ours: Option<(BlobType, Hash)>,
+    pub theirs: Option<(BlobType, Hash)>,
+    /// 作業ツリーに書き出す内容 (衝突マーカー入り、もしくは残った側の内容)
+    pub worktree: (BlobType, Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TreeMerge {
+    /// 衝突しなかったパスのマージ結果
+    pub files: TreeFiles,
+    pub conflicts: Vec<Conflict>,
+    /// 行単位のマージを試みたパス
+    pub auto_merged: Vec<String>,
+}
+
+/// 平坦にした tree 同士の 3-way マージ。きれいにマージできた blob は書き込んでおく
+pub fn merge_trees(
+    repo: &Repository,
+    base: &TreeFiles,
+    ours: &TreeFiles,
+    theirs: &TreeFiles,
+    labels: &Labels,
+    style: ConflictStyle,
+) -> Result<TreeMerge> {
+    let paths = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut files = TreeFiles::new();
+    let mut conflicts = Vec::new();
+    let mut auto_merged = Vec::new();
+
+    for path in paths {
+        let (base_file, ours_file, theirs_file) =
+            (base.get(path), ours.get(path), theirs.get(path));
+
+        let merged = if ours_file == theirs_file || base_file == theirs_file {
+            ours_file
+        } else if base_file == ours_file {
+            theirs_file
+        } else {
+            None
+        };
+        if let Some(merged) = merged {
+            files.insert(path.clone(), merged.clone());
+            continue;
+        }
+        if ours_file == theirs_file || base_file == theirs_file || base_file == ours_file {
+            // 削除
+            continue;
+        }
+
+        let conflict = |reason, worktree| Conflict {
+            path: path.clone(),
+            reason,
+            base: base_file.cloned(),
+            ours: ours_file.cloned(),
+            theirs: theirs_file.cloned(),
+            worktree,
+        };
+
+        let (Some((ours_type, ours_hash)), Some((theirs_type, theirs_hash))) =
+            (ours_file, theirs_file)
+        else {
+            let deleted_in_ours = ours_file.is_none();
+            let (blob_type, hash) = ours_file.or(theirs_file).unwrap();
+            conflicts.push(conflict(
+                ConflictReason::ModifyDelete { deleted_in_ours },
+                (*blob_type, GitObject::read(repo, hash)?.body),
+            ));
+            continue;
+        };
+
+        auto_merged.push(path.clone());
+        let reason = match base_file {
+            Some(_) => ConflictReason::Content,
+            None => ConflictReason::AddAdd,
+        };
+
+        // 片方だけモードを変えていればそれを採用する
+        let blob_type = match base_file {
+            Some((base_type, _)) if base_type == ours_type => *theirs_type,
+            _ => *ours_type,
+        };
+
+        let ours_content = GitObject::read(repo, ours_hash)?.body;
+        let theirs_content = GitObject::read(repo, theirs_hash)?.body;
+        let base_content = match base_file {
+            Some((_, hash)) => GitObject::read(repo, hash)?.body,
+            None => Vec::new(),
+        };
+
+        let mergeable = *ours_type != BlobType::Symlink
+            && *theirs_type != BlobType::Symlink
+            && ![&base_content, &ours_content, &theirs_content]
+                .iter()
+                .any(|content| diff::is_binary(content));
+        if !mergeable {
+            conflicts.push(conflict(reason, (*ours_type, ours_content)));
+            continue;
+        }
+
+        let merged = merge_content(&base_content, &ours_content, &theirs_content, labels, style);
+        if merged.conflicted {
+            conflicts.push(conflict(reason, (blob_type, merged.content)));
+            continue;
+        }
+
+        let object = GitObject::new(ObjectType::Blob, merged.content);
+        let hash = object.hash();
+        if !GitObject::exists(repo, &hash)? {
+            object.write(repo)?;
+        }
+        files.insert(path.clone(), (blob_type, hash));
+    }
+
+    move_directory_file_conflicts(repo, base, ours, theirs, labels, &mut files, &mut conflicts)?;
+
+    Ok(TreeMerge {
+        files,
+        conflicts,
+        auto_merged,
+    })
+}
+
+/// 結果の中でディレクトリと重なったファイルを `<path>~<label>` に移して衝突として残す
+fn move_directory_file_conflicts(
+    repo: &Repository,
+    base: &TreeFiles,
+    ours: &TreeFiles,
+    theirs: &TreeFiles,
+    labels: &Labels,
+    files: &mut TreeFiles,
+    conflicts: &mut Vec<Conflict>,
+) -> Result<()> {
+    let mut directories = BTreeSet::new();
+    for path in files
+        .keys()
+        .chain(conflicts.iter().map(|conflict| &conflict.path))
+    {
+        let mut parent = Path::new(path).parent();
+        while let Some(directory) = parent.filter(|directory| !directory.as_os_str().is_empty()) {
+            directories.insert(directory.to_string_lossy().to_string());
+            parent = directory.parent();
+        }
+    }
+    let clashes = files
+        .keys()
+        .chain(conflicts.iter().map(|conflict| &conflict.path))
+        .filter(|path| directories.contains(*path))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for path in clashes {
+        // 両方がファイルならディレクトリはできないので、ファイルなのはどちらか片方だけ
+        let file_in_ours = ours.contains_key(&path);
+        let label = if file_in_ours {
+            &labels.ours
+        } else {
+            &labels.theirs
+        };
+        let mut new_path = format!("{}~{}", path, label.replace('/', "_"));
+        let taken = |candidate: &String| {
+            [base, ours, theirs, &*files]
+                .iter()
+                .any(|tree| tree.contains_key(candidate))
+                || directories.contains(candidate)
+        };
+        let mut suffix = 0;
+        while taken(&new_path) {
+            new_path = format!("{}~{}_{}", path, label.replace('/', "_"), suffix);
+            suffix += 1;
+        }
+
+        let reason = ConflictReason::DirectoryFile { file_in_ours };
+        if let Some(conflict) = conflicts.iter_mut().find(|conflict| conflict.path == path) {
+            conflict.path = new_path;
+            conflict.reason = reason;
+            continue;
+        }
+
+        let (blob_type, hash) = files.remove(&path).unwrap();
+        let (ours_file, theirs_file) = if file_in_ours {
+            (ours.get(&path).cloned(), None)
+        } else {
+            (None, theirs.get(&path).cloned())
+        };
+        conflicts.push(Conflict {
+            path: new_path,
+            reason,
+            base: base.get(&path).cloned(),
+            ours: ours_file,
+            theirs: theirs_file,
+            worktree: (blob_type, GitObject::read(repo, &hash)?.body),
+        });
+    }
+
+    Ok(())
+}
+
+/// 衝突などで止まっているマージ (`MERGE_HEAD`, `MERGE_MSG`, `MERGE_MODE`)
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MergeState {
+    pub head: Hash,
+    pub message: String,
+    pub no_ff: bool,
+}
+impl MergeState {
+    pub fn read(repo: &Repository) -> Result<Option<Self>> {
+        if !repo.git_path(MERGE_HEAD_PATH).exists() {
+            return Ok(None);
+        }
+
+        let head = Hash::from_str(std::fs::read_to_string(repo.git_path(MERGE_HEAD_PATH))?.trim())?;
+        let message = std::fs::read_to_string(repo.git_path(MERGE_MSG_PATH)).unwrap_or_default();
+        let no_ff = std::fs::read_to_string(repo.git_path(MERGE_MODE_PATH))
+            .is_ok_and(|mode| mode == "no-ff");
+
+        Ok(Some(Self {
+            head,
+            message,
+            no_ff,
+        }))
+    }
+
+    pub fn write(&self, repo: &Repository) -> Result<()> {
+        std::fs::write(repo.git_path(MERGE_HEAD_PATH), format!("{}\n", self.head))?;
+        std::fs::write(repo.git_path(MERGE_MSG_PATH), &self.message)?;
+        std::fs::write(
+            repo.git_path(MERGE_MODE_PATH),
+            if self.no_ff { "no-ff" } else { "" },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn remove(repo: &Repository) -> Result<()> {
+        for path in [MERGE_HEAD_PATH, MERGE_MSG_PATH, MERGE_MODE_PATH] {
+            let path = repo.git_path(path);
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels() -> Labels {
+        Labels {
+            base: "base".to_string(),
+            ours: "ours".to_string(),
+            theirs: "theirs".to_string(),
+        }
+    }
+
+    fn merge(base: &str, ours: &str, theirs: &str, style: ConflictStyle) -> (String, bool) {
+        let merged = merge_content(
+            base.as_bytes(),
+            ours.as_bytes(),
+            theirs.as_bytes(),
+            &labels(),
+            style,
+        );
+        (
+            String::from_utf8(merged.content).unwrap(),
+            merged.conflicted,
+        )
+    }
+
+    #[test]
+    fn test_merge_clean() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "A\nb\nc\nd\ne\n";
+        let theirs = "a\nb\nc\nd\nE\nf\n";
+
+        assert_eq!(
+            merge(base, ours, theirs, ConflictStyle::Merge),
+            ("A\nb\nc\nd\nE\nf\n".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn test_merge_same_change() {
+        let base = "a\nb\nc\n";
+        let changed = "a\nB\nc\n";
+
+        assert_eq!(
+            merge(base, changed, changed, ConflictStyle::Merge),
+            (changed.to_string(), false)
+        );
+    }
+
+    #[test]
+    fn test_merge_conflict() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nx\nsame\nc\n";
+        let theirs = "a\ny\nsame\nc\n";
+
+        assert_eq!(
+            merge(base, ours, theirs, ConflictStyle::Merge),
+            (
+                "a\n<<<<<<< ours\nx\n=======\ny\n>>>>>>> theirs\nsame\nc\n".to_string(),
+                true
+            )
+        );
+        assert_eq!(
+            merge(base, ours, theirs, ConflictStyle::Diff3),
+            (
+                "a\n<<<<<<< ours\nx\nsame\n||||||| base\nb\n=======\ny\nsame\n>>>>>>> theirs\nc\n"
+                    .to_string(),
+                true
+            )
+        );
+    }
+
+    fn init_repo(name: &str) -> Repository {
+        let directory =
+            std::env::temp_dir().join(format!("ssgit-merge-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+
+        Repository::init(&directory, false, Some("main")).unwrap()
+    }
+
+    fn tree_files(repo: &Repository, files: &[(&str, &str)]) -> TreeFiles {
+        files
+            .iter()
+            .map(|(path, content)| {
+                let blob = GitObject::new(ObjectType::Blob, content.as_bytes().to_vec());
+                (
+                    path.to_string(),
+                    (BlobType::Plain, blob.write(repo).unwrap()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_directory_file_conflict() {
+        let repo = init_repo("directory-file");
+        let base = tree_files(&repo, &[("base", "base\n")]);
+        let ours = tree_files(&repo, &[("base", "base\n"), ("a", "file\n")]);
+        let theirs = tree_files(&repo, &[("base", "base\n"), ("a/f", "f\n"), ("0", "0\n")]);
+
+        let result = merge_trees(
+            &repo,
+            &base,
+            &ours,
+            &theirs,
+            &labels(),
+            ConflictStyle::Merge,
+        )
+        .unwrap();
+        assert_eq!(
+            result.files.keys().collect::<Vec<_>>(),
+            vec!["0", "a/f", "base"]
+        );
+        assert_eq!(result.conflicts.len(), 1);
+        let conflict = &result.conflicts[0];
+        assert_eq!(conflict.path, "a~ours");
+        assert_eq!(
+            conflict.reason,
+            ConflictReason::DirectoryFile { file_in_ours: true }
+        );
+        assert_eq!(conflict.ours, ours.get("a").cloned());
+        assert_eq!(conflict.theirs, None);
+        assert_eq!(conflict.worktree, (BlobType::Plain, b"file\n".to_vec()));
+
+        let result = merge_trees(
+            &repo,
+            &base,
+            &theirs,
+            &ours,
+            &labels(),
+            ConflictStyle::Merge,
+        )
+        .unwrap();
+        assert_eq!(result.conflicts[0].path, "a~theirs");
+        assert_eq!(result.conflicts[0].theirs, ours.get("a").cloned());
+    }
+
+    #[test]
+    fn test_merge_missing_newline() {
+        let (merged, conflicted) = merge("a\n", "a\nb", "a\nc", ConflictStyle::Merge);
+
+        assert!(conflicted);
+        assert_eq!(merged, "a\n<<<<<<< ours\nb\n=======\nc\n>>>>>>> theirs\n");
+    }
+}
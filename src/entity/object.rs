@@ -1,20 +1,24 @@
 use std::{
     fmt::{Display, Write},
-    os::unix::fs::PermissionsExt,
     str::FromStr,
 };
 
-use crate::parser::ObjectType as ParserObjectType;
-use anyhow::{bail, Result};
+use crate::{
+    entity::{config::Config, date, repository::Repository},
+    error::{Error, Result},
+};
 use chrono::{FixedOffset, TimeZone};
 
 use self::{
+    database::ObjectDatabase,
     mode::Mode,
     zip::{compress, decompress},
 };
 
+pub mod database;
 pub mod hash;
 pub mod mode;
+pub mod pack;
 pub mod zip;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -58,25 +62,18 @@ pub enum ObjectType {
     Blob,
     Tree,
     Commit,
-}
-impl From<ParserObjectType> for ObjectType {
-    fn from(parser_object_type: ParserObjectType) -> Self {
-        match parser_object_type {
-            ParserObjectType::Blob => Self::Blob,
-            ParserObjectType::Tree => Self::Tree,
-            ParserObjectType::Commit => Self::Commit,
-        }
-    }
+    Tag,
 }
 impl FromStr for ObjectType {
-    type Err = anyhow::Error;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "blob" => Ok(Self::Blob),
             "tree" => Ok(Self::Tree),
             "commit" => Ok(Self::Commit),
-            _ => bail!("Invalid object type {}", s),
+            "tag" => Ok(Self::Tag),
+            _ => Err(Error::InvalidArgument(format!("Invalid object type {}", s))),
         }
     }
 }
@@ -86,6 +83,7 @@ impl std::fmt::Display for ObjectType {
             Self::Blob => "blob",
             Self::Tree => "tree",
             Self::Commit => "commit",
+            Self::Tag => "tag",
         };
 
         f.write_str(s)
@@ -112,28 +110,36 @@ impl GitObject {
         hash::Hash::hash_bytes(&full_content)
     }
 
-    pub fn read(hash: &hash::Hash) -> Result<Self> {
-        let path = hash.get_object_path();
+    /// loose object を優先し、見つからなければ pack や alternates から探す
+    pub fn read(db: &dyn ObjectDatabase, hash: &hash::Hash) -> Result<Self> {
+        db.read(hash)?
+            .ok_or_else(|| Error::NotFound(format!("Object {} does not exist", hash)))
+    }
 
-        let content = std::fs::read(path)?;
+    /// 省略された hash に一致するオブジェクトを全ての store から探す
+    pub fn find_by_prefix(db: &dyn ObjectDatabase, prefix: &str) -> Result<Vec<hash::Hash>> {
+        let prefix = prefix.to_ascii_lowercase();
+        if prefix.len() < 2 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::InvalidArgument(format!(
+                "Invalid hash prefix {}",
+                prefix
+            )));
+        }
 
-        Self::from_raw(&content)
+        db.find_by_prefix(&prefix)
     }
 
-    pub fn write(&self) -> Result<()> {
-        let hash = self.hash();
-        let path = hash.get_object_path();
-
-        std::fs::create_dir_all(path.parent().unwrap())?;
-        std::fs::write(&path, self.to_raw()?)?;
-        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o444))?;
+    pub fn exists(db: &dyn ObjectDatabase, hash: &hash::Hash) -> Result<bool> {
+        db.exists(hash)
+    }
 
-        Ok(())
+    pub fn write(&self, db: &dyn ObjectDatabase) -> Result<hash::Hash> {
+        db.write(self)
     }
 
     pub fn parse_blob_body(&self) -> Result<String> {
         if self.type_ != ObjectType::Blob {
-            bail!("Object is not a blob");
+            return Err(Error::TypeMismatch("Object is not a blob".to_string()));
         }
 
         Ok(std::str::from_utf8(&self.body)?.to_string())
@@ -142,7 +148,7 @@ impl GitObject {
     const TREE_SHA1_LENGTH: usize = 20;
     pub fn parse_tree_body(&self) -> Result<Vec<TreeEntry>> {
         if self.type_ != ObjectType::Tree {
-            bail!("Object is not a tree");
+            return Err(Error::TypeMismatch("Object is not a tree".to_string()));
         }
 
         let mut entries = Vec::new();
@@ -151,29 +157,36 @@ impl GitObject {
         while !body.is_empty() {
             let mut parts = body.splitn(2, |b: &u8| *b == b' ');
 
-            let mode = parts.next().ok_or(anyhow::anyhow!("Could not find mode"))?;
+            let mode = parts
+                .next()
+                .ok_or(Error::CorruptObject("Could not find mode".to_string()))?;
             let mode = std::str::from_utf8(mode)?;
             let mode = Mode::from_str(mode)?;
 
             body = parts
                 .next()
-                .ok_or(anyhow::anyhow!("unexpected end of body"))?
+                .ok_or(Error::CorruptObject("unexpected end of body".to_string()))?
                 .to_vec();
 
             let mut parts = body.splitn(2, |b: &u8| *b == b'\0');
 
-            let name = parts.next().ok_or(anyhow::anyhow!("Could not find name"))?;
+            let name = parts
+                .next()
+                .ok_or(Error::CorruptObject("Could not find name".to_string()))?;
             let name = std::str::from_utf8(name)?;
             let name = name.to_string();
 
             body = parts
                 .next()
-                .ok_or(anyhow::anyhow!("unexpected end of body"))?
+                .ok_or(Error::CorruptObject("unexpected end of body".to_string()))?
                 .to_vec();
 
             let hash = body.drain(..Self::TREE_SHA1_LENGTH).collect::<Vec<u8>>();
             let hash: [u8; Self::TREE_SHA1_LENGTH] = hash.try_into().map_err(|_| {
-                anyhow::anyhow!("Could not convert hash to [u8; {}]", Self::TREE_SHA1_LENGTH)
+                Error::CorruptObject(format!(
+                    "Could not convert hash to [u8; {}]",
+                    Self::TREE_SHA1_LENGTH
+                ))
             })?;
             let hash = hash::Hash::from(hash);
 
@@ -203,6 +216,49 @@ impl GitObject {
         Self::new(ObjectType::Commit, body)
     }
 
+    pub fn parse_tag_body(&self) -> Result<Tag> {
+        if self.type_ != ObjectType::Tag {
+            return Err(Error::TypeMismatch("Object is not a tag".to_string()));
+        }
+
+        Tag::from_str(std::str::from_utf8(&self.body)?)
+    }
+
+    pub fn from_tag(tag: &Tag) -> Self {
+        let body = tag.to_string().as_bytes().to_vec();
+
+        Self::new(ObjectType::Tag, body)
+    }
+
+    /// tag を辿って type_ のオブジェクトにたどり着くまで剥がす
+    pub fn peel(
+        db: &dyn ObjectDatabase,
+        hash: &hash::Hash,
+        type_: ObjectType,
+    ) -> Result<(hash::Hash, Self)> {
+        let mut hash = hash.clone();
+
+        loop {
+            let object = Self::read(db, &hash)?;
+            if object.type_ == type_ {
+                return Ok((hash, object));
+            }
+
+            match object.type_ {
+                ObjectType::Tag => hash = object.parse_tag_body()?.object,
+                ObjectType::Commit if type_ == ObjectType::Tree => {
+                    hash = object.parse_commit_body()?.tree
+                }
+                _ => {
+                    return Err(Error::TypeMismatch(format!(
+                        "{} {} cannot be peeled to {}",
+                        object.type_, hash, type_
+                    )))
+                }
+            }
+        }
+    }
+
     pub fn new_tree(entries: &[TreeEntry]) -> Self {
         let mut entries = entries.to_vec();
         entries.sort();
@@ -232,30 +288,34 @@ impl GitObject {
     }
 }
 impl TryFrom<&[u8]> for GitObject {
-    type Error = anyhow::Error;
+    type Error = Error;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         let mut parts = value.splitn(2, |b: &u8| *b == b'\0');
 
         let header = parts
             .next()
-            .ok_or(anyhow::anyhow!("Could not find header"))?;
-        let body = parts.next().ok_or(anyhow::anyhow!("Could not find body"))?;
+            .ok_or(Error::CorruptObject("Could not find header".to_string()))?;
+        let body = parts
+            .next()
+            .ok_or(Error::CorruptObject("Could not find body".to_string()))?;
 
         let header = std::str::from_utf8(header)?;
         let mut header_parts = header.splitn(2, ' ');
         let type_ = header_parts
             .next()
-            .ok_or(anyhow::anyhow!("Could not find type"))?;
+            .ok_or(Error::CorruptObject("Could not find type".to_string()))?;
         let byte_size = header_parts
             .next()
-            .ok_or(anyhow::anyhow!("Could not find byte size"))?;
+            .ok_or(Error::CorruptObject("Could not find byte size".to_string()))?;
 
         let type_ = ObjectType::from_str(type_)?;
         let byte_size = usize::from_str(byte_size)?;
 
         if body.len() != byte_size {
-            bail!("Byte size did not match body length");
+            return Err(Error::CorruptObject(
+                "Byte size did not match body length".to_string(),
+            ));
         }
 
         Ok(Self::new(type_, body.to_vec()))
@@ -285,28 +345,28 @@ pub struct Commit {
     pub message: String,
 }
 impl FromStr for Commit {
-    type Err = anyhow::Error;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut parts = s.splitn(2, "\n\n");
 
         let header = parts
             .next()
-            .ok_or(anyhow::anyhow!("Could not find header"))?;
+            .ok_or(Error::CorruptObject("Could not find header".to_string()))?;
         let message = parts
             .next()
-            .ok_or(anyhow::anyhow!("Could not find message"))?;
+            .ok_or(Error::CorruptObject("Could not find message".to_string()))?;
 
         let header_lines = header.lines().collect::<Vec<&str>>();
         let mut header_lines = header_lines.iter().peekable();
 
         let tree_line = header_lines
             .next()
-            .ok_or(anyhow::anyhow!("Could not find tree line"))?;
+            .ok_or(Error::CorruptObject("Could not find tree line".to_string()))?;
         let tree = hash::Hash::from_str(
             tree_line
                 .strip_prefix("tree ")
-                .ok_or(anyhow::anyhow!("Could not find tree"))?,
+                .ok_or(Error::CorruptObject("Could not find tree".to_string()))?,
         )?;
 
         let parent = {
@@ -314,16 +374,18 @@ impl FromStr for Commit {
 
             while header_lines
                 .peek()
-                .ok_or(anyhow::anyhow!("Could not find parent line"))?
+                .ok_or(Error::CorruptObject(
+                    "Could not find parent line".to_string(),
+                ))?
                 .starts_with("parent ")
             {
-                let parent_line = header_lines
-                    .next()
-                    .ok_or(anyhow::anyhow!("Could not find parent line"))?;
+                let parent_line = header_lines.next().ok_or(Error::CorruptObject(
+                    "Could not find parent line".to_string(),
+                ))?;
                 let parent_hash = hash::Hash::from_str(
                     parent_line
                         .strip_prefix("parent ")
-                        .ok_or(anyhow::anyhow!("Could not find parent"))?,
+                        .ok_or(Error::CorruptObject("Could not find parent".to_string()))?,
                 )?;
                 parent.push(parent_hash);
             }
@@ -331,22 +393,22 @@ impl FromStr for Commit {
             parent
         };
 
-        let author_line = header_lines
-            .next()
-            .ok_or(anyhow::anyhow!("Could not find author line"))?;
+        let author_line = header_lines.next().ok_or(Error::CorruptObject(
+            "Could not find author line".to_string(),
+        ))?;
         let author = User::from_str(
             author_line
                 .strip_prefix("author ")
-                .ok_or(anyhow::anyhow!("Could not find author"))?,
+                .ok_or(Error::CorruptObject("Could not find author".to_string()))?,
         )?;
 
-        let committer_line = header_lines
-            .next()
-            .ok_or(anyhow::anyhow!("Could not find committer line"))?;
+        let committer_line = header_lines.next().ok_or(Error::CorruptObject(
+            "Could not find committer line".to_string(),
+        ))?;
         let committer = User::from_str(
             committer_line
                 .strip_prefix("committer ")
-                .ok_or(anyhow::anyhow!("Could not find committer"))?,
+                .ok_or(Error::CorruptObject("Could not find committer".to_string()))?,
         )?;
 
         let rest_of_header = header_lines.copied().collect::<Vec<&str>>().join("\n");
@@ -376,9 +438,71 @@ impl Display for Commit {
 
         write!(
             f,
-            "tree {}\n{}author {}\ncommitter {}\n{}\n\n{}",
-            self.tree, parent, self.author, self.committer, self.rest_of_header, self.message
-        )
+            "tree {}\n{}author {}\ncommitter {}\n",
+            self.tree, parent, self.author, self.committer
+        )?;
+        if !self.rest_of_header.is_empty() {
+            writeln!(f, "{}", self.rest_of_header)?;
+        }
+
+        write!(f, "\n{}", self.message)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Tag {
+    pub object: hash::Hash,
+    pub type_: ObjectType,
+    pub tag: String,
+    pub tagger: Option<User>,
+    pub message: String,
+}
+impl FromStr for Tag {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (header, message) = s.split_once("\n\n").unwrap_or((s, ""));
+
+        let mut object = None;
+        let mut type_ = None;
+        let mut tag = None;
+        let mut tagger = None;
+        for line in header.lines() {
+            let (key, value) = line.split_once(' ').ok_or(Error::CorruptObject(format!(
+                "Invalid tag header line: {}",
+                line
+            )))?;
+
+            match key {
+                "object" => object = Some(hash::Hash::from_str(value)?),
+                "type" => type_ = Some(ObjectType::from_str(value)?),
+                "tag" => tag = Some(value.to_string()),
+                "tagger" => tagger = Some(User::from_str(value)?),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            object: object.ok_or(Error::CorruptObject("Could not find object".to_string()))?,
+            type_: type_.ok_or(Error::CorruptObject("Could not find type".to_string()))?,
+            tag: tag.ok_or(Error::CorruptObject("Could not find tag".to_string()))?,
+            tagger,
+            message: message.to_string(),
+        })
+    }
+}
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "object {}\ntype {}\ntag {}\n",
+            self.object, self.type_, self.tag
+        )?;
+        if let Some(tagger) = &self.tagger {
+            writeln!(f, "tagger {}", tagger)?;
+        }
+
+        write!(f, "\n{}", self.message)
     }
 }
 
@@ -393,53 +517,84 @@ impl User {
         Self { name, email, time }
     }
 
-    pub fn read_from_git(time: chrono::DateTime<FixedOffset>) -> Result<Self> {
-        let username_child = std::process::Command::new("git")
-            .args(["config", "--get", "user.name"])
-            .stdout(std::process::Stdio::piped())
-            .spawn()?;
-        let email_child = std::process::Command::new("git")
-            .args(["config", "--get", "user.email"])
-            .stdout(std::process::Stdio::piped())
-            .spawn()?;
+    pub fn author(repo: &Repository) -> Result<Self> {
+        Self::read_identity(repo, "author")
+    }
+
+    pub fn committer(repo: &Repository) -> Result<Self> {
+        Self::read_identity(repo, "committer")
+    }
+
+    /// 環境変数 `GIT_<ROLE>_NAME` などを優先し、なければ `<role>.name`, `user.name` の順に config から読む
+    /// 日時は `GIT_<ROLE>_DATE` があればそれを、なければ現在時刻を使う
+    fn read_identity(repo: &Repository, role: &str) -> Result<Self> {
+        let config = Config::read(Some(repo))?;
+        let lookup = |key: &str| {
+            std::env::var(format!(
+                "GIT_{}_{}",
+                role.to_uppercase(),
+                key.to_uppercase()
+            ))
+            .ok()
+            .or_else(|| config.get(&format!("{}.{}", role, key)).map(str::to_string))
+            .or_else(|| config.get(&format!("user.{}", key)).map(str::to_string))
+        };
 
-        let username_raw = username_child.wait_with_output()?.stdout;
-        let email_raw = email_child.wait_with_output()?.stdout;
+        let name = lookup("name").unwrap_or_default();
+        let email = lookup("email")
+            .or_else(|| std::env::var("EMAIL").ok())
+            .unwrap_or_default();
+        if name.trim().is_empty() || email.trim().is_empty() {
+            let mut role = role.to_string();
+            role[..1].make_ascii_uppercase();
+            return Err(Error::Config(format!("{} identity unknown\n\n*** Please tell me who you are.\n\nRun\n\n  ssgit config --global user.email \"you@example.com\"\n  ssgit config --global user.name \"Your Name\"\n\nto set your account's default identity.",
+                role)));
+        }
 
-        let username = String::from_utf8_lossy(&username_raw).trim().to_string();
-        let email = String::from_utf8_lossy(&email_raw).trim().to_string();
+        let time = match std::env::var(format!("GIT_{}_DATE", role.to_uppercase())) {
+            Ok(date) => date::parse_date(&date)?,
+            Err(_) => {
+                let now = chrono::Local::now();
+                now.with_timezone(now.offset())
+            }
+        };
 
-        Ok(Self::new(username, email, time))
+        Ok(Self::new(
+            name.trim().to_string(),
+            email.trim().to_string(),
+            time,
+        ))
     }
 }
 impl FromStr for User {
-    type Err = anyhow::Error;
+    type Err = Error;
 
+    /// `Name <email> <unix> <+-HHMM>` をパースする (名前には空白を含みうる)
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut parts = s.splitn(4, ' ');
-
-        let name = parts
-            .next()
-            .ok_or(anyhow::anyhow!("Could not find name"))?
+        let (ident, offset) = s
+            .rsplit_once(' ')
+            .ok_or(Error::CorruptObject("Could not find offset".to_string()))?;
+        let (ident, timestamp) = ident
+            .rsplit_once(' ')
+            .ok_or(Error::CorruptObject("Could not find timestamp".to_string()))?;
+
+        let email_start = ident
+            .find('<')
+            .ok_or(Error::CorruptObject("Could not find email".to_string()))?;
+        let email = ident[email_start + 1..]
+            .strip_suffix('>')
+            .ok_or(Error::CorruptObject("Could not find email".to_string()))?
             .to_string();
-        let email = parts
-            .next()
-            .and_then(|s| s.strip_prefix('<'))
-            .and_then(|s| s.strip_suffix('>'))
-            .ok_or(anyhow::anyhow!("Could not find email"))?
-            .to_string();
-        let timestamp = parts
-            .next()
-            .ok_or(anyhow::anyhow!("Could not find timestamp"))?;
-        let offset = parts
-            .next()
-            .ok_or(anyhow::anyhow!("Could not find offset"))?;
+        let name = ident[..email_start].trim_end().to_string();
 
-        let offset = FixedOffset::from_str(format!("{}:{}", &offset[..3], &offset[3..]).as_str())?;
+        let offset = date::parse_offset(offset)
+            .ok_or(Error::CorruptObject(format!("Invalid offset {}", offset)))?;
         let time = offset
-            .timestamp_micros(i64::from_str(timestamp)?)
+            .timestamp_opt(i64::from_str(timestamp)?, 0)
             .single()
-            .ok_or(anyhow::anyhow!("Could not parse timestamp"))?;
+            .ok_or(Error::CorruptObject(
+                "Could not parse timestamp".to_string(),
+            ))?;
 
         Ok(Self::new(name, email, time))
     }
@@ -451,8 +606,80 @@ impl Display for User {
             "{} <{}> {} {}",
             self.name,
             self.email,
-            self.time.timestamp_micros(),
-            format!("{}", self.time.offset()).replace(':', "")
+            self.time.timestamp(),
+            date::format_offset(self.time.offset())
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAG_BODY: &str =
+        "object 0123456789012345678901234567890123456789\ntype commit\ntag v1.0\n\nRelease v1.0\n";
+
+    #[test]
+    fn test_tag_round_trip() {
+        let tag = Tag::from_str(TAG_BODY).unwrap();
+
+        assert_eq!(tag.type_, ObjectType::Commit);
+        assert_eq!(tag.tag, "v1.0");
+        assert_eq!(tag.tagger, None);
+        assert_eq!(tag.message, "Release v1.0\n");
+        assert_eq!(tag.to_string(), TAG_BODY);
+    }
+
+    /// git で作った commit (`GIT_AUTHOR_DATE="1112904793 +0200" GIT_COMMITTER_DATE="1700000000 -0530"`)
+    const GIT_COMMIT_BODY: &str = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\nauthor Jane Q Public <jane@example.com> 1112904793 +0200\ncommitter Jane Q Public <jane@example.com> 1700000000 -0530\n\nSubject line\n\nBody\n";
+    const GIT_COMMIT_HASH: &str = "aa586409b518fbb51698eb9de24d4a56321e522c";
+
+    const GIT_TAG_BODY: &str = "object aa586409b518fbb51698eb9de24d4a56321e522c\ntype commit\ntag v1\ntagger Jane Q Public <jane@example.com> 1700000000 +0900\n\nRelease\n";
+    const GIT_TAG_HASH: &str = "5847d738af78342e24cd980d7dd0bb934ccbbf37";
+
+    #[test]
+    fn test_commit_round_trip_with_git() {
+        let commit = Commit::from_str(GIT_COMMIT_BODY).unwrap();
+
+        assert_eq!(commit.author.name, "Jane Q Public");
+        assert_eq!(commit.author.email, "jane@example.com");
+        assert_eq!(commit.author.time.timestamp(), 1112904793);
+        assert_eq!(commit.author.time.offset().local_minus_utc(), 2 * 3600);
+        assert_eq!(
+            commit.committer.time.offset().local_minus_utc(),
+            -(5 * 3600 + 30 * 60)
+        );
+        assert_eq!(commit.author.time.to_rfc3339(), "2005-04-07T22:13:13+02:00");
+
+        let object = GitObject::from_commit(&commit);
+        assert_eq!(object.body, GIT_COMMIT_BODY.as_bytes());
+        assert_eq!(object.hash().to_string(), GIT_COMMIT_HASH);
+    }
+
+    #[test]
+    fn test_tag_round_trip_with_git() {
+        let tag = Tag::from_str(GIT_TAG_BODY).unwrap();
+        let tagger = tag.tagger.as_ref().unwrap();
+
+        assert_eq!(tagger.name, "Jane Q Public");
+        assert_eq!(tagger.time.timestamp(), 1700000000);
+
+        let object = GitObject::from_tag(&tag);
+        assert_eq!(object.hash().to_string(), GIT_TAG_HASH);
+    }
+
+    #[test]
+    fn test_user_from_str() {
+        assert!(User::from_str("No Email 1700000000 +0000").is_err());
+        assert!(User::from_str("Name <email> 1700000000 0900").is_err());
+
+        let user = User::from_str(" <> 0 +0000").unwrap();
+        assert_eq!(user.name, "");
+        assert_eq!(user.to_string(), " <> 0 +0000");
+    }
+
+    #[test]
+    fn test_tag_missing_object() {
+        assert!(Tag::from_str("type commit\ntag v1.0\n\nmessage\n").is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::error::{Error, Result};
+
+use super::{hash::Hash, pack::Pack, GitObject};
+
+/// alternates を辿る深さの上限 (git と同じ)
+const MAX_ALTERNATE_DEPTH: usize = 5;
+
+/// object を読み書きする場所
+pub trait ObjectDatabase {
+    /// object を読む (存在しなければ None)
+    fn read(&self, hash: &Hash) -> Result<Option<GitObject>>;
+
+    /// object を読む。pack の外にある delta の base は `bases` から探す
+    fn read_with_bases(
+        &self,
+        hash: &Hash,
+        _bases: &dyn ObjectDatabase,
+    ) -> Result<Option<GitObject>> {
+        self.read(hash)
+    }
+
+    /// object を書き込んでその hash を返す (既にあれば何もしない)
+    fn write(&self, object: &GitObject) -> Result<Hash>;
+
+    fn exists(&self, hash: &Hash) -> Result<bool> {
+        Ok(self.read(hash)?.is_some())
+    }
+
+    /// 全ての object の hash を列挙する
+    fn hashes(&self) -> Result<Vec<Hash>>;
+
+    /// 16 進数の prefix に一致する hash を全て返す
+    fn find_by_prefix(&self, prefix: &str) -> Result<Vec<Hash>> {
+        Ok(self
+            .hashes()?
+            .into_iter()
+            .filter(|hash| hash.to_string().starts_with(prefix))
+            .collect())
+    }
+}
+
+/// `objects/xx/yyyy...` に zlib 圧縮して置かれた object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LooseStore {
+    pub directory: PathBuf,
+}
+impl LooseStore {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    pub fn object_path(&self, hash: &Hash) -> PathBuf {
+        let hex = hash.to_string();
+        self.directory.join(&hex[..2]).join(&hex[2..])
+    }
+}
+impl ObjectDatabase for LooseStore {
+    fn read(&self, hash: &Hash) -> Result<Option<GitObject>> {
+        let path = self.object_path(hash);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(GitObject::from_raw(&std::fs::read(path)?)?))
+    }
+
+    fn write(&self, object: &GitObject) -> Result<Hash> {
+        let hash = object.hash();
+        let path = self.object_path(&hash);
+        if path.exists() {
+            return Ok(hash);
+        }
+
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, object.to_raw()?)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o444))?;
+
+        Ok(hash)
+    }
+
+    fn exists(&self, hash: &Hash) -> Result<bool> {
+        Ok(self.object_path(hash).exists())
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>> {
+        if !self.directory.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut hashes = Vec::new();
+        for entry in std::fs::read_dir(&self.directory)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.len() != 2 || !name.chars().all(|c| c.is_ascii_hexdigit()) {
+                continue;
+            }
+            hashes.extend(self.hashes_in(&name)?);
+        }
+        hashes.sort();
+
+        Ok(hashes)
+    }
+
+    /// 先頭 2 文字のディレクトリだけを見る
+    fn find_by_prefix(&self, prefix: &str) -> Result<Vec<Hash>> {
+        let Some(first) = prefix.get(..2) else {
+            return Ok(Vec::new());
+        };
+
+        let mut hashes = self
+            .hashes_in(first)?
+            .into_iter()
+            .filter(|hash| hash.to_string().starts_with(prefix))
+            .collect::<Vec<_>>();
+        hashes.sort();
+
+        Ok(hashes)
+    }
+}
+impl LooseStore {
+    fn hashes_in(&self, first: &str) -> Result<Vec<Hash>> {
+        let directory = self.directory.join(first);
+        if !directory.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut hashes = Vec::new();
+        for entry in std::fs::read_dir(directory)? {
+            let name = format!("{}{}", first, entry?.file_name().to_string_lossy());
+            if let Ok(hash) = Hash::from_str(&name) {
+                hashes.push(hash);
+            }
+        }
+
+        Ok(hashes)
+    }
+}
+
+/// `objects/pack` 以下の pack に入っている object (読み込み専用)
+#[derive(Debug, Clone)]
+pub struct PackStore {
+    pub packs: Vec<Pack>,
+}
+impl PackStore {
+    pub fn open(directory: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            packs: Pack::all(directory)?,
+        })
+    }
+}
+impl ObjectDatabase for PackStore {
+    fn read(&self, hash: &Hash) -> Result<Option<GitObject>> {
+        self.read_with_bases(hash, self)
+    }
+
+    fn read_with_bases(
+        &self,
+        hash: &Hash,
+        bases: &dyn ObjectDatabase,
+    ) -> Result<Option<GitObject>> {
+        for pack in &self.packs {
+            if let Some(object) = pack.read_object(bases, hash)? {
+                return Ok(Some(object));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn write(&self, _object: &GitObject) -> Result<Hash> {
+        Err(Error::InvalidArgument(
+            "cannot write an object into a pack store".to_string(),
+        ))
+    }
+
+    fn exists(&self, hash: &Hash) -> Result<bool> {
+        Ok(self.packs.iter().any(|pack| pack.contains(hash)))
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>> {
+        let hashes = self
+            .packs
+            .iter()
+            .flat_map(|pack| pack.index.hashes.iter().map(|hash| Hash::from(*hash)))
+            .collect::<BTreeSet<_>>();
+
+        Ok(hashes.into_iter().collect())
+    }
+
+    fn find_by_prefix(&self, prefix: &str) -> Result<Vec<Hash>> {
+        let hashes = self
+            .packs
+            .iter()
+            .flat_map(|pack| pack.find_prefix(prefix))
+            .collect::<BTreeSet<_>>();
+
+        Ok(hashes.into_iter().collect())
+    }
+}
+
+/// 複数の store を順に探す。書き込みは先頭の store に行う
+#[derive(Default)]
+pub struct CompositeStore {
+    pub stores: Vec<Box<dyn ObjectDatabase>>,
+}
+impl CompositeStore {
+    pub fn new(stores: Vec<Box<dyn ObjectDatabase>>) -> Self {
+        Self { stores }
+    }
+
+    /// objects ディレクトリの loose object と pack、`info/alternates` に書かれた先を順に探す
+    pub fn open(directory: impl AsRef<Path>) -> Result<Self> {
+        let mut store = Self::default();
+        store.push_directory(directory.as_ref(), 0)?;
+
+        Ok(store)
+    }
+
+    fn push_directory(&mut self, directory: &Path, depth: usize) -> Result<()> {
+        self.stores.push(Box::new(LooseStore::new(directory)));
+        self.stores
+            .push(Box::new(PackStore::open(directory.join("pack"))?));
+
+        if depth >= MAX_ALTERNATE_DEPTH {
+            return Ok(());
+        }
+        for alternate in read_alternates(directory)? {
+            self.push_directory(&alternate, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+impl ObjectDatabase for CompositeStore {
+    /// thin pack の base が loose や alternates にあっても読めるように、全体を base の探し先として渡す
+    fn read(&self, hash: &Hash) -> Result<Option<GitObject>> {
+        for store in &self.stores {
+            if let Some(object) = store.read_with_bases(hash, self)? {
+                return Ok(Some(object));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn write(&self, object: &GitObject) -> Result<Hash> {
+        let Some(store) = self.stores.first() else {
+            return Err(Error::InvalidArgument(
+                "no object store to write into".to_string(),
+            ));
+        };
+        if self.exists(&object.hash())? {
+            return Ok(object.hash());
+        }
+
+        store.write(object)
+    }
+
+    fn exists(&self, hash: &Hash) -> Result<bool> {
+        for store in &self.stores {
+            if store.exists(hash)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>> {
+        let mut hashes = BTreeSet::new();
+        for store in &self.stores {
+            hashes.extend(store.hashes()?);
+        }
+
+        Ok(hashes.into_iter().collect())
+    }
+
+    fn find_by_prefix(&self, prefix: &str) -> Result<Vec<Hash>> {
+        let mut hashes = BTreeSet::new();
+        for store in &self.stores {
+            hashes.extend(store.find_by_prefix(prefix)?);
+        }
+
+        Ok(hashes.into_iter().collect())
+    }
+}
+
+/// ディスクに触れずにメモリ上だけで object を持つ
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    pub objects: RefCell<BTreeMap<Hash, GitObject>>,
+}
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl ObjectDatabase for MemoryStore {
+    fn read(&self, hash: &Hash) -> Result<Option<GitObject>> {
+        Ok(self.objects.borrow().get(hash).cloned())
+    }
+
+    fn write(&self, object: &GitObject) -> Result<Hash> {
+        let hash = object.hash();
+        self.objects
+            .borrow_mut()
+            .entry(hash.clone())
+            .or_insert_with(|| object.clone());
+
+        Ok(hash)
+    }
+
+    fn exists(&self, hash: &Hash) -> Result<bool> {
+        Ok(self.objects.borrow().contains_key(hash))
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>> {
+        Ok(self.objects.borrow().keys().cloned().collect())
+    }
+}
+
+/// `objects/info/alternates` に 1 行ずつ書かれた別の objects ディレクトリ (相対パスは objects からの相対)
+fn read_alternates(directory: &Path) -> Result<Vec<PathBuf>> {
+    let path = directory.join("info").join("alternates");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| directory.join(line))
+        .filter(|alternate| alternate.is_dir())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone};
+
+    use super::*;
+    use crate::entity::{
+        object::{mode::BlobType, Commit, ObjectType, User},
+        tree::{find_entry, TreeNode},
+    };
+
+    #[test]
+    fn test_memory_store_history() {
+        let store = MemoryStore::new();
+
+        let blob = GitObject::new(ObjectType::Blob, b"hello\n".to_vec());
+        let blob_hash = blob.write(&store).unwrap();
+
+        let mut root = TreeNode::new_root();
+        root.push_full_path_blob("dir/hello.txt", BlobType::Plain, blob_hash.clone())
+            .unwrap();
+        let tree = root.write_recursive(&store).unwrap();
+
+        let user = User::new(
+            "a".to_string(),
+            "a@example.com".to_string(),
+            FixedOffset::east_opt(9 * 3600)
+                .unwrap()
+                .timestamp_opt(1_700_000_000, 0)
+                .unwrap(),
+        );
+        let commit = Commit {
+            tree: tree.clone(),
+            parent: Vec::new(),
+            author: user.clone(),
+            committer: user,
+            rest_of_header: String::new(),
+            message: "initial\n".to_string(),
+        };
+        let commit_hash = GitObject::from_commit(&commit).write(&store).unwrap();
+
+        let read = GitObject::read(&store, &commit_hash)
+            .unwrap()
+            .parse_commit_body()
+            .unwrap();
+        assert_eq!(read, commit);
+        assert_eq!(
+            find_entry(&store, &read.tree, "dir/hello.txt")
+                .unwrap()
+                .map(|entry| entry.hash),
+            Some(blob_hash)
+        );
+        assert_eq!(store.hashes().unwrap().len(), 4);
+        assert_eq!(
+            GitObject::find_by_prefix(&store, &commit_hash.to_string()[..6]).unwrap(),
+            vec![commit_hash]
+        );
+    }
+
+    #[test]
+    fn test_composite_store_alternates() {
+        let directory = std::env::temp_dir().join(format!("ssgit-odb-test-{}", std::process::id()));
+        let objects = directory.join("objects");
+        let alternate = directory.join("alternate");
+        std::fs::create_dir_all(objects.join("info")).unwrap();
+        std::fs::create_dir_all(&alternate).unwrap();
+        std::fs::write(objects.join("info").join("alternates"), "../alternate\n").unwrap();
+
+        let shared = GitObject::new(ObjectType::Blob, b"shared".to_vec());
+        let shared_hash = LooseStore::new(&alternate).write(&shared).unwrap();
+
+        let store = CompositeStore::open(&objects).unwrap();
+        assert_eq!(store.read(&shared_hash).unwrap(), Some(shared));
+
+        let own = GitObject::new(ObjectType::Blob, b"own".to_vec());
+        let own_hash = store.write(&own).unwrap();
+        assert!(LooseStore::new(&objects).exists(&own_hash).unwrap());
+        assert!(!LooseStore::new(&alternate).exists(&own_hash).unwrap());
+
+        let mut expected = vec![shared_hash, own_hash];
+        expected.sort();
+        assert_eq!(store.hashes().unwrap(), expected);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,9 +1,12 @@
 use std::{path::PathBuf, str::FromStr};
 
-use anyhow::{bail, Result};
 use sha1::{Digest, Sha1};
 
-use crate::consts::OBJECTS_DIRECTORY;
+use crate::{
+    consts::OBJECTS_DIRECTORY,
+    entity::{object::database::LooseStore, repository::Repository},
+    error::{Error, Result},
+};
 
 pub fn bytes_to_hex(bytes: &[u8]) -> String {
     bytes
@@ -14,8 +17,10 @@ pub fn bytes_to_hex(bytes: &[u8]) -> String {
 }
 
 pub fn hex_to_bytes(hex: &str) -> Result<Vec<u8>> {
-    if hex.len() % 2 != 0 {
-        bail!("Hex string must be even length");
+    if !hex.len().is_multiple_of(2) {
+        return Err(Error::InvalidArgument(
+            "Hex string must be even length".to_string(),
+        ));
     }
 
     let mut bytes = Vec::new();
@@ -35,7 +40,10 @@ pub fn hex_to_fixed_bytes<const N: usize>(hex: &str) -> Result<[u8; N]> {
     let bytes = hex_to_bytes(hex)?;
 
     if bytes.len() != N {
-        bail!("Hex string must be {} bytes long", N);
+        return Err(Error::InvalidArgument(format!(
+            "Hex string must be {} bytes long",
+            N
+        )));
     }
 
     let mut fixed_bytes = [0; N];
@@ -44,16 +52,18 @@ pub fn hex_to_fixed_bytes<const N: usize>(hex: &str) -> Result<[u8; N]> {
     Ok(fixed_bytes)
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord)]
 pub struct Hash {
     value: String,
 }
 impl FromStr for Hash {
-    type Err = anyhow::Error;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.len() != 40 {
-            bail!("Hash must be 40 characters long");
+            return Err(Error::InvalidArgument(
+                "Hash must be 40 characters long".to_string(),
+            ));
         }
 
         Ok(Self {
@@ -69,11 +79,13 @@ impl From<[u8; 20]> for Hash {
     }
 }
 impl TryFrom<&[u8]> for Hash {
-    type Error = anyhow::Error;
+    type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self> {
         if bytes.len() != 20 {
-            bail!("Hash must be 20 bytes long");
+            return Err(Error::InvalidArgument(
+                "Hash must be 20 bytes long".to_string(),
+            ));
         }
 
         Ok(Self {
@@ -102,6 +114,10 @@ impl Hash {
         hex_to_bytes(&self.value).unwrap()
     }
 
+    pub fn to_raw_fixed(&self) -> [u8; 20] {
+        hex_to_fixed_bytes(&self.value).unwrap()
+    }
+
     #[allow(clippy::self_named_constructors)]
     pub fn hash(content: &str) -> Self {
         Self::hash_bytes(content.as_bytes())
@@ -113,10 +129,8 @@ impl Hash {
         Self::from(hash)
     }
 
-    pub fn get_object_path(&self) -> PathBuf {
-        std::path::Path::new(OBJECTS_DIRECTORY)
-            .join(self.directory())
-            .join(self.file())
+    pub fn get_object_path(&self, repo: &Repository) -> PathBuf {
+        LooseStore::new(repo.git_path(OBJECTS_DIRECTORY)).object_path(self)
     }
 }
 impl std::fmt::Display for Hash {
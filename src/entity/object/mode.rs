@@ -1,6 +1,6 @@
 use std::{fs::Metadata, os::unix::fs::PermissionsExt, str::FromStr};
 
-use anyhow::{bail, Result};
+use crate::error::{Error, Result};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BlobType {
@@ -15,7 +15,7 @@ pub enum Mode {
     Tree,
 }
 impl FromStr for Mode {
-    type Err = anyhow::Error;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
@@ -23,12 +23,12 @@ impl FromStr for Mode {
             "100644" => Ok(Self::Blob(BlobType::Plain)),
             "100755" => Ok(Self::Blob(BlobType::Executable)),
             "120000" => Ok(Self::Blob(BlobType::Symlink)),
-            _ => bail!("Invalid object type {}", s),
+            _ => Err(Error::InvalidArgument(format!("Invalid object type {}", s))),
         }
     }
 }
 impl TryFrom<u32> for Mode {
-    type Error = anyhow::Error;
+    type Error = Error;
 
     fn try_from(value: u32) -> Result<Self, Self::Error> {
         match value {
@@ -36,7 +36,10 @@ impl TryFrom<u32> for Mode {
             0o100644 => Ok(Self::Blob(BlobType::Plain)),
             0o100755 => Ok(Self::Blob(BlobType::Executable)),
             0o120000 => Ok(Self::Blob(BlobType::Symlink)),
-            _ => bail!("Invalid object type {}", value),
+            _ => Err(Error::InvalidArgument(format!(
+                "Invalid object type {}",
+                value
+            ))),
         }
     }
 }
@@ -70,7 +73,13 @@ impl Mode {
 
     pub fn from_file_metadata(metadata: &Metadata) -> Result<Self> {
         if metadata.is_dir() {
-            bail!("Cannot determine mode for directory");
+            return Err(Error::InvalidArgument(
+                "Cannot determine mode for directory".to_string(),
+            ));
+        }
+
+        if metadata.is_symlink() {
+            return Ok(Self::Blob(BlobType::Symlink));
         }
 
         let mode = metadata.permissions().mode();
@@ -0,0 +1,473 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::ZlibDecoder, Crc};
+
+use crate::error::{Error, Result};
+
+use self::{
+    delta::{apply_delta, create_delta},
+    index::PackIndex,
+};
+
+use super::{database::ObjectDatabase, hash::Hash, zip::compress, GitObject, ObjectType};
+
+pub mod delta;
+pub mod index;
+
+const PACK_SIGNATURE: &[u8] = b"PACK";
+const SHA1_LENGTH: usize = 20;
+
+/// git が許容する delta chain の長さより十分大きい値
+const MAX_DELTA_DEPTH: usize = 10_000;
+
+/// 書き込み時に delta の base として探す直前のオブジェクト数
+const DELTA_WINDOW: usize = 10;
+/// 書き込み時の delta chain の最大長 (git のデフォルトと同じ)
+const MAX_WRITE_DELTA_DEPTH: usize = 50;
+/// これより小さいオブジェクトは delta にしない
+const MIN_DELTA_SIZE: usize = 64;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum PackObjectType {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+    OfsDelta,
+    RefDelta,
+}
+impl TryFrom<u8> for PackObjectType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Commit),
+            2 => Ok(Self::Tree),
+            3 => Ok(Self::Blob),
+            4 => Ok(Self::Tag),
+            6 => Ok(Self::OfsDelta),
+            7 => Ok(Self::RefDelta),
+            _ => Err(Error::CorruptObject(format!(
+                "Invalid pack object type {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl From<ObjectType> for PackObjectType {
+    fn from(object_type: ObjectType) -> Self {
+        match object_type {
+            ObjectType::Commit => Self::Commit,
+            ObjectType::Tree => Self::Tree,
+            ObjectType::Blob => Self::Blob,
+            ObjectType::Tag => Self::Tag,
+        }
+    }
+}
+impl From<PackObjectType> for u8 {
+    fn from(pack_object_type: PackObjectType) -> Self {
+        match pack_object_type {
+            PackObjectType::Commit => 1,
+            PackObjectType::Tree => 2,
+            PackObjectType::Blob => 3,
+            PackObjectType::Tag => 4,
+            PackObjectType::OfsDelta => 6,
+            PackObjectType::RefDelta => 7,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum PackEntry {
+    Base(ObjectType, Vec<u8>),
+    OfsDelta(u64, Vec<u8>),
+    RefDelta(Hash, Vec<u8>),
+}
+
+/// `.pack` と対応する `.idx` の組
+#[derive(Debug, Clone)]
+pub struct Pack {
+    pub pack_path: PathBuf,
+    pub index: PackIndex,
+}
+impl Pack {
+    pub fn open(index_path: impl AsRef<Path>) -> Result<Self> {
+        let index_path = index_path.as_ref();
+        let index = PackIndex::from_raw(&std::fs::read(index_path)?)?;
+        let pack_path = index_path.with_extension("pack");
+
+        let mut file = File::open(&pack_path)?;
+        let mut header = [0; 12];
+        file.read_exact(&mut header)?;
+        if &header[..4] != PACK_SIGNATURE {
+            return Err(Error::CorruptObject(format!(
+                "Invalid pack signature: {}",
+                pack_path.display()
+            )));
+        }
+        let version = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        if version != 2 && version != 3 {
+            return Err(Error::CorruptObject(format!(
+                "Unsupported pack version {}",
+                version
+            )));
+        }
+
+        let mut checksum = [0; SHA1_LENGTH];
+        file.seek(SeekFrom::End(-(SHA1_LENGTH as i64)))?;
+        file.read_exact(&mut checksum)?;
+        if checksum != index.pack_checksum {
+            return Err(Error::CorruptObject(format!(
+                "Pack checksum did not match index: {}",
+                pack_path.display()
+            )));
+        }
+
+        Ok(Self { pack_path, index })
+    }
+
+    /// `objects/pack` 以下の全ての pack を開く
+    pub fn all(directory: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let directory = directory.as_ref();
+        if !directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut index_paths = Vec::new();
+        for entry in std::fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "idx") {
+                index_paths.push(path);
+            }
+        }
+        index_paths.sort();
+
+        index_paths.iter().map(Self::open).collect()
+    }
+
+    /// objects を 1 つの pack として `objects/pack` に書き出す
+    pub fn write(directory: impl AsRef<Path>, objects: &[GitObject]) -> Result<Self> {
+        let (pack_bytes, index) = Self::build(objects)?;
+
+        let directory = directory.as_ref();
+        std::fs::create_dir_all(directory)?;
+
+        let name = format!("pack-{}", Hash::from(index.pack_checksum));
+        let pack_path = directory.join(format!("{}.pack", name));
+        let index_path = directory.join(format!("{}.idx", name));
+
+        // idx が先に存在すると読み込み側が不完全な pack を開いてしまうので pack から書く
+        std::fs::write(&pack_path, pack_bytes)?;
+        std::fs::write(&index_path, index.to_raw())?;
+        for path in [&pack_path, &index_path] {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o444))?;
+        }
+
+        Ok(Self { pack_path, index })
+    }
+
+    fn build(objects: &[GitObject]) -> Result<(Vec<u8>, PackIndex)> {
+        // 同じ種類で大きさの近いものが並ぶようにして、delta の base を探しやすくする
+        let mut order = (0..objects.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| {
+            (
+                u8::from(PackObjectType::from(objects[i].type_)),
+                std::cmp::Reverse(objects[i].size()),
+            )
+        });
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(PACK_SIGNATURE);
+        bytes.extend_from_slice(&2u32.to_be_bytes());
+        bytes.extend_from_slice(&(objects.len() as u32).to_be_bytes());
+
+        let mut offsets: Vec<u64> = Vec::with_capacity(objects.len());
+        let mut depths: Vec<usize> = Vec::with_capacity(objects.len());
+        let mut index_entries = Vec::with_capacity(objects.len());
+
+        for (position, &i) in order.iter().enumerate() {
+            let object = &objects[i];
+            let offset = bytes.len() as u64;
+
+            let mut best: Option<(usize, Vec<u8>)> = None;
+            if object.size() >= MIN_DELTA_SIZE {
+                for base_position in position.saturating_sub(DELTA_WINDOW)..position {
+                    let base = &objects[order[base_position]];
+                    if base.type_ != object.type_ || depths[base_position] >= MAX_WRITE_DELTA_DEPTH
+                    {
+                        continue;
+                    }
+
+                    let delta = create_delta(&base.body, &object.body);
+                    let limit = best
+                        .as_ref()
+                        .map_or(object.size() / 2, |(_, best_delta)| best_delta.len());
+                    if delta.len() < limit {
+                        best = Some((base_position, delta));
+                    }
+                }
+            }
+
+            let mut entry = Vec::new();
+            match best {
+                Some((base_position, delta)) => {
+                    write_entry_header(&mut entry, PackObjectType::OfsDelta, delta.len());
+                    write_base_offset(&mut entry, offset - offsets[base_position]);
+                    entry.extend(compress(&delta)?);
+                    depths.push(depths[base_position] + 1);
+                }
+                None => {
+                    write_entry_header(&mut entry, object.type_.into(), object.size());
+                    entry.extend(compress(&object.body)?);
+                    depths.push(0);
+                }
+            }
+
+            let mut crc = Crc::new();
+            crc.update(&entry);
+
+            index_entries.push((object.hash().to_raw_fixed(), crc.sum(), offset));
+            offsets.push(offset);
+            bytes.extend(entry);
+        }
+
+        let checksum = Hash::hash_bytes(&bytes).to_raw_fixed();
+        bytes.extend_from_slice(&checksum);
+
+        Ok((bytes, PackIndex::new(index_entries, checksum)))
+    }
+
+    pub fn find_prefix(&self, prefix: &str) -> Vec<Hash> {
+        self.index.find_prefix(prefix)
+    }
+
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.index.find_offset(hash).is_some()
+    }
+
+    pub fn read_object(&self, db: &dyn ObjectDatabase, hash: &Hash) -> Result<Option<GitObject>> {
+        let Some(offset) = self.index.find_offset(hash) else {
+            return Ok(None);
+        };
+
+        self.read_object_at(db, offset).map(Some)
+    }
+
+    fn read_object_at(&self, db: &dyn ObjectDatabase, offset: u64) -> Result<GitObject> {
+        let mut file = File::open(&self.pack_path)?;
+
+        let mut deltas = Vec::new();
+        let mut offset = offset;
+        let (type_, mut body) = loop {
+            if deltas.len() > MAX_DELTA_DEPTH {
+                return Err(Error::CorruptObject("Delta chain is too deep".to_string()));
+            }
+
+            match Self::read_entry(&mut file, offset)? {
+                PackEntry::Base(type_, body) => break (type_, body),
+                PackEntry::OfsDelta(base_offset, delta) => {
+                    deltas.push(delta);
+                    offset = base_offset;
+                }
+                PackEntry::RefDelta(base_hash, delta) => {
+                    deltas.push(delta);
+                    match self.index.find_offset(&base_hash) {
+                        Some(base_offset) => offset = base_offset,
+                        None => {
+                            // thin pack など、base が pack の外にある場合
+                            let base = GitObject::read(db, &base_hash)?;
+                            break (base.type_, base.body);
+                        }
+                    }
+                }
+            }
+        };
+
+        for delta in deltas.iter().rev() {
+            body = apply_delta(&body, delta)?;
+        }
+
+        Ok(GitObject::new(type_, body))
+    }
+
+    fn read_entry(file: &mut File, offset: u64) -> Result<PackEntry> {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::new(file);
+
+        let mut byte = read_byte(&mut reader)?;
+        let type_ = PackObjectType::try_from((byte >> 4) & 0x07)?;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            byte = read_byte(&mut reader)?;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+
+        match type_ {
+            PackObjectType::Commit => {
+                Ok(PackEntry::Base(ObjectType::Commit, inflate(reader, size)?))
+            }
+            PackObjectType::Tree => Ok(PackEntry::Base(ObjectType::Tree, inflate(reader, size)?)),
+            PackObjectType::Blob => Ok(PackEntry::Base(ObjectType::Blob, inflate(reader, size)?)),
+            PackObjectType::Tag => Ok(PackEntry::Base(ObjectType::Tag, inflate(reader, size)?)),
+            PackObjectType::OfsDelta => {
+                let mut byte = read_byte(&mut reader)?;
+                let mut relative = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    byte = read_byte(&mut reader)?;
+                    relative = ((relative + 1) << 7) | (byte & 0x7f) as u64;
+                }
+                if relative > offset {
+                    return Err(Error::CorruptObject(
+                        "Delta base offset is out of range".to_string(),
+                    ));
+                }
+
+                Ok(PackEntry::OfsDelta(
+                    offset - relative,
+                    inflate(reader, size)?,
+                ))
+            }
+            PackObjectType::RefDelta => {
+                let mut raw = [0; SHA1_LENGTH];
+                reader.read_exact(&mut raw)?;
+
+                Ok(PackEntry::RefDelta(Hash::from(raw), inflate(reader, size)?))
+            }
+        }
+    }
+}
+
+fn write_entry_header(bytes: &mut Vec<u8>, type_: PackObjectType, size: usize) {
+    let mut byte = (u8::from(type_) << 4) | (size & 0x0f) as u8;
+    let mut size = size >> 4;
+
+    while size > 0 {
+        bytes.push(byte | 0x80);
+        byte = (size & 0x7f) as u8;
+        size >>= 7;
+    }
+    bytes.push(byte);
+}
+
+fn write_base_offset(bytes: &mut Vec<u8>, relative: u64) {
+    let mut relative = relative;
+    let mut encoded = vec![(relative & 0x7f) as u8];
+    relative >>= 7;
+
+    while relative > 0 {
+        relative -= 1;
+        encoded.push(0x80 | (relative & 0x7f) as u8);
+        relative >>= 7;
+    }
+    encoded.reverse();
+
+    bytes.extend(encoded);
+}
+
+fn inflate(reader: impl Read, size: usize) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size);
+    ZlibDecoder::new(reader).read_to_end(&mut data)?;
+    if data.len() != size {
+        return Err(Error::CorruptObject(
+            "Pack entry size did not match".to_string(),
+        ));
+    }
+
+    Ok(data)
+}
+
+fn read_byte(reader: &mut impl Read) -> Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::object::database::{CompositeStore, LooseStore, MemoryStore};
+
+    #[test]
+    fn test_build_and_read() {
+        let base = (0..500)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let objects = vec![
+            GitObject::new(ObjectType::Blob, base.as_bytes().to_vec()),
+            GitObject::new(ObjectType::Blob, (base.clone() + "more\n").into_bytes()),
+            GitObject::new(ObjectType::Blob, (base + "more\nand more\n").into_bytes()),
+            GitObject::new(ObjectType::Blob, b"small".to_vec()),
+        ];
+
+        let (pack_bytes, index) = Pack::build(&objects).unwrap();
+
+        let directory =
+            std::env::temp_dir().join(format!("ssgit-pack-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let index_path = directory.join("test.idx");
+        std::fs::write(directory.join("test.pack"), pack_bytes).unwrap();
+        std::fs::write(&index_path, index.to_raw()).unwrap();
+
+        let pack = Pack::open(&index_path).unwrap();
+        for object in &objects {
+            assert_eq!(
+                pack.read_object(&MemoryStore::new(), &object.hash())
+                    .unwrap()
+                    .as_ref(),
+                Some(object)
+            );
+        }
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_ref_delta_base_outside_pack() {
+        let base_text = (0..100)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let base = GitObject::new(ObjectType::Blob, base_text.as_bytes().to_vec());
+        let target = GitObject::new(ObjectType::Blob, (base_text + "more\n").into_bytes());
+
+        // base を含まない thin pack を組み立てる
+        let delta = create_delta(&base.body, &target.body);
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(PACK_SIGNATURE);
+        bytes.extend_from_slice(&2u32.to_be_bytes());
+        bytes.extend_from_slice(&1u32.to_be_bytes());
+        let offset = bytes.len() as u64;
+        let mut entry = Vec::new();
+        write_entry_header(&mut entry, PackObjectType::RefDelta, delta.len());
+        entry.extend_from_slice(&base.hash().to_raw_fixed());
+        entry.extend(compress(&delta).unwrap());
+        let mut crc = Crc::new();
+        crc.update(&entry);
+        bytes.extend(entry);
+        let checksum = Hash::hash_bytes(&bytes).to_raw_fixed();
+        bytes.extend_from_slice(&checksum);
+        let index = PackIndex::new(
+            vec![(target.hash().to_raw_fixed(), crc.sum(), offset)],
+            checksum,
+        );
+
+        let objects =
+            std::env::temp_dir().join(format!("ssgit-thin-pack-test-{}", std::process::id()));
+        std::fs::create_dir_all(objects.join("pack")).unwrap();
+        std::fs::write(objects.join("pack/thin.pack"), bytes).unwrap();
+        std::fs::write(objects.join("pack/thin.idx"), index.to_raw()).unwrap();
+        LooseStore::new(&objects).write(&base).unwrap();
+
+        let store = CompositeStore::open(&objects).unwrap();
+        assert_eq!(store.read(&target.hash()).unwrap(), Some(target));
+
+        std::fs::remove_dir_all(objects).unwrap();
+    }
+}
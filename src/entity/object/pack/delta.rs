@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+/// base を索引化する際のブロック長
+const BLOCK_LENGTH: usize = 16;
+/// insert 命令 1 つで挿入できる最大長
+const MAX_INSERT_LENGTH: usize = 0x7f;
+/// copy 命令 1 つでコピーする最大長
+const MAX_COPY_LENGTH: usize = 0x10000;
+
+/// delta の先頭にある可変長のサイズを読む (little endian, 7 bit ずつ)
+fn read_size(delta: &[u8], shown_index: &mut usize) -> Result<usize> {
+    let mut size = 0;
+    let mut shift = 0;
+
+    loop {
+        let Some(&byte) = delta.get(*shown_index) else {
+            bail!("Unexpected end of delta");
+        };
+        *shown_index += 1;
+
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+fn write_size(delta: &mut Vec<u8>, mut size: usize) {
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+
+        if size == 0 {
+            delta.push(byte);
+            return;
+        }
+        delta.push(byte | 0x80);
+    }
+}
+
+fn write_insert(delta: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(MAX_INSERT_LENGTH) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+fn write_copy(delta: &mut Vec<u8>, mut offset: usize, mut size: usize) {
+    while size > 0 {
+        let chunk_size = size.min(MAX_COPY_LENGTH);
+
+        let mut instruction = 0x80;
+        let mut arguments = Vec::with_capacity(7);
+        for i in 0..4 {
+            let byte = ((offset >> (i * 8)) & 0xff) as u8;
+            if byte != 0 {
+                instruction |= 1 << i;
+                arguments.push(byte);
+            }
+        }
+        // 0x10000 は size を省略することで表す
+        if chunk_size != MAX_COPY_LENGTH {
+            for i in 0..3 {
+                let byte = ((chunk_size >> (i * 8)) & 0xff) as u8;
+                if byte != 0 {
+                    instruction |= 1 << (4 + i);
+                    arguments.push(byte);
+                }
+            }
+        }
+
+        delta.push(instruction);
+        delta.extend(arguments);
+
+        offset += chunk_size;
+        size -= chunk_size;
+    }
+}
+
+/// base から target を復元する delta を作る
+pub fn create_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_size(&mut delta, base.len());
+    write_size(&mut delta, target.len());
+
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    for (i, block) in base.chunks_exact(BLOCK_LENGTH).enumerate() {
+        blocks.entry(block).or_insert(i * BLOCK_LENGTH);
+    }
+
+    let mut pending_start = 0;
+    let mut position = 0;
+    while position + BLOCK_LENGTH <= target.len() {
+        let Some(&base_start) = blocks.get(&target[position..position + BLOCK_LENGTH]) else {
+            position += 1;
+            continue;
+        };
+
+        // 一致した範囲を前後に広げる
+        let mut start = position;
+        let mut base_start = base_start;
+        while start > pending_start && base_start > 0 && target[start - 1] == base[base_start - 1] {
+            start -= 1;
+            base_start -= 1;
+        }
+        let mut length = position - start + BLOCK_LENGTH;
+        while start + length < target.len()
+            && base_start + length < base.len()
+            && target[start + length] == base[base_start + length]
+        {
+            length += 1;
+        }
+
+        write_insert(&mut delta, &target[pending_start..start]);
+        write_copy(&mut delta, base_start, length);
+
+        position = start + length;
+        pending_start = position;
+    }
+    write_insert(&mut delta, &target[pending_start..]);
+
+    delta
+}
+
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut shown_index = 0;
+
+    let base_size = read_size(delta, &mut shown_index)?;
+    if base_size != base.len() {
+        bail!("Delta base size did not match base length");
+    }
+    let result_size = read_size(delta, &mut shown_index)?;
+
+    let mut result = Vec::with_capacity(result_size);
+
+    while shown_index < delta.len() {
+        let instruction = delta[shown_index];
+        shown_index += 1;
+
+        if instruction & 0x80 != 0 {
+            // copy: 下位 4 bit が offset, 続く 3 bit が size の存在フラグ
+            let mut offset = 0;
+            for i in 0..4 {
+                if instruction & (1 << i) != 0 {
+                    let Some(&byte) = delta.get(shown_index) else {
+                        bail!("Unexpected end of delta");
+                    };
+                    offset |= (byte as usize) << (i * 8);
+                    shown_index += 1;
+                }
+            }
+
+            let mut size = 0;
+            for i in 0..3 {
+                if instruction & (1 << (4 + i)) != 0 {
+                    let Some(&byte) = delta.get(shown_index) else {
+                        bail!("Unexpected end of delta");
+                    };
+                    size |= (byte as usize) << (i * 8);
+                    shown_index += 1;
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+
+            let Some(chunk) = base.get(offset..offset + size) else {
+                bail!("Delta copy is out of range");
+            };
+            result.extend_from_slice(chunk);
+        } else if instruction != 0 {
+            // insert: instruction 自体が挿入するバイト数
+            let size = instruction as usize;
+            let Some(chunk) = delta.get(shown_index..shown_index + size) else {
+                bail!("Unexpected end of delta");
+            };
+            result.extend_from_slice(chunk);
+            shown_index += size;
+        } else {
+            bail!("Invalid delta instruction 0");
+        }
+    }
+
+    if result.len() != result_size {
+        bail!("Delta result size did not match");
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_delta() {
+        let base = b"Hello, world!";
+        // base size 13, result size 19
+        // copy offset 0, size 7 ("Hello, ")
+        // insert "ssgit"
+        // copy offset 12, size 1 ("!")
+        // insert " :)"
+        let delta = [
+            13, 19, 0x90, 7, 5, b's', b's', b'g', b'i', b't', 0x91, 12, 1, 6, b'!', b'!', b' ',
+            b':', b')', b' ',
+        ];
+
+        let result = apply_delta(base, &delta).unwrap();
+
+        assert_eq!(result, b"Hello, ssgit!!! :) ");
+    }
+
+    #[test]
+    fn test_create_delta_round_trip() {
+        let base = (0..2000)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let target = base.replace("line 1000\n", "changed\n") + "appended\n";
+
+        let delta = create_delta(base.as_bytes(), target.as_bytes());
+
+        assert!(delta.len() < target.len() / 10);
+        assert_eq!(
+            apply_delta(base.as_bytes(), &delta).unwrap(),
+            target.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_create_delta_large_copy() {
+        let base = vec![b'a'; MAX_COPY_LENGTH * 2 + 5];
+        let target = [b"head".as_slice(), &base].concat();
+
+        let delta = create_delta(&base, &target);
+
+        assert_eq!(apply_delta(&base, &delta).unwrap(), target);
+    }
+
+    #[test]
+    fn test_apply_delta_wrong_base_size() {
+        let base = b"Hello";
+        let delta = [4, 1, 1, b'a'];
+
+        assert!(apply_delta(base, &delta).is_err());
+    }
+}
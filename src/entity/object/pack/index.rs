@@ -0,0 +1,198 @@
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::entity::object::hash::Hash;
+
+const IDX_SIGNATURE: &[u8] = b"\xfftOc";
+const IDX_VERSION: u32 = 2;
+const FANOUT_LENGTH: usize = 256;
+const SHA1_LENGTH: usize = 20;
+const LARGE_OFFSET_FLAG: u32 = 0x8000_0000;
+
+/// `.idx` (version 2) の中身
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackIndex {
+    pub fanout: [u32; FANOUT_LENGTH],
+    pub hashes: Vec<[u8; SHA1_LENGTH]>,
+    pub crc32s: Vec<u32>,
+    pub offsets: Vec<u64>,
+    pub pack_checksum: [u8; SHA1_LENGTH],
+}
+impl PackIndex {
+    pub fn from_raw(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 8 + FANOUT_LENGTH * 4 + SHA1_LENGTH * 2 {
+            bail!("Pack index is too short");
+        }
+        if &bytes[..4] != IDX_SIGNATURE {
+            bail!("Unsupported pack index (only version 2 is supported)");
+        }
+        let version = BigEndian::read_u32(&bytes[4..8]);
+        if version != IDX_VERSION {
+            bail!("Unsupported pack index version {}", version);
+        }
+
+        let trailer_start = bytes.len() - SHA1_LENGTH;
+        if Hash::hash_bytes(&bytes[..trailer_start]).to_raw() != bytes[trailer_start..] {
+            bail!("Pack index checksum mismatch");
+        }
+
+        let mut shown_index = 8;
+
+        let mut fanout = [0; FANOUT_LENGTH];
+        for count in fanout.iter_mut() {
+            *count = BigEndian::read_u32(&bytes[shown_index..shown_index + 4]);
+            shown_index += 4;
+        }
+        let object_count = fanout[FANOUT_LENGTH - 1] as usize;
+
+        let tables_length = object_count * (SHA1_LENGTH + 4 + 4);
+        if shown_index + tables_length + SHA1_LENGTH * 2 > bytes.len() {
+            bail!("Pack index is truncated");
+        }
+
+        let mut hashes = Vec::with_capacity(object_count);
+        for _ in 0..object_count {
+            let mut hash = [0; SHA1_LENGTH];
+            hash.copy_from_slice(&bytes[shown_index..shown_index + SHA1_LENGTH]);
+            hashes.push(hash);
+            shown_index += SHA1_LENGTH;
+        }
+
+        let mut crc32s = Vec::with_capacity(object_count);
+        for _ in 0..object_count {
+            crc32s.push(BigEndian::read_u32(&bytes[shown_index..shown_index + 4]));
+            shown_index += 4;
+        }
+
+        let mut small_offsets = Vec::with_capacity(object_count);
+        for _ in 0..object_count {
+            small_offsets.push(BigEndian::read_u32(&bytes[shown_index..shown_index + 4]));
+            shown_index += 4;
+        }
+
+        let large_offsets_start = shown_index;
+        let mut offsets = Vec::with_capacity(object_count);
+        for offset in small_offsets {
+            if offset & LARGE_OFFSET_FLAG == 0 {
+                offsets.push(offset as u64);
+                continue;
+            }
+
+            let position = large_offsets_start + (offset & !LARGE_OFFSET_FLAG) as usize * 8;
+            if position + 8 > trailer_start - SHA1_LENGTH {
+                bail!("Pack index large offset is out of range");
+            }
+            offsets.push(BigEndian::read_u64(&bytes[position..position + 8]));
+        }
+
+        let mut pack_checksum = [0; SHA1_LENGTH];
+        pack_checksum.copy_from_slice(&bytes[trailer_start - SHA1_LENGTH..trailer_start]);
+
+        Ok(Self {
+            fanout,
+            hashes,
+            crc32s,
+            offsets,
+            pack_checksum,
+        })
+    }
+
+    /// `(hash, crc32, offset)` の組から index を作る
+    pub fn new(
+        mut entries: Vec<([u8; SHA1_LENGTH], u32, u64)>,
+        pack_checksum: [u8; SHA1_LENGTH],
+    ) -> Self {
+        entries.sort_by_key(|(hash, _, _)| *hash);
+
+        let mut fanout = [0; FANOUT_LENGTH];
+        for (hash, _, _) in &entries {
+            fanout[hash[0] as usize] += 1;
+        }
+        for i in 1..FANOUT_LENGTH {
+            fanout[i] += fanout[i - 1];
+        }
+
+        Self {
+            fanout,
+            hashes: entries.iter().map(|(hash, _, _)| *hash).collect(),
+            crc32s: entries.iter().map(|(_, crc32, _)| *crc32).collect(),
+            offsets: entries.iter().map(|(_, _, offset)| *offset).collect(),
+            pack_checksum,
+        }
+    }
+
+    pub fn to_raw(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(IDX_SIGNATURE);
+        bytes.extend_from_slice(&IDX_VERSION.to_be_bytes());
+        for count in &self.fanout {
+            bytes.extend_from_slice(&count.to_be_bytes());
+        }
+        for hash in &self.hashes {
+            bytes.extend_from_slice(hash);
+        }
+        for crc32 in &self.crc32s {
+            bytes.extend_from_slice(&crc32.to_be_bytes());
+        }
+
+        let mut large_offsets = Vec::new();
+        for offset in &self.offsets {
+            if *offset < LARGE_OFFSET_FLAG as u64 {
+                bytes.extend_from_slice(&(*offset as u32).to_be_bytes());
+            } else {
+                let position = large_offsets.len() as u32 | LARGE_OFFSET_FLAG;
+                bytes.extend_from_slice(&position.to_be_bytes());
+                large_offsets.push(*offset);
+            }
+        }
+        for offset in large_offsets {
+            bytes.extend_from_slice(&offset.to_be_bytes());
+        }
+
+        bytes.extend_from_slice(&self.pack_checksum);
+        let checksum = Hash::hash_bytes(&bytes).to_raw();
+        bytes.extend_from_slice(&checksum);
+
+        bytes
+    }
+
+    /// pack 内での offset を返す
+    pub fn find_offset(&self, hash: &Hash) -> Option<u64> {
+        let raw = hash.to_raw_fixed();
+        let first = raw[0] as usize;
+
+        let start = if first == 0 {
+            0
+        } else {
+            self.fanout[first - 1] as usize
+        };
+        let end = self.fanout[first] as usize;
+
+        self.hashes[start..end]
+            .binary_search(&raw)
+            .ok()
+            .map(|position| self.offsets[start + position])
+    }
+
+    /// 16 進数の prefix (2 文字以上) に一致する hash を全て返す
+    pub fn find_prefix(&self, prefix: &str) -> Vec<Hash> {
+        let Some(first) = prefix.get(..2).and_then(|s| u8::from_str_radix(s, 16).ok()) else {
+            return Vec::new();
+        };
+        let first = first as usize;
+
+        let start = if first == 0 {
+            0
+        } else {
+            self.fanout[first - 1] as usize
+        };
+        let end = self.fanout[first] as usize;
+
+        self.hashes[start..end]
+            .iter()
+            .map(|hash| Hash::from(*hash))
+            .filter(|hash| hash.to_string().starts_with(prefix))
+            .collect()
+    }
+}
@@ -1,6 +1,6 @@
 use std::io::{Read, Write};
 
-use anyhow::Result;
+use crate::error::Result;
 use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 
 pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
@@ -0,0 +1,277 @@
+use std::{path::PathBuf, str::FromStr};
+
+use crate::{
+    consts::PACKED_REFS_PATH,
+    entity::{
+        lock::LockFile,
+        object::{hash::Hash, GitObject, ObjectType},
+        refs::{loose_ref_names, remove_empty_parents},
+        repository::Repository,
+    },
+    error::{Error, Result},
+};
+
+const HEADER: &str = "# pack-refs with: peeled fully-peeled sorted ";
+
+/// `packed-refs` の 1 つの ref
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedRef {
+    /// `refs/heads/main` のような完全な名前
+    pub name: String,
+    pub hash: Hash,
+    /// annotated tag なら tag 以外になるまで剥がした先 (`^` の行)
+    pub peeled: Option<Hash>,
+}
+
+/// `git gc` や `pack-refs` が ref をまとめて書き出す `packed-refs`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PackedRefs {
+    /// 名前順に並んでいる
+    pub refs: Vec<PackedRef>,
+}
+impl PackedRefs {
+    pub fn path(repo: &Repository) -> PathBuf {
+        repo.git_path(PACKED_REFS_PATH)
+    }
+
+    /// ファイルがなければ空として扱う
+    pub fn read(repo: &Repository) -> Result<Self> {
+        let path = Self::path(repo);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    pub fn lock(repo: &Repository) -> Result<LockFile> {
+        LockFile::acquire(&Self::path(repo))
+    }
+
+    pub fn write(&self, repo: &Repository) -> Result<()> {
+        self.commit(Self::lock(repo)?)
+    }
+
+    /// lock を取った状態で書き出す (ref が 1 つもなければファイルを消す)
+    pub fn commit(&self, mut lock: LockFile) -> Result<()> {
+        if self.refs.is_empty() {
+            return lock.delete();
+        }
+
+        lock.write(self.to_string().as_bytes())?;
+        lock.commit()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PackedRef> {
+        self.refs
+            .binary_search_by(|packed| packed.name.as_str().cmp(name))
+            .ok()
+            .map(|i| &self.refs[i])
+    }
+
+    /// 同じ名前のものがあれば置き換える
+    pub fn insert(&mut self, packed: PackedRef) {
+        match self
+            .refs
+            .binary_search_by(|existing| existing.name.cmp(&packed.name))
+        {
+            Ok(i) => self.refs[i] = packed,
+            Err(i) => self.refs.insert(i, packed),
+        }
+    }
+
+    /// 取り除いたら true を返す
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.refs.len();
+        self.refs.retain(|packed| packed.name != name);
+
+        self.refs.len() != before
+    }
+}
+/// loose な ref を packed-refs にまとめる
+///
+/// all でなければ tag と既に packed-refs にある ref だけを対象にする。
+/// prune なら packed-refs に書いた後で、その間に変更されていない loose な ref を消す
+pub fn pack_refs(repo: &Repository, all: bool, prune: bool) -> Result<()> {
+    let lock = PackedRefs::lock(repo)?;
+    let mut packed_refs = PackedRefs::read(repo)?;
+
+    let mut packed = Vec::new();
+    for name in loose_ref_names(repo)? {
+        if !all && !name.starts_with("refs/tags/") && packed_refs.get(&name).is_none() {
+            continue;
+        }
+        let content = std::fs::read_to_string(repo.git_path(&name))?;
+        // symref は packed-refs に入れられない
+        let Ok(hash) = Hash::from_str(content.trim()) else {
+            continue;
+        };
+
+        packed_refs.insert(PackedRef {
+            name: name.clone(),
+            peeled: peel_tag(repo, &hash)?,
+            hash: hash.clone(),
+        });
+        packed.push((name, hash));
+    }
+    packed_refs.commit(lock)?;
+
+    if prune {
+        for (name, hash) in packed {
+            let lock = LockFile::acquire(&repo.git_path(&name))?;
+            let content = std::fs::read_to_string(repo.git_path(&name))?;
+            if content.trim() == hash.to_string() {
+                lock.delete()?;
+                remove_empty_parents(repo, &name)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// annotated tag なら tag 以外の object になるまで剥がす
+pub fn peel_tag(repo: &Repository, hash: &Hash) -> Result<Option<Hash>> {
+    let mut object = GitObject::read(repo, hash)?;
+    if object.type_ != ObjectType::Tag {
+        return Ok(None);
+    }
+
+    loop {
+        let target = object.parse_tag_body()?.object;
+        object = GitObject::read(repo, &target)?;
+        if object.type_ != ObjectType::Tag {
+            return Ok(Some(target));
+        }
+    }
+}
+
+impl FromStr for PackedRefs {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut refs: Vec<PackedRef> = Vec::new();
+
+        for line in s.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(peeled) = line.strip_prefix('^') {
+                let last = refs.last_mut().ok_or(Error::InvalidRef(format!(
+                    "unexpected line in packed-refs: {}",
+                    line
+                )))?;
+                last.peeled = Some(Hash::from_str(peeled)?);
+                continue;
+            }
+
+            let (hash, name) = line.split_once(' ').ok_or(Error::InvalidRef(format!(
+                "unexpected line in packed-refs: {}",
+                line
+            )))?;
+            refs.push(PackedRef {
+                name: name.to_string(),
+                hash: Hash::from_str(hash)?,
+                peeled: None,
+            });
+        }
+        refs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self { refs })
+    }
+}
+impl std::fmt::Display for PackedRefs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for packed in &self.refs {
+            writeln!(f, "{} {}", packed.hash, packed.name)?;
+            if let Some(peeled) = &packed.peeled {
+                writeln!(f, "^{}", peeled)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        let content = format!(
+            "{}\n{} refs/heads/main\n{} refs/tags/v1\n^{}\n",
+            HEADER,
+            Hash::from([1; 20]),
+            Hash::from([2; 20]),
+            Hash::from([3; 20]),
+        );
+        let mut packed_refs = PackedRefs::from_str(&content).unwrap();
+        assert_eq!(packed_refs.refs.len(), 2);
+        assert_eq!(
+            packed_refs.get("refs/tags/v1").unwrap().peeled,
+            Some(Hash::from([3; 20]))
+        );
+        assert_eq!(packed_refs.to_string(), content);
+
+        packed_refs.insert(PackedRef {
+            name: "refs/heads/feature/x".to_string(),
+            hash: Hash::from([4; 20]),
+            peeled: None,
+        });
+        assert_eq!(packed_refs.refs[0].name, "refs/heads/feature/x");
+        assert!(packed_refs.remove("refs/heads/main"));
+        assert!(!packed_refs.remove("refs/heads/main"));
+        assert!(packed_refs.get("refs/heads/main").is_none());
+
+        assert!(PackedRefs::from_str("^0000000000000000000000000000000000000000\n").is_err());
+    }
+
+    #[test]
+    fn test_cached_packed_refs_follow_rewrites() {
+        let directory =
+            std::env::temp_dir().join(format!("ssgit-packed-refs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let repo = Repository::init(&directory, false, Some("main")).unwrap();
+
+        let packed = |hash: Hash| PackedRef {
+            name: "refs/tags/v1".to_string(),
+            hash,
+            peeled: None,
+        };
+        let mut packed_refs = PackedRefs::default();
+        packed_refs.insert(packed(Hash::from([1; 20])));
+        packed_refs.write(&repo).unwrap();
+        assert!(Rc::ptr_eq(
+            &repo.packed_refs().unwrap(),
+            &repo.packed_refs().unwrap()
+        ));
+        assert_eq!(
+            repo.packed_refs()
+                .unwrap()
+                .get("refs/tags/v1")
+                .unwrap()
+                .hash,
+            Hash::from([1; 20])
+        );
+
+        packed_refs.insert(packed(Hash::from([2; 20])));
+        packed_refs.write(&repo).unwrap();
+        assert_eq!(
+            repo.packed_refs()
+                .unwrap()
+                .get("refs/tags/v1")
+                .unwrap()
+                .hash,
+            Hash::from([2; 20])
+        );
+
+        PackedRefs::default().write(&repo).unwrap();
+        assert!(repo.packed_refs().unwrap().refs.is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{
+    entity::{repository::Repository, wildmatch::wildmatch},
+    error::{Error, Result},
+};
+
+const GLOB_CHARACTERS: &[char] = &['*', '?', '['];
+
+/// コマンドラインで指定された 1 つのパス
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PathspecItem {
+    /// 指定されたままの文字列 (エラーメッセージに使う)
+    pub original: String,
+    /// 作業ツリーの root からのパス (空なら全て)
+    pub path: String,
+    pub exclude: bool,
+    pub icase: bool,
+    /// glob として解釈しない
+    pub literal: bool,
+    /// `*` や `?` が `/` に一致しない glob として解釈する
+    pub glob: bool,
+}
+impl PathspecItem {
+    /// `:(top,exclude,icase,literal,glob)path` や `:/path`, `:!path` の形の magic を解釈し、
+    /// `top` がなければカレントディレクトリからの相対パスとして扱う
+    pub fn parse(repo: &Repository, spec: &str) -> Result<Self> {
+        let mut top = false;
+        let mut exclude = false;
+        let mut icase = false;
+        let mut literal = false;
+        let mut glob = false;
+
+        let rest = if let Some(rest) = spec.strip_prefix(":(") {
+            let (magic, rest) = rest.split_once(')').ok_or(Error::InvalidArgument(format!(
+                "Missing ')' at the end of pathspec magic in '{}'",
+                spec
+            )))?;
+            for word in magic.split(',') {
+                match word.trim() {
+                    "top" => top = true,
+                    "exclude" => exclude = true,
+                    "icase" => icase = true,
+                    "literal" => literal = true,
+                    "glob" => glob = true,
+                    "" => {}
+                    word => {
+                        return Err(Error::InvalidArgument(format!(
+                            "Invalid pathspec magic '{}' in '{}'",
+                            word, spec
+                        )))
+                    }
+                }
+            }
+            if literal && glob {
+                return Err(Error::InvalidArgument(format!(
+                    "'literal' and 'glob' are incompatible in '{}'",
+                    spec
+                )));
+            }
+            rest
+        } else if let Some(mut rest) = spec.strip_prefix(':') {
+            // 短い形式は magic の文字が続き、`:` で終わってもよい
+            loop {
+                match rest.chars().next() {
+                    Some('/') => top = true,
+                    Some('!' | '^') => exclude = true,
+                    Some(':') => {
+                        rest = &rest[1..];
+                        break;
+                    }
+                    _ => break,
+                }
+                rest = &rest[1..];
+            }
+            rest
+        } else {
+            spec
+        };
+
+        let path = if top {
+            let absolute = repo.work_tree()?.join(rest);
+            repo.relative_path(&absolute.to_string_lossy())?
+        } else {
+            repo.relative_path(rest)?
+        };
+
+        Ok(Self {
+            original: spec.to_string(),
+            path,
+            exclude,
+            icase,
+            literal,
+            glob,
+        })
+    }
+
+    pub fn has_glob(&self) -> bool {
+        !self.literal && self.path.contains(GLOB_CHARACTERS)
+    }
+
+    /// パスそのもの、そのディレクトリ以下、または glob に一致するか
+    pub fn matches(&self, path: &str) -> bool {
+        let (pattern, path) = if self.icase {
+            (self.path.to_lowercase(), path.to_lowercase())
+        } else {
+            (self.path.clone(), path.to_string())
+        };
+
+        pattern.is_empty()
+            || path == pattern
+            || path
+                .strip_prefix(&pattern)
+                .is_some_and(|rest| rest.starts_with('/'))
+            || (self.has_glob() && wildmatch(pattern.as_bytes(), path.as_bytes(), self.glob))
+    }
+}
+
+/// 複数のパス指定。空なら全てのパスに一致する
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Pathspec {
+    pub items: Vec<PathspecItem>,
+}
+impl Pathspec {
+    pub fn parse(repo: &Repository, specs: &[String]) -> Result<Self> {
+        let mut items = specs
+            .iter()
+            .map(|spec| PathspecItem::parse(repo, spec))
+            .collect::<Result<Vec<_>>>()?;
+        // 除外だけが指定されたらカレントディレクトリ以下の全てから除く
+        if !items.is_empty() && items.iter().all(|item| item.exclude) {
+            items.push(PathspecItem::parse(repo, ".")?);
+        }
+
+        Ok(Self { items })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// どれかに一致し、どの除外にも一致しない
+    pub fn matches(&self, path: &str) -> bool {
+        if self.items.is_empty() {
+            return true;
+        }
+
+        self.items
+            .iter()
+            .any(|item| !item.exclude && item.matches(path))
+            && !self
+                .items
+                .iter()
+                .any(|item| item.exclude && item.matches(path))
+    }
+
+    /// glob, 除外, icase を含まなければ指定されたパスをそのまま返す
+    pub fn literal_paths(&self) -> Option<Vec<&str>> {
+        self.items
+            .iter()
+            .map(|item| {
+                (!item.has_glob() && !item.exclude && !item.icase).then_some(item.path.as_str())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn repo() -> Repository {
+        Repository::new(
+            PathBuf::from("/repo/.git"),
+            Some(PathBuf::from("/repo")),
+            "src/".to_string(),
+        )
+    }
+
+    fn pathspec(specs: &[&str]) -> Pathspec {
+        let specs = specs
+            .iter()
+            .map(|spec| spec.to_string())
+            .collect::<Vec<_>>();
+        Pathspec::parse(&repo(), &specs).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let item = PathspecItem::parse(&repo(), ":(top,icase)README").unwrap();
+        assert_eq!(item.path, "README");
+        assert!(item.icase && !item.exclude);
+
+        assert_eq!(PathspecItem::parse(&repo(), ":/a").unwrap().path, "a");
+        assert_eq!(PathspecItem::parse(&repo(), "a").unwrap().path, "src/a");
+        assert_eq!(PathspecItem::parse(&repo(), "../a").unwrap().path, "a");
+        assert!(PathspecItem::parse(&repo(), ":!a").unwrap().exclude);
+        assert!(PathspecItem::parse(&repo(), ":(unknown)a").is_err());
+        assert!(PathspecItem::parse(&repo(), ":(top").is_err());
+        assert!(PathspecItem::parse(&repo(), ":(glob)a").unwrap().glob);
+        assert!(PathspecItem::parse(&repo(), ":(glob,literal)a").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(pathspec(&[]).matches("anything"));
+        assert!(pathspec(&["."]).matches("src/lib.rs"));
+        assert!(!pathspec(&["."]).matches("README"));
+        assert!(pathspec(&["*.rs"]).matches("src/entity/tree.rs"));
+        assert!(!pathspec(&[":(literal)*.rs"]).matches("src/tree.rs"));
+        assert!(pathspec(&[":(literal)*.rs"]).matches("src/*.rs"));
+        assert!(pathspec(&[":(icase)LIB.RS"]).matches("src/lib.rs"));
+        assert!(pathspec(&[":/", ":(top,exclude)*.md"]).matches("src/lib.rs"));
+        assert!(!pathspec(&[":/", ":/!*.md"]).matches("README.md"));
+        assert!(pathspec(&[":/", ":!*.md"]).matches("README.md"));
+        assert!(!pathspec(&[":!main.rs"]).matches("src/main.rs"));
+        assert!(pathspec(&[":!main.rs"]).matches("src/lib.rs"));
+        assert!(pathspec(&[":(glob)*.rs"]).matches("src/lib.rs"));
+        assert!(!pathspec(&[":(glob)*.rs"]).matches("src/entity/tree.rs"));
+        assert!(pathspec(&[":(glob)**/*.rs"]).matches("src/entity/tree.rs"));
+        assert!(pathspec(&[":(glob)**/*.rs"]).matches("src/lib.rs"));
+        assert!(pathspec(&[":(glob)entity/**"]).matches("src/entity/tree.rs"));
+        assert!(!pathspec(&[":!main.rs"]).matches("Cargo.toml"));
+    }
+
+    #[test]
+    fn test_literal_paths() {
+        assert_eq!(
+            pathspec(&["a", "b"]).literal_paths(),
+            Some(vec!["src/a", "src/b"])
+        );
+        assert_eq!(pathspec(&["*.rs"]).literal_paths(), None);
+        assert_eq!(pathspec(&[":!a"]).literal_paths(), None);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{
+    consts::HEAD_PATH,
+    entity::{
+        lock::LockFile,
+        object::hash::Hash,
+        packed_refs::PackedRefs,
+        reflog::Reflog,
+        refs::{read_ref, remove_empty_parents, resolve_symref},
+        repository::Repository,
+    },
+    error::{Error, Result},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefChange {
+    Update(Hash),
+    Delete,
+    /// 値は変えずに old の確認だけをする
+    Verify,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefUpdate {
+    /// `HEAD` や `refs/heads/main` (symref なら参照先を更新する)
+    pub name: String,
+    pub change: RefChange,
+    /// Some(None) なら存在しないこと、Some(Some(hash)) ならその値であることを更新の条件にする
+    pub old: Option<Option<Hash>>,
+    pub message: String,
+}
+
+/// 複数の ref の更新をまとめて適用する
+///
+/// 全ての ref の lock を取って old を確認してから書き込むので、
+/// どれかの条件が満たされなければどの ref も変更しない
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RefTransaction {
+    pub updates: Vec<RefUpdate>,
+}
+impl RefTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, name: &str, new: Hash, old: Option<Option<Hash>>, message: &str) {
+        self.push(name, RefChange::Update(new), old, message);
+    }
+
+    pub fn delete(&mut self, name: &str, old: Option<Option<Hash>>, message: &str) {
+        self.push(name, RefChange::Delete, old, message);
+    }
+
+    pub fn verify(&mut self, name: &str, old: Option<Hash>) {
+        self.push(name, RefChange::Verify, Some(old), "");
+    }
+
+    fn push(&mut self, name: &str, change: RefChange, old: Option<Option<Hash>>, message: &str) {
+        self.updates.push(RefUpdate {
+            name: name.to_string(),
+            change,
+            old,
+            message: message.to_string(),
+        });
+    }
+
+    pub fn commit(self, repo: &Repository) -> Result<()> {
+        let head_target = resolve_symref(repo, HEAD_PATH)?;
+        // packed-refs にある ref を消すときは packed-refs も書き換えるので lock を取っておく
+        let mut packed_refs = None;
+
+        let mut locked: Vec<(RefUpdate, String, LockFile, Option<Hash>)> = Vec::new();
+        for update in self.updates {
+            let name = resolve_symref(repo, &update.name)?;
+            if locked
+                .iter()
+                .any(|(_, locked_name, _, _)| locked_name == &name)
+            {
+                return Err(Error::InvalidArgument(format!(
+                    "multiple updates for ref '{}' not allowed",
+                    update.name
+                )));
+            }
+
+            let path = repo.git_path(&name);
+            if path.is_dir() {
+                return Err(Error::InvalidRef(format!("Ref is a directory: {}", name)));
+            }
+            let lock = LockFile::acquire(&path)?;
+            if update.change == RefChange::Delete && packed_refs.is_none() {
+                packed_refs = Some(PackedRefs::lock(repo)?);
+            }
+            // lock を取ってから読むことで、確認した値が書き込むまで変わらないようにする
+            let current = read_ref(repo, &name)?;
+            check_old(&update, current.as_ref())?;
+
+            locked.push((update, name, lock, current));
+        }
+
+        // 先に packed-refs から消して、loose な ref を消した後に古い値が見えないようにする
+        if let Some(lock) = packed_refs {
+            let mut packed_refs = PackedRefs::read(repo)?;
+            let mut changed = false;
+            for (update, name, _, _) in &locked {
+                if update.change == RefChange::Delete {
+                    changed |= packed_refs.remove(name);
+                }
+            }
+            if changed {
+                packed_refs.commit(lock)?;
+            }
+        }
+
+        for (update, name, mut lock, current) in locked {
+            match &update.change {
+                RefChange::Update(hash) => {
+                    lock.write(format!("{}\n", hash).as_bytes())?;
+                    lock.commit()?;
+
+                    Reflog::record(repo, &name, current.as_ref(), Some(hash), &update.message)?;
+                    if name != HEAD_PATH && head_target == name {
+                        Reflog::record(
+                            repo,
+                            HEAD_PATH,
+                            current.as_ref(),
+                            Some(hash),
+                            &update.message,
+                        )?;
+                    }
+                }
+                RefChange::Delete => {
+                    lock.delete()?;
+                    Reflog::delete(repo, &name)?;
+                    remove_empty_parents(repo, &name)?;
+                }
+                RefChange::Verify => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn check_old(update: &RefUpdate, current: Option<&Hash>) -> Result<()> {
+    let Some(expected) = &update.old else {
+        return Ok(());
+    };
+
+    let reason = match (expected, current) {
+        (None, Some(_)) => "reference already exists".to_string(),
+        (Some(_), None) => format!("unable to resolve reference '{}'", update.name),
+        (Some(expected), Some(current)) if expected != current => {
+            format!("is at {} but expected {}", current, expected)
+        }
+        _ => return Ok(()),
+    };
+
+    Err(Error::InvalidRef(format!(
+        "cannot lock ref '{}': {}",
+        update.name, reason
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_all_or_nothing() {
+        let directory =
+            std::env::temp_dir().join(format!("ssgit-ref-transaction-test-{}", std::process::id()));
+        let repo = Repository::new(directory.clone(), None, String::new());
+        std::fs::create_dir_all(directory.join("refs/heads")).unwrap();
+        std::fs::write(directory.join(HEAD_PATH), "ref: refs/heads/main\n").unwrap();
+        let one = Hash::from([1; 20]);
+        let two = Hash::from([2; 20]);
+
+        let mut transaction = RefTransaction::new();
+        transaction.update("HEAD", one.clone(), Some(None), "");
+        transaction.update("refs/heads/topic", one.clone(), None, "");
+        transaction.commit(&repo).unwrap();
+        assert_eq!(
+            read_ref(&repo, "refs/heads/main").unwrap(),
+            Some(one.clone())
+        );
+
+        // 2 つ目の条件が満たされないので 1 つ目も適用されない
+        let mut transaction = RefTransaction::new();
+        transaction.update("refs/heads/main", two.clone(), Some(Some(one.clone())), "");
+        transaction.update("refs/heads/topic", two.clone(), Some(Some(two.clone())), "");
+        assert!(matches!(
+            transaction.commit(&repo),
+            Err(Error::InvalidRef(message)) if message.contains("is at")
+        ));
+        assert_eq!(
+            read_ref(&repo, "refs/heads/main").unwrap(),
+            Some(one.clone())
+        );
+        assert!(!directory.join("refs/heads/main.lock").exists());
+
+        let mut transaction = RefTransaction::new();
+        transaction.delete("refs/heads/topic", Some(Some(one)), "");
+        transaction.commit(&repo).unwrap();
+        assert_eq!(read_ref(&repo, "refs/heads/topic").unwrap(), None);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
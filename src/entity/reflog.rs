@@ -0,0 +1,318 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::{
+    consts::LOGS_DIRECTORY,
+    entity::{
+        config::{is_true, Config},
+        head::Head,
+        lock::LockFile,
+        object::{hash::Hash, User},
+        refs::Ref,
+        repository::Repository,
+    },
+    error::{Error, Result},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReflogEntry {
+    pub old: Hash,
+    pub new: Hash,
+    pub committer: User,
+    pub message: String,
+}
+impl ReflogEntry {
+    /// 現在の committer で old から new への更新を表す (None は ref が存在しない状態)
+    ///
+    /// メッセージは 1 行にまとめ、連続する空白は 1 つにする
+    pub fn new(repo: &Repository, old: Option<&Hash>, new: Option<&Hash>, message: &str) -> Self {
+        let committer = User::committer(repo).unwrap_or_else(|_| {
+            let name = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+            let now = chrono::Local::now();
+            User::new(name, String::new(), now.with_timezone(now.offset()))
+        });
+        let null = || Hash::from([0; 20]);
+
+        Self {
+            old: old.cloned().unwrap_or_else(null),
+            new: new.cloned().unwrap_or_else(null),
+            committer,
+            message: message.split_whitespace().collect::<Vec<_>>().join(" "),
+        }
+    }
+}
+impl FromStr for ReflogEntry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (header, message) = s.split_once('\t').unwrap_or((s, ""));
+
+        let mut parts = header.splitn(3, ' ');
+        let mut next = |name: &str| {
+            parts
+                .next()
+                .ok_or_else(|| Error::InvalidRef(format!("Could not find {} in reflog", name)))
+        };
+        let old = Hash::from_str(next("old hash")?)?;
+        let new = Hash::from_str(next("new hash")?)?;
+        let committer = User::from_str(next("committer")?)?;
+
+        Ok(Self {
+            old,
+            new,
+            committer,
+            message: message.to_string(),
+        })
+    }
+}
+impl std::fmt::Display for ReflogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.old, self.new, self.committer)?;
+        if !self.message.is_empty() {
+            write!(f, "\t{}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// `HEAD` や `refs/heads/main` の reflog
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Reflog {
+    pub name: String,
+    pub entries: Vec<ReflogEntry>,
+}
+impl Reflog {
+    pub fn path(repo: &Repository, name: &str) -> PathBuf {
+        repo.git_path(LOGS_DIRECTORY).join(name)
+    }
+
+    pub fn read(repo: &Repository, name: &str) -> Result<Self> {
+        let path = Self::path(repo, name);
+
+        let entries = if path.exists() {
+            std::fs::read_to_string(path)?
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(ReflogEntry::from_str)
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            entries,
+        })
+    }
+
+    /// `logs/<name>.lock` に書いてから置き換える
+    pub fn write(&self, repo: &Repository) -> Result<()> {
+        let mut lock = LockFile::acquire(&Self::path(repo, &self.name))?;
+
+        let content = self
+            .entries
+            .iter()
+            .map(|entry| format!("{}\n", entry))
+            .collect::<String>();
+        lock.write(content.as_bytes())?;
+
+        lock.commit()
+    }
+
+    pub fn exists(repo: &Repository, name: &str) -> bool {
+        Self::path(repo, name).is_file()
+    }
+
+    pub fn delete(repo: &Repository, name: &str) -> Result<()> {
+        let path = Self::path(repo, name);
+        if path.is_file() {
+            std::fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// logs 以下にある全ての reflog の名前
+    pub fn names(repo: &Repository) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        let directory = repo.git_path(LOGS_DIRECTORY);
+        if directory.is_dir() {
+            names_in(&directory, Path::new(""), &mut names)?;
+        }
+        names.sort();
+
+        Ok(names)
+    }
+
+    /// `HEAD`, ブランチ名, `refs/...` を reflog の名前にする (空なら HEAD が指しているブランチ)
+    pub fn resolve_name(repo: &Repository, name: &str) -> Result<String> {
+        Ok(match name {
+            "" => match Head::read(repo)? {
+                Head::Ref(ref_) => ref_.to_string(),
+                Head::Detached(_) => "HEAD".to_string(),
+            },
+            "HEAD" => "HEAD".to_string(),
+            name if name.starts_with("refs/") => name.to_string(),
+            name => Ref::Branch(name.to_string()).to_string(),
+        })
+    }
+
+    /// 既に reflog があるか、`core.logAllRefUpdates` で記録する対象になっているか
+    ///
+    /// 既定では作業ツリーのある repository で HEAD とブランチなどを記録する
+    pub fn should_log(repo: &Repository, name: &str) -> Result<bool> {
+        if Self::exists(repo, name) {
+            return Ok(true);
+        }
+
+        let config = Config::read(Some(repo))?;
+        Ok(match config.get("core.logAllRefUpdates") {
+            Some(value) if value.eq_ignore_ascii_case("always") => true,
+            Some(value) if !is_true(value) => false,
+            Some(_) => is_default_logged(name),
+            None => !repo.is_bare() && is_default_logged(name),
+        })
+    }
+
+    /// ref の更新を記録する対象なら 1 行追記する
+    pub fn record(
+        repo: &Repository,
+        name: &str,
+        old: Option<&Hash>,
+        new: Option<&Hash>,
+        message: &str,
+    ) -> Result<()> {
+        if !Self::should_log(repo, name)? {
+            return Ok(());
+        }
+
+        let path = Self::path(repo, name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{}", ReflogEntry::new(repo, old, new, message))?;
+
+        Ok(())
+    }
+
+    /// `@{n}` の n 番目 (0 が最新) のエントリ
+    pub fn nth_latest(&self, n: usize) -> Option<&ReflogEntry> {
+        self.entries.iter().rev().nth(n)
+    }
+
+    /// n 番目 (0 が最新) のエントリを取り除く
+    pub fn remove_nth_latest(&mut self, n: usize) -> Option<ReflogEntry> {
+        let index = self.entries.len().checked_sub(n + 1)?;
+        Some(self.entries.remove(index))
+    }
+
+    /// time より前に記録されたエントリを取り除き、取り除いた数を返す
+    pub fn expire(&mut self, time: &DateTime<FixedOffset>) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|entry| &entry.committer.time >= time);
+
+        before - self.entries.len()
+    }
+}
+
+fn is_default_logged(name: &str) -> bool {
+    name == "HEAD"
+        || ["refs/heads/", "refs/remotes/", "refs/notes/"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+fn names_in(directory: &Path, prefix: &Path, names: &mut Vec<String>) -> Result<()> {
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = prefix.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            names_in(&entry.path(), &name, names)?;
+        } else {
+            names.push(name.to_string_lossy().to_string());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_roundtrip() {
+        let line = "0000000000000000000000000000000000000000 6accf810eda4d84bc32dbc337e62469db5d22282 a <a@a> 1792205663 +0900\tcommit (initial): first";
+        let entry = ReflogEntry::from_str(line).unwrap();
+        assert_eq!(entry.message, "commit (initial): first");
+        assert_eq!(entry.to_string(), line);
+
+        let line = "6accf810eda4d84bc32dbc337e62469db5d22282 dc687fd955a62f77d408adb21d54a074973233dc a <a@a> 1792205663 +0000";
+        let entry = ReflogEntry::from_str(line).unwrap();
+        assert_eq!(entry.message, "");
+        assert_eq!(entry.to_string(), line);
+    }
+
+    #[test]
+    fn test_remove_and_expire() {
+        let entries = (0..3)
+            .map(|i| {
+                ReflogEntry::from_str(&format!(
+                    "{} {} a <a@a> {} +0000\t{}",
+                    Hash::from([i; 20]),
+                    Hash::from([i + 1; 20]),
+                    1000 + i as i64,
+                    i
+                ))
+                .unwrap()
+            })
+            .collect();
+        let mut reflog = Reflog {
+            name: "HEAD".to_string(),
+            entries,
+        };
+
+        assert_eq!(reflog.nth_latest(0).unwrap().message, "2");
+        assert_eq!(reflog.remove_nth_latest(1).unwrap().message, "1");
+        assert!(reflog.remove_nth_latest(2).is_none());
+
+        let time = DateTime::from_timestamp(1001, 0).unwrap().fixed_offset();
+        assert_eq!(reflog.expire(&time), 1);
+        assert_eq!(reflog.entries.len(), 1);
+        assert_eq!(reflog.entries[0].message, "2");
+    }
+
+    #[test]
+    fn test_write_takes_lock() {
+        let directory =
+            std::env::temp_dir().join(format!("ssgit-reflog-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let repo = Repository::init(&directory, false, Some("main")).unwrap();
+        let line = "0000000000000000000000000000000000000000 6accf810eda4d84bc32dbc337e62469db5d22282 a <a@a> 1792205663 +0900\tcommit (initial): first";
+        let reflog = Reflog {
+            name: "refs/heads/main".to_string(),
+            entries: vec![ReflogEntry::from_str(line).unwrap()],
+        };
+
+        reflog.write(&repo).unwrap();
+        assert_eq!(Reflog::read(&repo, "refs/heads/main").unwrap(), reflog);
+
+        let lock_path = repo.git_path(LOGS_DIRECTORY).join("refs/heads/main.lock");
+        std::fs::write(&lock_path, "").unwrap();
+        assert!(matches!(
+            reflog.write(&repo),
+            Err(Error::LockContention(path)) if path == lock_path
+        ));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,89 +1,326 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use anyhow::{bail, Result};
+use crate::{
+    consts::REFS_DIRECTORY,
+    entity::{
+        lock::LockFile,
+        object::{hash::Hash, GitObject, ObjectType},
+        ref_transaction::RefTransaction,
+        reflog::Reflog,
+        repository::Repository,
+    },
+    error::{Error, Result},
+};
 
-use crate::consts::REFS_DIRECTORY;
+/// symref を辿る深さの上限
+const MAX_SYMREF_DEPTH: usize = 5;
+const SYMREF_PREFIX: &str = "ref: ";
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Ref {
     Branch(String),
     Tag(String),
+    /// `refs/remotes/` 以下 (`origin/main` のように remote 名から)
+    Remote(String),
+    /// それ以外の `refs/` 以下 (`refs/stash` のように完全な名前で持つ)
+    Other(String),
 }
 impl Ref {
-    fn name_to_path(name: &str) -> PathBuf {
-        PathBuf::from(name)
+    pub fn branch_names(repo: &Repository) -> Result<Vec<String>> {
+        Self::names_in(repo, "heads")
     }
 
-    pub fn branch_names() -> Result<Vec<String>> {
-        let mut ret = Vec::new();
+    pub fn tag_names(repo: &Repository) -> Result<Vec<String>> {
+        Self::names_in(repo, "tags")
+    }
 
-        let path = PathBuf::from(REFS_DIRECTORY).join("heads");
-        if !path.exists() {
-            return Ok(ret);
-        }
+    pub fn remote_names(repo: &Repository) -> Result<Vec<String>> {
+        Self::names_in(repo, "remotes")
+    }
 
-        for entry in std::fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
-            let name = path.file_name().unwrap().to_str().unwrap().to_string();
-            ret.push(name);
-        }
+    /// `refs/` 以下の全ての ref (名前として不正なファイルは除く)
+    pub fn all(repo: &Repository) -> Result<Vec<Self>> {
+        Ok(ref_names(repo, &format!("{}/", REFS_DIRECTORY))?
+            .iter()
+            .filter_map(|name| Self::from_str(name).ok())
+            .collect())
+    }
+
+    fn names_in(repo: &Repository, directory: &str) -> Result<Vec<String>> {
+        let prefix = format!("{}/{}/", REFS_DIRECTORY, directory);
 
-        Ok(ret)
+        Ok(ref_names(repo, &prefix)?
+            .into_iter()
+            .filter_map(|name| name.strip_prefix(&prefix).map(str::to_string))
+            .collect())
     }
 
-    pub fn to_path(&self) -> PathBuf {
-        let mut ret = PathBuf::from(REFS_DIRECTORY);
+    pub fn to_path(&self, repo: &Repository) -> PathBuf {
+        repo.git_path(self.to_string())
+    }
 
+    /// `refs/heads/` などを除いた表示用の名前
+    pub fn short_name(&self) -> String {
         match self {
-            Ref::Branch(name) => {
-                ret.push("heads");
-                ret.push(Self::name_to_path(name));
-            }
-            Ref::Tag(name) => {
-                ret.push("tags");
-                ret.push(Self::name_to_path(name));
-            }
+            Ref::Branch(name) | Ref::Tag(name) | Ref::Remote(name) => name.clone(),
+            Ref::Other(name) => name
+                .strip_prefix(&format!("{}/", REFS_DIRECTORY))
+                .unwrap_or(name)
+                .to_string(),
         }
+    }
+
+    /// 新しく作る ref の名前が `check-ref-format` の規則を満たすか
+    pub fn check_format(&self) -> Result<()> {
+        check_ref_format(&self.to_string(), false, false)
+    }
 
-        ret
+    /// symref ならその参照先 (1 段だけ辿る)
+    pub fn read_symref(&self, repo: &Repository) -> Result<Option<Ref>> {
+        read_symref(repo, &self.to_string())?
+            .map(|target| target.parse())
+            .transpose()
     }
 
-    pub fn write_hash(&self, hash: &str) -> Result<()> {
-        let path = self.to_path();
+    pub fn write_hash(&self, repo: &Repository, hash: &str) -> Result<()> {
+        let path = self.to_path(repo);
         if path.is_dir() {
-            bail!("Ref is a directory: {}", self.to_string());
-        }
-        if !path.exists() {
-            std::fs::create_dir_all(path.parent().unwrap())?;
+            return Err(Error::InvalidRef(format!("Ref is a directory: {}", self)));
         }
 
-        std::fs::write(path, hash)?;
+        let mut lock = LockFile::acquire(&path)?;
+        lock.write(format!("{}\n", hash).as_bytes())?;
+        lock.commit()
+    }
+
+    /// hash に更新して ref の reflog に記録する (HEAD がこの ref を指していれば HEAD の reflog にも)
+    pub fn update(&self, repo: &Repository, hash: &Hash, message: &str) -> Result<()> {
+        let mut transaction = RefTransaction::new();
+        transaction.update(&self.to_string(), hash.clone(), None, message);
+        transaction.commit(repo)
+    }
 
-        Ok(())
+    /// ref とその reflog を消す
+    pub fn delete(&self, repo: &Repository) -> Result<()> {
+        let mut transaction = RefTransaction::new();
+        transaction.delete(&self.to_string(), None, "");
+        transaction.commit(repo)
     }
 
-    pub fn read_hash(&self) -> Result<Option<String>> {
-        let path = self.to_path();
+    pub fn read_hash(&self, repo: &Repository) -> Result<Option<String>> {
+        Ok(read_ref(repo, &self.to_string())?.map(|hash| hash.to_string()))
+    }
 
-        if !path.exists() {
+    pub fn exists(&self, repo: &Repository) -> Result<bool> {
+        Ok(self.read_hash(repo)?.is_some())
+    }
+
+    /// annotated tag を指している場合も commit まで剥がして返す
+    pub fn read_commit_hash(&self, repo: &Repository) -> Result<Option<Hash>> {
+        let Some(hash) = self.read_hash(repo)? else {
             return Ok(None);
+        };
+
+        let (hash, _) = GitObject::peel(repo, &Hash::from_str(&hash)?, ObjectType::Commit)?;
+        Ok(Some(hash))
+    }
+}
+/// symref の参照先の名前 (symref でなければ None)
+pub fn read_symref(repo: &Repository, name: &str) -> Result<Option<String>> {
+    Ok(read_raw(repo, name)?
+        .and_then(|content| content.strip_prefix(SYMREF_PREFIX).map(str::to_string)))
+}
+
+/// name を target を指す symref にする
+///
+/// target が既に存在すれば、name の reflog に前後の値を記録する
+pub fn write_symref(repo: &Repository, name: &str, target: &str, message: &str) -> Result<()> {
+    let path = repo.git_path(name);
+    if path.is_dir() {
+        return Err(Error::InvalidRef(format!("Ref is a directory: {}", name)));
+    }
+
+    let mut lock = LockFile::acquire(&path)?;
+    let old = read_ref(repo, name)?;
+    lock.write(format!("{}{}\n", SYMREF_PREFIX, target).as_bytes())?;
+    lock.commit()?;
+
+    if let Some(new) = read_ref(repo, target)? {
+        Reflog::record(repo, name, old.as_ref(), Some(&new), message)?;
+    }
+
+    Ok(())
+}
+
+/// symref 自体を消す (参照先の ref はそのまま残す)
+pub fn delete_symref(repo: &Repository, name: &str) -> Result<()> {
+    let lock = LockFile::acquire(&repo.git_path(name))?;
+    lock.delete()?;
+    Reflog::delete(repo, name)?;
+    remove_empty_parents(repo, name)
+}
+
+/// `git check-ref-format` の規則で ref の名前を確かめる
+///
+/// allow_onelevel なら `/` を含まない名前も、refspec_pattern なら `*` を 1 つまで許す
+pub fn check_ref_format(name: &str, allow_onelevel: bool, refspec_pattern: bool) -> Result<()> {
+    let invalid = |reason: &str| {
+        Err(Error::InvalidRef(format!(
+            "Invalid ref name '{}': {}",
+            name, reason
+        )))
+    };
+
+    if name == "@" {
+        return invalid("cannot be '@'");
+    }
+    let components = name.split('/').collect::<Vec<_>>();
+    if components.iter().any(|component| component.is_empty()) {
+        return invalid("empty component");
+    }
+    if components.len() < 2 && !allow_onelevel {
+        return invalid("must contain at least one '/'");
+    }
+    if components
+        .iter()
+        .any(|component| component.starts_with('.') || component.ends_with(".lock"))
+    {
+        return invalid("component begins with '.' or ends with '.lock'");
+    }
+    if name.ends_with('.') || name.contains("..") || name.contains("@{") {
+        return invalid("contains '..' or '@{' or ends with '.'");
+    }
+
+    let mut stars = 0;
+    for c in name.chars() {
+        match c {
+            '*' if refspec_pattern && stars == 0 => stars += 1,
+            '*' | ' ' | '~' | '^' | ':' | '?' | '[' | '\\' => {
+                return invalid(&format!("contains '{}'", c));
+            }
+            c if c.is_ascii_control() => return invalid("contains a control character"),
+            _ => {}
         }
+    }
 
-        let content = std::fs::read_to_string(path)?;
-        Ok(Some(content.trim().to_string()))
+    Ok(())
+}
+
+/// symref を辿った先の ref の名前 (symref でなければそのまま)
+pub fn resolve_symref(repo: &Repository, name: &str) -> Result<String> {
+    let mut name = name.to_string();
+    for _ in 0..MAX_SYMREF_DEPTH {
+        match read_raw(repo, &name)? {
+            Some(content) if content.starts_with(SYMREF_PREFIX) => {
+                name = content[SYMREF_PREFIX.len()..].to_string();
+            }
+            _ => return Ok(name),
+        }
+    }
+
+    Err(Error::InvalidRef(format!("Too deep symref: {}", name)))
+}
+
+fn read_raw(repo: &Repository, name: &str) -> Result<Option<String>> {
+    let path = repo.git_path(name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    Ok(Some(std::fs::read_to_string(path)?.trim().to_string()))
+}
+
+/// 完全な名前の ref の値を読む (symref なら辿った先、loose な ref がなければ packed-refs から)
+pub fn read_ref(repo: &Repository, name: &str) -> Result<Option<Hash>> {
+    let name = resolve_symref(repo, name)?;
+    match read_raw(repo, &name)? {
+        Some(content) => Ok(Some(Hash::from_str(&content).map_err(|_| {
+            Error::InvalidRef(format!("Invalid ref content in {}", name))
+        })?)),
+        None => Ok(repo
+            .packed_refs()?
+            .get(&name)
+            .map(|packed| packed.hash.clone())),
+    }
+}
+
+/// prefix (`refs/heads/` など) で始まる ref の完全な名前を loose と packed-refs の両方から集める
+pub fn ref_names(repo: &Repository, prefix: &str) -> Result<Vec<String>> {
+    let mut names = loose_ref_names(repo)?;
+    names.extend(
+        repo.packed_refs()?
+            .refs
+            .iter()
+            .map(|packed| packed.name.clone()),
+    );
+    names.retain(|name| name.starts_with(prefix));
+    names.sort();
+    names.dedup();
+
+    Ok(names)
+}
+
+/// refs 以下のファイルとして置かれている ref の完全な名前 (lock ファイルは除く)
+pub fn loose_ref_names(repo: &Repository) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let directory = repo.git_path(REFS_DIRECTORY);
+    if directory.is_dir() {
+        loose_ref_names_in(&directory, Path::new(REFS_DIRECTORY), &mut names)?;
+    }
+    names.sort();
+
+    Ok(names)
+}
+
+fn loose_ref_names_in(directory: &Path, prefix: &Path, names: &mut Vec<String>) -> Result<()> {
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = prefix.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            loose_ref_names_in(&entry.path(), &name, names)?;
+        } else if !name.to_string_lossy().ends_with(".lock") {
+            names.push(name.to_string_lossy().to_string());
+        }
     }
+
+    Ok(())
 }
+
+/// ref を消して空になったディレクトリを refs の直下まで消す
+pub fn remove_empty_parents(repo: &Repository, name: &str) -> Result<()> {
+    let mut parent = Path::new(name).parent();
+    while let Some(directory) = parent {
+        if directory.as_os_str().is_empty()
+            || directory.parent() == Some(Path::new(REFS_DIRECTORY))
+            || std::fs::remove_dir(repo.git_path(directory)).is_err()
+        {
+            break;
+        }
+        parent = directory.parent();
+    }
+
+    Ok(())
+}
+
 impl FromStr for Ref {
-    type Err = anyhow::Error;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        if s.starts_with("refs/heads/") {
-            Ok(Ref::Branch(s.replace("refs/heads/", "")))
-        } else if s.starts_with("refs/tags/") {
-            Ok(Ref::Tag(s.replace("refs/tags/", "")))
+        if !s.starts_with("refs/") || check_ref_format(s, false, false).is_err() {
+            return Err(Error::InvalidRef(format!("Invalid ref: {}", s)));
+        }
+
+        if let Some(name) = s.strip_prefix("refs/heads/") {
+            Ok(Ref::Branch(name.to_string()))
+        } else if let Some(name) = s.strip_prefix("refs/tags/") {
+            Ok(Ref::Tag(name.to_string()))
+        } else if let Some(name) = s.strip_prefix("refs/remotes/") {
+            Ok(Ref::Remote(name.to_string()))
         } else {
-            bail!("Invalid ref: {}", s);
+            Ok(Ref::Other(s.to_string()))
         }
     }
 }
@@ -92,6 +329,74 @@ impl std::fmt::Display for Ref {
         match self {
             Ref::Branch(name) => write!(f, "refs/heads/{}", name),
             Ref::Tag(name) => write!(f, "refs/tags/{}", name),
+            Ref::Remote(name) => write!(f, "refs/remotes/{}", name),
+            Ref::Other(name) => write!(f, "{}", name),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        for (name, ref_) in [
+            ("refs/heads/feature/x", Ref::Branch("feature/x".to_string())),
+            ("refs/tags/v1", Ref::Tag("v1".to_string())),
+            (
+                "refs/remotes/origin/HEAD",
+                Ref::Remote("origin/HEAD".to_string()),
+            ),
+            ("refs/stash", Ref::Other("refs/stash".to_string())),
+        ] {
+            assert_eq!(Ref::from_str(name).unwrap(), ref_);
+            assert_eq!(ref_.to_string(), name);
+        }
+        assert_eq!(
+            Ref::Other("refs/notes/commits".to_string()).short_name(),
+            "notes/commits"
+        );
+
+        assert!(Ref::from_str("HEAD").is_err());
+        assert!(Ref::from_str("refs/heads/a..b").is_err());
+    }
+
+    #[test]
+    fn test_check_ref_format() {
+        for name in [
+            "refs/heads/main",
+            "refs/heads/feature/x-1",
+            "refs/tags/v1.0",
+        ] {
+            assert!(check_ref_format(name, false, false).is_ok(), "{}", name);
+        }
+        for name in [
+            "main",
+            "refs/heads/",
+            "/refs/heads/main",
+            "refs//heads",
+            "refs/heads/.hidden",
+            "refs/heads/main.lock",
+            "refs/heads/main.",
+            "refs/heads/a..b",
+            "refs/heads/a@{1}",
+            "refs/heads/a b",
+            "refs/heads/a~1",
+            "refs/heads/a^",
+            "refs/heads/a:b",
+            "refs/heads/a?",
+            "refs/heads/a[b",
+            "refs/heads/a\\b",
+            "refs/heads/a\tb",
+            "refs/heads/*",
+            "@",
+        ] {
+            assert!(check_ref_format(name, false, false).is_err(), "{}", name);
+        }
+
+        assert!(check_ref_format("main", true, false).is_ok());
+        assert!(check_ref_format("refs/heads/*", false, true).is_ok());
+        assert!(check_ref_format("refs/*/*", false, true).is_err());
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    cell::RefCell,
+    os::unix::fs::MetadataExt,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+use crate::{
+    consts::{
+        CONFIG_PATH, DEFAULT_BRANCH, GIT_DIRECTORY, HEAD_PATH, OBJECTS_DIRECTORY, REFS_DIRECTORY,
+    },
+    entity::{
+        config::{is_true, Config},
+        object::{
+            database::{CompositeStore, ObjectDatabase},
+            hash::Hash,
+            GitObject,
+        },
+        packed_refs::PackedRefs,
+    },
+    error::{Error, Result},
+};
+
+/// git directory と作業ツリーの場所
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repository {
+    /// `.git` ディレクトリ (bare なら repository 自体)
+    pub git_dir: PathBuf,
+    /// bare repository なら None
+    pub work_tree: Option<PathBuf>,
+    /// 作業ツリーの root から見たカレントディレクトリ (`dir/sub/` の形、root や作業ツリーの外なら空)
+    pub prefix: String,
+    objects: Cache<Rc<CompositeStore>>,
+    packed_refs: Cache<(FileStamp, Rc<PackedRefs>)>,
+}
+impl Repository {
+    pub fn new(git_dir: PathBuf, work_tree: Option<PathBuf>, prefix: String) -> Self {
+        Self {
+            git_dir,
+            work_tree,
+            prefix,
+            objects: Cache::default(),
+            packed_refs: Cache::default(),
+        }
+    }
+
+    /// `GIT_DIR`, `GIT_WORK_TREE` を見て、なければカレントディレクトリから上に辿って探す
+    pub fn discover() -> Result<Self> {
+        let current = std::env::current_dir()?;
+
+        if let Some(git_dir) = std::env::var_os("GIT_DIR") {
+            let git_dir = current.join(git_dir);
+            if !is_git_dir(&git_dir) {
+                return Err(Error::NotARepository(format!(
+                    "not a git repository: '{}'",
+                    git_dir.display()
+                )));
+            }
+            // GIT_DIR だけ指定された場合はカレントディレクトリを作業ツリーとみなす
+            return Self::open(&git_dir, Some(&current), &current);
+        }
+
+        for directory in current.ancestors() {
+            let dot_git = directory.join(GIT_DIRECTORY);
+            if is_git_dir(&dot_git) {
+                return Self::open(&dot_git, Some(directory), &current);
+            }
+            if dot_git.is_file() {
+                return Self::open(&read_gitdir_file(&dot_git)?, Some(directory), &current);
+            }
+            if is_git_dir(directory) {
+                return Self::open(directory, None, &current);
+            }
+        }
+
+        Err(Error::NotARepository(format!(
+            "not a git repository (or any of the parent directories): {}",
+            GIT_DIRECTORY
+        )))
+    }
+
+    /// `GIT_WORK_TREE`, `core.worktree`, `core.bare` の順に作業ツリーを決める
+    fn open(git_dir: &Path, default_work_tree: Option<&Path>, current: &Path) -> Result<Self> {
+        let git_dir = git_dir.canonicalize()?;
+        let config = Config::read_file(None, &git_dir.join(CONFIG_PATH))?;
+
+        let work_tree = if let Some(work_tree) = std::env::var_os("GIT_WORK_TREE") {
+            Some(current.join(work_tree))
+        } else if let Some(work_tree) = config.get("core.worktree") {
+            Some(git_dir.join(work_tree))
+        } else if config.get("core.bare").is_some_and(is_true) {
+            None
+        } else {
+            default_work_tree.map(Path::to_path_buf)
+        };
+        let work_tree = work_tree
+            .map(|work_tree| work_tree.canonicalize())
+            .transpose()?;
+
+        let prefix = match &work_tree {
+            Some(work_tree) => match current.canonicalize()?.strip_prefix(work_tree) {
+                Ok(relative) if !relative.as_os_str().is_empty() => {
+                    format!("{}/", relative.to_string_lossy())
+                }
+                _ => String::new(),
+            },
+            None => String::new(),
+        };
+
+        Ok(Self::new(git_dir, work_tree, prefix))
+    }
+
+    /// 新しい repository を作る (作業ツリーがあれば `<path>/.git` に、bare なら path 自体に)
+    pub fn init(path: &Path, bare: bool, initial_branch: Option<&str>) -> Result<Self> {
+        let git_dir = if bare {
+            path.to_path_buf()
+        } else {
+            path.join(GIT_DIRECTORY)
+        };
+        if git_dir.join(HEAD_PATH).exists() {
+            return Err(Error::AlreadyExists(format!(
+                "already initialized: {}",
+                git_dir.display()
+            )));
+        }
+
+        std::fs::create_dir_all(git_dir.join(REFS_DIRECTORY).join("heads"))?;
+        std::fs::create_dir_all(git_dir.join(REFS_DIRECTORY).join("tags"))?;
+        std::fs::create_dir_all(git_dir.join(OBJECTS_DIRECTORY))?;
+
+        let branch = initial_branch.unwrap_or(DEFAULT_BRANCH);
+        std::fs::write(
+            git_dir.join(HEAD_PATH),
+            format!("ref: refs/heads/{}\n", branch),
+        )?;
+        std::fs::write(
+            git_dir.join(CONFIG_PATH),
+            format!(
+                "[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = {}\n",
+                bare
+            ),
+        )?;
+
+        let work_tree = (!bare).then(|| path.to_path_buf());
+        let current = std::env::current_dir()?;
+        Self::open(&git_dir, work_tree.as_deref(), &current)
+    }
+
+    pub fn is_bare(&self) -> bool {
+        self.work_tree.is_none()
+    }
+
+    /// git directory の中のパス
+    pub fn git_path(&self, name: impl AsRef<Path>) -> PathBuf {
+        self.git_dir.join(name)
+    }
+
+    pub fn work_tree(&self) -> Result<&Path> {
+        self.work_tree.as_deref().ok_or(Error::NoWorkTree)
+    }
+
+    /// 作業ツリーの root からの相対パスを実際のパスにする
+    pub fn work_tree_path(&self, file_name: impl AsRef<Path>) -> Result<PathBuf> {
+        Ok(self.work_tree()?.join(file_name))
+    }
+
+    /// `objects` 以下の loose object と pack、alternates をまとめた object database
+    ///
+    /// 最初に使うときに開いて、以降は同じものを使い回す
+    pub fn objects(&self) -> Result<Rc<CompositeStore>> {
+        if let Some(objects) = self.objects.0.borrow().as_ref() {
+            return Ok(objects.clone());
+        }
+
+        let objects = Rc::new(CompositeStore::open(self.git_path(OBJECTS_DIRECTORY))?);
+        *self.objects.0.borrow_mut() = Some(objects.clone());
+        Ok(objects)
+    }
+
+    /// pack を作ったり消したりした後に、次の `objects()` で開き直させる
+    pub fn refresh_objects(&self) {
+        self.objects.0.borrow_mut().take();
+    }
+
+    /// `packed-refs` の内容。ファイルが書き換えられるまでは読み直さない
+    pub fn packed_refs(&self) -> Result<Rc<PackedRefs>> {
+        let stamp = file_stamp(&PackedRefs::path(self));
+        if let Some((cached, packed_refs)) = self.packed_refs.0.borrow().as_ref() {
+            if *cached == stamp {
+                return Ok(packed_refs.clone());
+            }
+        }
+
+        let packed_refs = Rc::new(PackedRefs::read(self)?);
+        *self.packed_refs.0.borrow_mut() = Some((stamp, packed_refs.clone()));
+        Ok(packed_refs)
+    }
+
+    /// コマンドラインで指定されたパス (カレントディレクトリからの相対パス) を作業ツリーの root からのパスにする
+    pub fn relative_path(&self, path: &str) -> Result<String> {
+        let work_tree = self.work_tree()?;
+        let joined = if Path::new(path).is_absolute() {
+            PathBuf::from(path)
+        } else {
+            work_tree.join(&self.prefix).join(path)
+        };
+
+        let mut components = Vec::new();
+        for component in joined.components() {
+            match component {
+                Component::ParentDir => {
+                    components.pop();
+                }
+                Component::CurDir => {}
+                component => components.push(component),
+            }
+        }
+        let normalized = components.iter().collect::<PathBuf>();
+
+        let relative = normalized
+            .strip_prefix(work_tree)
+            .map_err(|_| Error::InvalidArgument(format!("'{}' is outside repository", path)))?;
+        Ok(relative.to_string_lossy().to_string())
+    }
+
+    /// 作業ツリーの root からのパスをカレントディレクトリからの相対パスにする (`relative_path` の逆)
+    pub fn display_path(&self, path: &str) -> String {
+        let mut prefix = self.prefix.as_str();
+        let mut ups = String::new();
+        loop {
+            if let Some(rest) = path.strip_prefix(prefix) {
+                return format!("{}{}", ups, rest);
+            }
+            let parent = prefix.trim_end_matches('/');
+            prefix = match parent.rfind('/') {
+                Some(i) => &parent[..=i],
+                None => "",
+            };
+            ups.push_str("../");
+        }
+    }
+}
+
+impl ObjectDatabase for Repository {
+    fn read(&self, hash: &Hash) -> Result<Option<GitObject>> {
+        self.objects()?.read(hash)
+    }
+
+    fn write(&self, object: &GitObject) -> Result<Hash> {
+        self.objects()?.write(object)
+    }
+
+    fn exists(&self, hash: &Hash) -> Result<bool> {
+        self.objects()?.exists(hash)
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>> {
+        self.objects()?.hashes()
+    }
+
+    fn find_by_prefix(&self, prefix: &str) -> Result<Vec<Hash>> {
+        self.objects()?.find_by_prefix(prefix)
+    }
+}
+
+/// 開いた object database などを持っておく (複製した Repository では読み直す)
+struct Cache<T>(RefCell<Option<T>>);
+impl<T> Default for Cache<T> {
+    fn default() -> Self {
+        Self(RefCell::new(None))
+    }
+}
+impl<T> Clone for Cache<T> {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+impl<T> PartialEq for Cache<T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+impl<T> Eq for Cache<T> {}
+impl<T> std::fmt::Debug for Cache<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Cache")
+    }
+}
+
+/// ファイルが置き換えられたことを見分けるための stat 情報 (なければ None)
+type FileStamp = Option<(SystemTime, u64, u64)>;
+
+fn file_stamp(path: &Path) -> FileStamp {
+    let metadata = path.metadata().ok()?;
+    Some((metadata.modified().ok()?, metadata.len(), metadata.ino()))
+}
+
+/// HEAD, objects, refs が揃っていれば git directory とみなす
+fn is_git_dir(path: &Path) -> bool {
+    path.join(HEAD_PATH).is_file()
+        && path.join(OBJECTS_DIRECTORY).is_dir()
+        && path.join(REFS_DIRECTORY).is_dir()
+}
+
+/// worktree や submodule の `.git` は `gitdir: <path>` と書かれたファイルになっている
+fn read_gitdir_file(path: &Path) -> Result<PathBuf> {
+    let content = std::fs::read_to_string(path)?;
+    let git_dir = content
+        .trim_end()
+        .strip_prefix("gitdir: ")
+        .ok_or(Error::NotARepository(format!(
+            "invalid gitfile format: {}",
+            path.display()
+        )))?;
+
+    let git_dir = match path.parent() {
+        Some(parent) => parent.join(git_dir),
+        None => PathBuf::from(git_dir),
+    };
+    if !is_git_dir(&git_dir) {
+        return Err(Error::NotARepository(format!(
+            "not a git repository: {}",
+            git_dir.display()
+        )));
+    }
+
+    Ok(git_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() {
+        let repo = Repository::new(
+            PathBuf::from("/repo/.git"),
+            Some(PathBuf::from("/repo")),
+            "dir/sub/".to_string(),
+        );
+
+        assert_eq!(repo.relative_path("file").unwrap(), "dir/sub/file");
+        assert_eq!(repo.relative_path("./a/../b").unwrap(), "dir/sub/b");
+        assert_eq!(repo.relative_path("../../file").unwrap(), "file");
+        assert_eq!(repo.relative_path("/repo/x/y").unwrap(), "x/y");
+        assert_eq!(repo.relative_path(".").unwrap(), "dir/sub");
+        assert!(repo.relative_path("../../../file").is_err());
+
+        assert_eq!(repo.display_path("dir/sub/file"), "file");
+        assert_eq!(repo.display_path("dir/other/file"), "../other/file");
+        assert_eq!(repo.display_path("file"), "../../file");
+
+        let bare = Repository::new(repo.git_dir, None, String::new());
+        assert!(bare.relative_path("file").is_err());
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+
+use crate::entity::{
+    object::{hash::Hash, mode::Mode, Commit, GitObject, TreeEntry},
+    pathspec::Pathspec,
+    repository::Repository,
+    revision::resolve_commit,
+    tree::find_entry,
+    worktree::read_tree_files,
+};
+
+/// 除外だけが残った後に余分に辿る commit の数
+const SLOP: usize = 5;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Order {
+    /// commit 日時の新しい順 (親子関係は保証しない)
+    #[default]
+    Default,
+    /// 子を親より先に出しつつ commit 日時の新しい順
+    Date,
+    /// 子を親より先に出しつつ、同じ系列の commit をまとめる
+    Topo,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WalkedCommit {
+    pub hash: Hash,
+    pub commit: Commit,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RevWalk {
+    pub include: Vec<Hash>,
+    pub exclude: Vec<Hash>,
+    pub order: Order,
+    pub first_parent: bool,
+    pub since: Option<DateTime<FixedOffset>>,
+    pub until: Option<DateTime<FixedOffset>>,
+    pub pathspec: Pathspec,
+    pub max_count: Option<usize>,
+}
+impl RevWalk {
+    /// `A`, `^A`, `A..B`, `A...B` 形式の指定を追加する
+    pub fn push_revision(&mut self, repo: &Repository, revision: &str) -> Result<()> {
+        if let Some(revision) = revision.strip_prefix('^') {
+            self.exclude.push(resolve_commit(repo, revision)?);
+        } else if let Some((left, right)) = revision.split_once("...") {
+            let left = resolve_commit(repo, or_head(left))?;
+            let right = resolve_commit(repo, or_head(right))?;
+
+            self.exclude.extend(merge_bases(repo, &left, &right)?);
+            self.include.push(left);
+            self.include.push(right);
+        } else if let Some((left, right)) = revision.split_once("..") {
+            self.exclude.push(resolve_commit(repo, or_head(left))?);
+            self.include.push(resolve_commit(repo, or_head(right))?);
+        } else {
+            self.include.push(resolve_commit(repo, revision)?);
+        }
+
+        Ok(())
+    }
+
+    /// 除外する commit も同じ queue で辿り、祖先に印を付けながら進む
+    ///
+    /// 除外がなく topo order でもなければ、max_count 件を出した時点で打ち切る
+    pub fn walk(&self, repo: &Repository) -> Result<Vec<WalkedCommit>> {
+        let mut queue = BinaryHeap::new();
+        let mut seen = HashSet::new();
+        let mut uninteresting = HashSet::new();
+        let mut processed = HashSet::new();
+        let mut sequence = 0usize;
+        let roots = self
+            .exclude
+            .iter()
+            .map(|hash| (hash, true))
+            .chain(self.include.iter().map(|hash| (hash, false)));
+        for (hash, excluded) in roots {
+            if excluded {
+                uninteresting.insert(hash.clone());
+            }
+            if seen.insert(hash.clone()) {
+                let commit = read_commit(repo, hash)?;
+                queue.push(QueueItem::new(hash.clone(), commit, &mut sequence));
+            }
+        }
+
+        // 除外する commit が後から見つかると表示済みの commit が取り消されうるので、その場合は打ち切らない
+        let limit = match self.order {
+            Order::Default | Order::Date if self.exclude.is_empty() => self.max_count,
+            _ => None,
+        };
+
+        let mut commits = Vec::new();
+        let mut slop = SLOP;
+        let mut last_time = i64::MAX;
+        loop {
+            if limit.is_some_and(|limit| commits.len() >= limit) {
+                break;
+            }
+            // 残りが全て除外されるものなら、それ以上辿っても何も表示されない
+            // ただし日時が前後している祖先から除外が伝わることがあるので、SLOP 件だけ余分に辿る
+            if !self.exclude.is_empty()
+                && queue
+                    .iter()
+                    .all(|item: &QueueItem| uninteresting.contains(&item.hash))
+            {
+                if queue.peek().is_some_and(|item| item.time >= last_time) {
+                    slop = SLOP;
+                } else {
+                    slop -= 1;
+                }
+                if slop == 0 {
+                    break;
+                }
+            } else {
+                slop = SLOP;
+            }
+            let Some(QueueItem {
+                time, hash, commit, ..
+            }) = queue.pop()
+            else {
+                break;
+            };
+            last_time = time;
+            processed.insert(hash.clone());
+
+            if uninteresting.contains(&hash) {
+                for parent in &commit.parent {
+                    mark_uninteresting(repo, parent, &mut uninteresting, &processed)?;
+                    if seen.insert(parent.clone()) {
+                        let parent_commit = read_commit(repo, parent)?;
+                        queue.push(QueueItem::new(parent.clone(), parent_commit, &mut sequence));
+                    }
+                }
+                continue;
+            }
+
+            let parents = if self.first_parent {
+                commit.parent.iter().take(1).cloned().collect::<Vec<_>>()
+            } else {
+                commit.parent.clone()
+            };
+            let (show, follow) = self.simplify(repo, &commit, parents)?;
+
+            for parent in follow {
+                if seen.insert(parent.clone()) {
+                    let parent_commit = read_commit(repo, &parent)?;
+                    queue.push(QueueItem::new(parent, parent_commit, &mut sequence));
+                }
+            }
+
+            let time = commit.committer.time;
+            let in_range = self.since.is_none_or(|since| time >= since)
+                && self.until.is_none_or(|until| time <= until);
+            if show && in_range {
+                commits.push(WalkedCommit { hash, commit });
+            }
+        }
+        commits.retain(|walked| !uninteresting.contains(&walked.hash));
+
+        let mut commits = match self.order {
+            Order::Default => commits,
+            Order::Date | Order::Topo => sort_topologically(commits, self.order),
+        };
+
+        if let Some(max_count) = self.max_count {
+            commits.truncate(max_count);
+        }
+
+        Ok(commits)
+    }
+
+    /// パス指定がある場合に、表示するかどうかと辿る親を決める
+    ///
+    /// 親のどれかと指定パスの内容が同じならその commit は表示せず、その親だけを辿る
+    fn simplify(
+        &self,
+        repo: &Repository,
+        commit: &Commit,
+        parents: Vec<Hash>,
+    ) -> Result<(bool, Vec<Hash>)> {
+        if self.pathspec.is_empty() {
+            return Ok((true, parents));
+        }
+
+        let entries = self.path_entries(repo, &commit.tree)?;
+        if parents.is_empty() {
+            return Ok((!entries.is_empty(), parents));
+        }
+
+        for parent in &parents {
+            let parent_tree = read_commit(repo, parent)?.tree;
+            if self.path_entries(repo, &parent_tree)? == entries {
+                return Ok((false, vec![parent.clone()]));
+            }
+        }
+
+        Ok((true, parents))
+    }
+
+    /// 指定パスに一致する entry。glob などを含まなければ tree 全体は読まない
+    fn path_entries(&self, repo: &Repository, tree: &Hash) -> Result<Vec<(String, TreeEntry)>> {
+        if let Some(paths) = self.pathspec.literal_paths() {
+            let mut entries = Vec::new();
+            for path in paths {
+                if let Some(entry) = find_entry(repo, tree, path)? {
+                    entries.push((path.to_string(), entry));
+                }
+            }
+            return Ok(entries);
+        }
+
+        Ok(read_tree_files(repo, Some(tree))?
+            .into_iter()
+            .filter(|(path, _)| self.pathspec.matches(path))
+            .map(|(path, (blob_type, hash))| {
+                let entry = TreeEntry {
+                    file_type: Mode::Blob(blob_type),
+                    name: path.clone(),
+                    hash,
+                };
+                (path, entry)
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct QueueItem {
+    time: i64,
+    sequence: std::cmp::Reverse<usize>,
+    hash: Hash,
+    commit: Commit,
+}
+impl QueueItem {
+    fn new(hash: Hash, commit: Commit, sequence: &mut usize) -> Self {
+        *sequence += 1;
+
+        Self {
+            time: commit.committer.time.timestamp(),
+            sequence: std::cmp::Reverse(*sequence),
+            hash,
+            commit,
+        }
+    }
+}
+impl PartialOrd for QueueItem {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for QueueItem {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.time, self.sequence).cmp(&(other.time, other.sequence))
+    }
+}
+
+fn or_head(revision: &str) -> &str {
+    if revision.is_empty() {
+        "HEAD"
+    } else {
+        revision
+    }
+}
+
+fn read_commit(repo: &Repository, hash: &Hash) -> Result<Commit> {
+    Ok(GitObject::read(repo, hash)?.parse_commit_body()?)
+}
+
+/// hash と、既に queue から取り出した祖先に除外の印を付ける
+fn mark_uninteresting(
+    repo: &Repository,
+    hash: &Hash,
+    uninteresting: &mut HashSet<Hash>,
+    processed: &HashSet<Hash>,
+) -> Result<()> {
+    let mut stack = vec![hash.clone()];
+    while let Some(hash) = stack.pop() {
+        if uninteresting.insert(hash.clone()) && processed.contains(&hash) {
+            stack.extend(read_commit(repo, &hash)?.parent);
+        }
+    }
+
+    Ok(())
+}
+
+/// from から target に辿り着けるか。reachable は複数の from で使い回して同じ commit を何度も辿らない
+pub fn can_reach(
+    repo: &Repository,
+    from: &Hash,
+    target: &Hash,
+    reachable: &mut HashMap<Hash, bool>,
+) -> Result<bool> {
+    // 親を読んだ commit は、親の結果が全て揃ってから自分の結果を決める
+    let mut stack: Vec<(Hash, Option<Vec<Hash>>)> = vec![(from.clone(), None)];
+    while let Some((hash, parents)) = stack.pop() {
+        if reachable.contains_key(&hash) {
+            continue;
+        }
+        if hash == *target {
+            reachable.insert(hash, true);
+            continue;
+        }
+
+        match parents {
+            Some(parents) => {
+                let result = parents
+                    .iter()
+                    .any(|parent| reachable.get(parent) == Some(&true));
+                reachable.insert(hash, result);
+            }
+            None => {
+                let parents = read_commit(repo, &hash)?.parent;
+                let unknown = parents
+                    .iter()
+                    .filter(|parent| !reachable.contains_key(*parent))
+                    .map(|parent| (parent.clone(), None))
+                    .collect::<Vec<_>>();
+                stack.push((hash, Some(parents)));
+                stack.extend(unknown);
+            }
+        }
+    }
+
+    Ok(reachable[from])
+}
+
+/// roots 自身を含む祖先を全て集める
+pub fn ancestors(repo: &Repository, roots: &[Hash]) -> Result<HashSet<Hash>> {
+    let mut visited = HashSet::new();
+    let mut stack = roots.to_vec();
+
+    while let Some(hash) = stack.pop() {
+        if visited.insert(hash.clone()) {
+            stack.extend(read_commit(repo, &hash)?.parent);
+        }
+    }
+
+    Ok(visited)
+}
+
+/// 2 つの commit の共通祖先のうち、他の共通祖先の祖先でないものを返す
+pub fn merge_bases(repo: &Repository, left: &Hash, right: &Hash) -> Result<Vec<Hash>> {
+    let left_ancestors = ancestors(repo, std::slice::from_ref(left))?;
+    let right_ancestors = ancestors(repo, std::slice::from_ref(right))?;
+    let common = left_ancestors
+        .intersection(&right_ancestors)
+        .cloned()
+        .collect::<HashSet<_>>();
+
+    let mut common_parents = Vec::new();
+    for hash in &common {
+        common_parents.extend(read_commit(repo, hash)?.parent);
+    }
+    let redundant = ancestors(repo, &common_parents)?;
+
+    let mut bases = common
+        .into_iter()
+        .filter(|hash| !redundant.contains(hash))
+        .collect::<Vec<_>>();
+    bases.sort();
+
+    Ok(bases)
+}
+
+/// 子が親より先に来るように並べ替える
+fn sort_topologically(commits: Vec<WalkedCommit>, order: Order) -> Vec<WalkedCommit> {
+    let positions = commits
+        .iter()
+        .enumerate()
+        .map(|(i, walked)| (walked.hash.clone(), i))
+        .collect::<HashMap<_, _>>();
+
+    let mut child_counts = vec![0; commits.len()];
+    for walked in &commits {
+        for parent in &walked.commit.parent {
+            if let Some(&i) = positions.get(parent) {
+                child_counts[i] += 1;
+            }
+        }
+    }
+
+    // commits はおおむね日時の新しい順に並んでいるので、位置が小さいほど新しい
+    let mut ready = (0..commits.len())
+        .filter(|&i| child_counts[i] == 0)
+        .collect::<Vec<_>>();
+    if order == Order::Topo {
+        // スタックとして使うので新しいものを末尾にする
+        ready.reverse();
+    }
+    let mut sorted = Vec::with_capacity(commits.len());
+    while !ready.is_empty() {
+        let i = match order {
+            Order::Topo => ready.pop().unwrap(),
+            _ => {
+                // 日時が前後していることがあるので、位置ではなく日時で選ぶ
+                let newest = ready
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, &i)| {
+                        (
+                            std::cmp::Reverse(commits[i].commit.committer.time.timestamp()),
+                            i,
+                        )
+                    })
+                    .map(|(position, _)| position)
+                    .unwrap();
+                ready.remove(newest)
+            }
+        };
+        sorted.push(i);
+
+        // Topo では git と同様に、後ろの親の系列が先に取り出される
+        let parents = commits[i]
+            .commit
+            .parent
+            .iter()
+            .filter_map(|parent| positions.get(parent).copied())
+            .collect::<Vec<_>>();
+        for parent in parents {
+            child_counts[parent] -= 1;
+            if child_counts[parent] == 0 {
+                ready.push(parent);
+            }
+        }
+    }
+
+    let mut commits = commits.into_iter().map(Some).collect::<Vec<_>>();
+    sorted
+        .into_iter()
+        .map(|i| commits[i].take().unwrap())
+        .collect()
+}
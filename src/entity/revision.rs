@@ -0,0 +1,322 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    consts::{MERGE_HEAD_PATH, ORIG_HEAD_PATH},
+    entity::{
+        head::Head,
+        index::Index,
+        object::{hash::Hash, GitObject, ObjectType},
+        reflog::Reflog,
+        refs::Ref,
+        repository::Repository,
+        tree::find_entry,
+    },
+};
+
+/// 省略形の hash として受け付ける最短の長さ
+const MIN_ABBREV_LENGTH: usize = 4;
+
+/// リビジョン式を解決する
+///
+/// `HEAD`, ブランチ名, タグ名, `refs/...`, (省略された) hash を基点として、
+/// `~N`, `^N`, `^{type}`, `@{N}`, `@{-N}`, `<rev>:<path>`, `:<path>` を解釈する
+pub fn resolve(repo: &Repository, revision: &str) -> Result<Hash> {
+    if let Some((revision, path)) = split_path(revision) {
+        return resolve_path(repo, revision, path);
+    }
+
+    let (base, suffixes) = split_suffixes(revision);
+    let hash = resolve_base(repo, base)?;
+
+    apply_suffixes(repo, hash, suffixes, revision)
+}
+
+/// 解決した結果を commit まで剥がす
+pub fn resolve_commit(repo: &Repository, revision: &str) -> Result<Hash> {
+    let hash = resolve(repo, revision)?;
+    let (hash, _) = GitObject::peel(repo, &hash, ObjectType::Commit)?;
+
+    Ok(hash)
+}
+
+/// `{...}` の外にある最初の `:` で分ける
+fn split_path(revision: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in revision.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ':' if depth == 0 => return Some((&revision[..i], &revision[i + 1..])),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn resolve_path(repo: &Repository, revision: &str, path: &str) -> Result<Hash> {
+    if revision.is_empty() {
+        // `:<n>:<path>` で stage を指定できる
+        let (stage, path) = match path.split_once(':') {
+            Some((stage @ ("0" | "1" | "2" | "3"), path)) => (stage.parse()?, path),
+            _ => (0, path),
+        };
+
+        let index = Index::read(repo)?.unwrap_or_else(Index::new);
+        return index
+            .entries
+            .into_iter()
+            .find(|entry| entry.file_name == path && entry.stage == stage)
+            .map(|entry| entry.hash)
+            .ok_or(anyhow!("path '{}' does not exist in the index", path));
+    }
+
+    let (tree, _) = GitObject::peel(repo, &resolve(repo, revision)?, ObjectType::Tree)?;
+    let entry = find_entry(repo, &tree, path)?.ok_or(anyhow!(
+        "path '{}' does not exist in '{}'",
+        path,
+        revision
+    ))?;
+
+    Ok(entry.hash)
+}
+
+/// `{...}` の外にある最初の `~` か `^` で基点と接尾辞に分ける
+fn split_suffixes(revision: &str) -> (&str, &str) {
+    let mut depth = 0;
+    for (i, c) in revision.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '~' | '^' if depth == 0 => return (&revision[..i], &revision[i..]),
+            _ => {}
+        }
+    }
+
+    (revision, "")
+}
+
+fn resolve_base(repo: &Repository, base: &str) -> Result<Hash> {
+    if base.is_empty() || base == "@" {
+        return resolve_name(repo, "HEAD");
+    }
+
+    let Some((name, spec)) = base.split_once("@{") else {
+        return resolve_name(repo, base);
+    };
+    let spec = spec
+        .strip_suffix('}')
+        .ok_or(anyhow!("invalid revision '{}'", base))?;
+
+    if let Some(n) = spec.strip_prefix('-') {
+        if !name.is_empty() {
+            bail!("invalid revision '{}'", base);
+        }
+        let n = usize::from_str(n)?;
+        let branch = previous_branch(repo, n)?;
+
+        return resolve_name(repo, &branch);
+    }
+
+    let n = usize::from_str(spec).map_err(|_| anyhow!("unsupported reflog selector '{}'", base))?;
+    let log_name = Reflog::resolve_name(repo, name)?;
+    let reflog = Reflog::read(repo, &log_name)?;
+    let entry = reflog.nth_latest(n).ok_or(anyhow!(
+        "log for '{}' only has {} entries",
+        log_name,
+        reflog.entries.len()
+    ))?;
+
+    Ok(entry.new.clone())
+}
+
+/// `@{-n}` で n 個前にいたブランチを HEAD の reflog から探す
+fn previous_branch(repo: &Repository, n: usize) -> Result<String> {
+    let reflog = Reflog::read(repo, "HEAD")?;
+
+    reflog
+        .entries
+        .iter()
+        .rev()
+        .filter_map(|entry| {
+            let moving = entry.message.strip_prefix("checkout: moving from ")?;
+            let (from, _to) = moving.split_once(" to ")?;
+            Some(from.to_string())
+        })
+        .nth(n.saturating_sub(1))
+        .ok_or(anyhow!("@{{-{}}}: not enough checkouts in the reflog", n))
+}
+
+fn resolve_name(repo: &Repository, name: &str) -> Result<Hash> {
+    if name == "HEAD" {
+        return Head::read(repo)?
+            .read_hash(repo)?
+            .ok_or(anyhow!("ambiguous argument 'HEAD': unknown revision"));
+    }
+
+    // reset や merge が残す `ORIG_HEAD`, `MERGE_HEAD` (複数行なら最初の行)
+    if [ORIG_HEAD_PATH, MERGE_HEAD_PATH].contains(&name) {
+        if let Ok(content) = std::fs::read_to_string(repo.git_path(name)) {
+            let line = content.lines().next().unwrap_or_default();
+            return Ok(Hash::from_str(line.trim())?);
+        }
+    }
+
+    let is_hex = !name.is_empty() && name.chars().all(|c| c.is_ascii_hexdigit());
+    if is_hex && name.len() == 40 {
+        return Ok(Hash::from_str(&name.to_ascii_lowercase())?);
+    }
+
+    // `git rev-parse` と同じ順に `refs/<name>`, tag, branch, remote, remote の HEAD を探す
+    let candidates = if name.starts_with("refs/") {
+        vec![Ref::from_str(name)?]
+    } else {
+        [
+            format!("refs/{}", name),
+            format!("refs/tags/{}", name),
+            format!("refs/heads/{}", name),
+            format!("refs/remotes/{}", name),
+            format!("refs/remotes/{}/HEAD", name),
+        ]
+        .iter()
+        .filter_map(|candidate| Ref::from_str(candidate).ok())
+        .collect()
+    };
+    for ref_ in candidates {
+        if let Some(hash) = ref_.read_hash(repo)? {
+            return Ok(Hash::from_str(&hash)?);
+        }
+    }
+
+    if is_hex && name.len() >= MIN_ABBREV_LENGTH {
+        let hashes = GitObject::find_by_prefix(repo, name)?;
+        match hashes.as_slice() {
+            [hash] => return Ok(hash.clone()),
+            [] => {}
+            _ => {
+                let candidates = hashes
+                    .iter()
+                    .map(|hash| {
+                        let type_ = GitObject::read(repo, hash)
+                            .map(|object| object.type_.to_string())
+                            .unwrap_or_default();
+                        format!("  {} {}", hash, type_)
+                    })
+                    .collect::<Vec<_>>();
+                bail!(
+                    "short object ID {} is ambiguous\nThe candidates are:\n{}",
+                    name,
+                    candidates.join("\n")
+                );
+            }
+        }
+    }
+
+    bail!("ambiguous argument '{}': unknown revision", name);
+}
+
+fn apply_suffixes(
+    repo: &Repository,
+    mut hash: Hash,
+    mut suffixes: &str,
+    revision: &str,
+) -> Result<Hash> {
+    while let Some(c) = suffixes.chars().next() {
+        suffixes = &suffixes[1..];
+
+        if c == '^' && suffixes.starts_with('{') {
+            let end = suffixes
+                .find('}')
+                .ok_or(anyhow!("invalid revision '{}'", revision))?;
+            let type_ = &suffixes[1..end];
+            suffixes = &suffixes[end + 1..];
+
+            hash = match type_ {
+                // tag 以外のオブジェクトになるまで剥がす
+                "" => loop {
+                    let object = GitObject::read(repo, &hash)?;
+                    if object.type_ != ObjectType::Tag {
+                        break hash;
+                    }
+                    hash = object.parse_tag_body()?.object;
+                },
+                type_ => GitObject::peel(repo, &hash, ObjectType::from_str(type_)?)?.0,
+            };
+            continue;
+        }
+
+        let digits = suffixes
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(suffixes.len());
+        let n = if digits == 0 {
+            1
+        } else {
+            usize::from_str(&suffixes[..digits])?
+        };
+        suffixes = &suffixes[digits..];
+
+        let (commit_hash, object) = GitObject::peel(repo, &hash, ObjectType::Commit)?;
+        let commit = object.parse_commit_body()?;
+        hash = match c {
+            '^' if n == 0 => commit_hash,
+            '^' => commit.parent.get(n - 1).cloned().ok_or(anyhow!(
+                "invalid revision '{}': no parent {}",
+                revision,
+                n
+            ))?,
+            '~' => {
+                let mut hash = commit_hash;
+                for _ in 0..n {
+                    let commit = GitObject::read(repo, &hash)?.parse_commit_body()?;
+                    hash = commit
+                        .parent
+                        .first()
+                        .cloned()
+                        .ok_or(anyhow!("invalid revision '{}': no ancestor", revision))?;
+                }
+                hash
+            }
+            _ => unreachable!("suffix must start with ~ or ^"),
+        };
+    }
+
+    Ok(hash)
+}
+
+/// 全てのオブジェクトの中で一意になる最短の省略形を返す
+pub fn shortest_unique_prefix(repo: &Repository, hash: &Hash, min_length: usize) -> Result<String> {
+    let hex = hash.to_string();
+
+    for length in min_length.max(MIN_ABBREV_LENGTH)..hex.len() {
+        if GitObject::find_by_prefix(repo, &hex[..length])?.len() <= 1 {
+            return Ok(hex[..length].to_string());
+        }
+    }
+
+    Ok(hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_path() {
+        assert_eq!(
+            split_path("HEAD:src/main.rs"),
+            Some(("HEAD", "src/main.rs"))
+        );
+        assert_eq!(split_path(":README.md"), Some(("", "README.md")));
+        assert_eq!(split_path("main@{1}"), None);
+        assert_eq!(split_path("HEAD~2"), None);
+    }
+
+    #[test]
+    fn test_split_suffixes() {
+        assert_eq!(split_suffixes("HEAD~2^{tree}"), ("HEAD", "~2^{tree}"));
+        assert_eq!(split_suffixes("main@{1}^2"), ("main@{1}", "^2"));
+        assert_eq!(split_suffixes("v1.0"), ("v1.0", ""));
+    }
+}
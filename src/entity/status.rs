@@ -0,0 +1,494 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    os::unix::fs::MetadataExt,
+    path::Path,
+};
+
+use anyhow::Result;
+
+use crate::{
+    consts::{GIT_DIRECTORY, GIT_INDEX_PATH},
+    entity::{
+        head::Head,
+        ignore::Ignore,
+        index::{Index, IndexEntry},
+        object::{
+            hash::Hash,
+            mode::{BlobType, Mode},
+        },
+        pathspec::Pathspec,
+        repository::Repository,
+        worktree::{self, TreeFiles},
+    },
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+    TypeChanged,
+}
+impl ChangeKind {
+    pub fn between(from: Option<&(BlobType, Hash)>, to: Option<&(BlobType, Hash)>) -> Option<Self> {
+        match (from, to) {
+            (None, None) => None,
+            (None, Some(_)) => Some(Self::Added),
+            (Some(_), None) => Some(Self::Deleted),
+            (Some(from), Some(to)) if from == to => None,
+            (Some((from_type, _)), Some((to_type, _)))
+                if (*from_type == BlobType::Symlink) != (*to_type == BlobType::Symlink) =>
+            {
+                Some(Self::TypeChanged)
+            }
+            (Some(_), Some(_)) => Some(Self::Modified),
+        }
+    }
+
+    pub fn short_code(&self) -> char {
+        match self {
+            Self::Added => 'A',
+            Self::Modified => 'M',
+            Self::Deleted => 'D',
+            Self::TypeChanged => 'T',
+        }
+    }
+}
+impl std::fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Added => "new file",
+            Self::Modified => "modified",
+            Self::Deleted => "deleted",
+            Self::TypeChanged => "typechange",
+        };
+
+        f.write_str(s)
+    }
+}
+
+/// 1 つのパスについての HEAD, index, 作業ツリーの状態
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StatusEntry {
+    pub path: String,
+    pub head: Option<(BlobType, Hash)>,
+    pub index: Option<(BlobType, Hash)>,
+    pub worktree: Option<(BlobType, Hash)>,
+}
+impl StatusEntry {
+    pub fn staged(&self) -> Option<ChangeKind> {
+        ChangeKind::between(self.head.as_ref(), self.index.as_ref())
+    }
+
+    pub fn unstaged(&self) -> Option<ChangeKind> {
+        // index にないファイルは untracked として別に扱う
+        self.index.as_ref()?;
+
+        ChangeKind::between(self.index.as_ref(), self.worktree.as_ref())
+    }
+}
+
+/// 衝突中のパスが base, ours, theirs のどれを持っているか
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnmergedKind {
+    BothDeleted,
+    AddedByUs,
+    DeletedByThem,
+    AddedByThem,
+    DeletedByUs,
+    BothAdded,
+    BothModified,
+}
+impl UnmergedKind {
+    fn from_stages(base: bool, ours: bool, theirs: bool) -> Self {
+        match (base, ours, theirs) {
+            (true, false, false) => Self::BothDeleted,
+            (false, true, false) => Self::AddedByUs,
+            (true, true, false) => Self::DeletedByThem,
+            (false, false, true) => Self::AddedByThem,
+            (true, false, true) => Self::DeletedByUs,
+            (false, true, true) => Self::BothAdded,
+            _ => Self::BothModified,
+        }
+    }
+
+    pub fn short_code(&self) -> &'static str {
+        match self {
+            Self::BothDeleted => "DD",
+            Self::AddedByUs => "AU",
+            Self::DeletedByThem => "UD",
+            Self::AddedByThem => "UA",
+            Self::DeletedByUs => "DU",
+            Self::BothAdded => "AA",
+            Self::BothModified => "UU",
+        }
+    }
+}
+impl std::fmt::Display for UnmergedKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::BothDeleted => "both deleted",
+            Self::AddedByUs => "added by us",
+            Self::DeletedByThem => "deleted by them",
+            Self::AddedByThem => "added by them",
+            Self::DeletedByUs => "deleted by us",
+            Self::BothAdded => "both added",
+            Self::BothModified => "both modified",
+        };
+
+        f.write_str(s)
+    }
+}
+
+/// マージの衝突が解消されていないパス
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UnmergedEntry {
+    pub path: String,
+    /// stage 1, 2, 3 (base, ours, theirs) の内容
+    pub stages: [Option<(BlobType, Hash)>; 3],
+    pub worktree: Option<(BlobType, Hash)>,
+}
+impl UnmergedEntry {
+    pub fn kind(&self) -> UnmergedKind {
+        let [base, ours, theirs] = &self.stages;
+        UnmergedKind::from_stages(base.is_some(), ours.is_some(), theirs.is_some())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Status {
+    pub entries: Vec<StatusEntry>,
+    pub unmerged: Vec<UnmergedEntry>,
+    pub untracked: Vec<String>,
+}
+impl Status {
+    /// pathspec に一致するパスだけを見る
+    pub fn read(repo: &Repository, pathspec: &Pathspec) -> Result<Self> {
+        let head_files =
+            worktree::read_tree_files(repo, Head::read(repo)?.read_tree_hash(repo)?.as_ref())?;
+        let index = Index::read(repo)?.unwrap_or_else(Index::new);
+        let index_entries = index
+            .entries
+            .iter()
+            .filter(|entry| entry.stage == 0)
+            .map(|entry| (entry.file_name.as_str(), entry))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut unmerged = BTreeMap::new();
+        for entry in index
+            .entries
+            .iter()
+            .filter(|entry| entry.stage != 0 && pathspec.matches(&entry.file_name))
+        {
+            let unmerged_entry =
+                unmerged
+                    .entry(entry.file_name.as_str())
+                    .or_insert_with(|| UnmergedEntry {
+                        path: entry.file_name.clone(),
+                        stages: [None, None, None],
+                        worktree: None,
+                    });
+            unmerged_entry.stages[entry.stage as usize - 1] =
+                Some((blob_type_of(entry), entry.hash.clone()));
+        }
+        for unmerged_entry in unmerged.values_mut() {
+            unmerged_entry.worktree = worktree::hash_file(repo, &unmerged_entry.path)?;
+        }
+        let index_modified = std::fs::metadata(repo.git_path(GIT_INDEX_PATH))
+            .map(|metadata| (metadata.mtime(), metadata.mtime_nsec()))
+            .ok();
+
+        let paths = head_files
+            .keys()
+            .map(String::as_str)
+            .chain(index_entries.keys().copied())
+            .filter(|path| !unmerged.contains_key(path) && pathspec.matches(path))
+            .collect::<BTreeSet<_>>();
+
+        let mut entries = Vec::new();
+        for path in paths {
+            let index_entry = index_entries.get(path).copied();
+            let worktree = match index_entry {
+                Some(entry) if is_stat_clean(repo, entry, index_modified) => {
+                    Some((blob_type_of(entry), entry.hash.clone()))
+                }
+                _ => worktree::hash_file(repo, path)?,
+            };
+
+            let entry = StatusEntry {
+                path: path.to_string(),
+                head: head_files.get(path).cloned(),
+                index: index_entry.map(|entry| (blob_type_of(entry), entry.hash.clone())),
+                worktree,
+            };
+            if entry.staged().is_some() || entry.unstaged().is_some() {
+                entries.push(entry);
+            }
+        }
+
+        let tracked = index
+            .entries
+            .iter()
+            .map(|entry| entry.file_name.as_str())
+            .collect::<BTreeSet<_>>();
+        let ignore = Ignore::read(repo)?;
+        let mut untracked = Vec::new();
+        collect_untracked(
+            repo,
+            &ignore,
+            pathspec,
+            Path::new(""),
+            &tracked,
+            &mut untracked,
+        )?;
+
+        Ok(Self {
+            entries,
+            unmerged: unmerged.into_values().collect(),
+            untracked,
+        })
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.entries.is_empty() && self.unmerged.is_empty() && self.untracked.is_empty()
+    }
+}
+
+/// index の中身を tree と同じ形に平坦にする
+pub fn read_index_files(index: &Index) -> TreeFiles {
+    index
+        .entries
+        .iter()
+        .filter(|entry| entry.stage == 0 && !entry.intent_to_add)
+        .map(|entry| {
+            (
+                entry.file_name.clone(),
+                (blob_type_of(entry), entry.hash.clone()),
+            )
+        })
+        .collect()
+}
+
+/// index に載っているパスについて作業ツリー上の状態を返す (stat が一致すれば hash し直さない)
+pub fn read_worktree_files(repo: &Repository, index: &Index) -> Result<TreeFiles> {
+    let index_modified = std::fs::metadata(repo.git_path(GIT_INDEX_PATH))
+        .map(|metadata| (metadata.mtime(), metadata.mtime_nsec()))
+        .ok();
+
+    let mut files = TreeFiles::new();
+    for entry in index.entries.iter().filter(|entry| entry.stage == 0) {
+        let state = if is_stat_clean(repo, entry, index_modified) {
+            Some((blob_type_of(entry), entry.hash.clone()))
+        } else {
+            worktree::hash_file(repo, &entry.file_name)?
+        };
+        if let Some(state) = state {
+            files.insert(entry.file_name.clone(), state);
+        }
+    }
+
+    Ok(files)
+}
+
+fn blob_type_of(entry: &IndexEntry) -> BlobType {
+    match entry.mode {
+        Mode::Blob(blob_type) => blob_type,
+        Mode::Tree => unreachable!("Unexpected tree entry in index: {:?}", entry),
+    }
+}
+
+/// index に記録された stat 情報と一致すれば内容も変わっていないとみなす
+fn is_stat_clean(
+    repo: &Repository,
+    entry: &IndexEntry,
+    index_modified: Option<(i64, i64)>,
+) -> bool {
+    if entry.assume_valid || entry.skip_worktree {
+        return true;
+    }
+    let Ok(path) = repo.work_tree_path(&entry.file_name) else {
+        return false;
+    };
+    let Ok(metadata) = path.symlink_metadata() else {
+        return false;
+    };
+    let Ok(mode) = Mode::from_file_metadata(&metadata) else {
+        return false;
+    };
+
+    let modified = (metadata.mtime(), metadata.mtime_nsec());
+    // index を書いた後に同じ時刻のうちに変更された可能性がある (racy git) ので再計算させる
+    if index_modified.is_some_and(|index_modified| modified >= index_modified) {
+        return false;
+    }
+
+    mode == entry.mode
+        && modified == (entry.updated_at as i64, entry.updated_at_nsec as i64)
+        && metadata.size() as u32 == entry.size
+        && metadata.ino() as u32 == entry.inode
+}
+
+/// tracked なファイルを含まないディレクトリは `dir/` にまとめる (無視されるものは含めない)
+fn collect_untracked(
+    repo: &Repository,
+    ignore: &Ignore,
+    pathspec: &Pathspec,
+    directory: &Path,
+    tracked: &BTreeSet<&str>,
+    untracked: &mut Vec<String>,
+) -> Result<()> {
+    let mut children = std::fs::read_dir(repo.work_tree_path(directory)?)?
+        .map(|entry| entry.map(|entry| directory.join(entry.file_name())))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    children.sort();
+
+    for path in children {
+        if path == Path::new(GIT_DIRECTORY) {
+            continue;
+        }
+
+        let name = path.to_string_lossy().to_string();
+        let metadata = repo.work_tree_path(&path)?.symlink_metadata()?;
+        if !tracked.contains(name.as_str()) && ignore.is_ignored(&name, metadata.is_dir())? {
+            continue;
+        }
+        if !metadata.is_dir() {
+            if !tracked.contains(name.as_str()) && pathspec.matches(&name) {
+                untracked.push(name);
+            }
+            continue;
+        }
+
+        let prefix = format!("{}/", name);
+        let contains_tracked = tracked
+            .range(prefix.as_str()..)
+            .next()
+            .is_some_and(|tracked| tracked.starts_with(&prefix));
+        // ディレクトリごと pathspec に含まれなければ中のファイルを個別に見る
+        // 入れ子の repository は中を見ずにディレクトリとして表示する
+        if worktree::is_nested_repository(repo, &name)? {
+            if !contains_tracked && pathspec.matches(&name) {
+                untracked.push(prefix);
+            }
+        } else if contains_tracked || !pathspec.matches(&name) {
+            collect_untracked(repo, ignore, pathspec, &path, tracked, untracked)?;
+        } else {
+            let (files, repositories) =
+                worktree::list_files_and_repositories(repo, &name, Some(ignore))?;
+            if !files.is_empty() || !repositories.is_empty() {
+                untracked.push(prefix);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    fn init_repo(name: &str) -> Repository {
+        let directory =
+            std::env::temp_dir().join(format!("ssgit-status-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+
+        Repository::init(&directory, false, Some("main")).unwrap()
+    }
+
+    fn set_modified(path: &Path, time: SystemTime) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_racily_clean_entry_is_rehashed() {
+        let repo = init_repo("racy");
+        let path = repo.work_tree_path("a").unwrap();
+        let index_path = repo.git_path(GIT_INDEX_PATH);
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        std::fs::write(&path, "old\n").unwrap();
+        set_modified(&path, time);
+        let (_, hash) = worktree::hash_file(&repo, "a").unwrap().unwrap();
+        let mut index = Index::new();
+        index.insert(&[IndexEntry::with_file_metadata(
+            Mode::Blob(BlobType::Plain),
+            hash,
+            "a",
+            &path.symlink_metadata().unwrap(),
+        )
+        .unwrap()]);
+        index.write(&repo).unwrap();
+
+        // 大きさも mtime も同じまま中身だけ書き換える
+        std::fs::write(&path, "new\n").unwrap();
+        set_modified(&path, time);
+        let status = |index_time: SystemTime| {
+            set_modified(&index_path, index_time);
+            Status::read(&repo, &Pathspec::parse(&repo, &[]).unwrap()).unwrap()
+        };
+
+        // index が後で書かれたなら stat だけで変更がないと判断する
+        let status_after = status(time + Duration::from_secs(1));
+        assert_eq!(status_after.entries[0].unstaged(), None);
+
+        // 同じ時刻に書かれた index の entry は信用せずに hash し直す
+        let status_racy = status(time);
+        assert_eq!(
+            status_racy.entries[0].unstaged(),
+            Some(ChangeKind::Modified)
+        );
+
+        std::fs::remove_dir_all(repo.work_tree().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_untracked_directory_is_collapsed() {
+        let repo = init_repo("untracked");
+        let root = repo.work_tree().unwrap().to_path_buf();
+        for (path, content) in [
+            ("tracked/a", "a\n"),
+            ("tracked/b", "b\n"),
+            ("new/x", "x\n"),
+            ("new/sub/y", "y\n"),
+            ("build/out.o", "o\n"),
+            (".gitignore", "*.o\n"),
+        ] {
+            std::fs::create_dir_all(root.join(path).parent().unwrap()).unwrap();
+            std::fs::write(root.join(path), content).unwrap();
+        }
+        std::fs::create_dir_all(root.join("empty")).unwrap();
+        let (_, hash) = worktree::hash_file(&repo, "tracked/a").unwrap().unwrap();
+        let mut index = Index::new();
+        index.insert(&[IndexEntry::with_default(
+            Mode::Blob(BlobType::Plain),
+            hash,
+            "tracked/a",
+        )]);
+        index.write(&repo).unwrap();
+
+        let untracked = |specs: &[&str]| {
+            let specs = specs
+                .iter()
+                .map(|spec| spec.to_string())
+                .collect::<Vec<_>>();
+            Status::read(&repo, &Pathspec::parse(&repo, &specs).unwrap())
+                .unwrap()
+                .untracked
+        };
+
+        // 無視されるものしかないディレクトリや空のディレクトリは表示しない
+        assert_eq!(untracked(&[]), [".gitignore", "new/", "tracked/b"]);
+        assert_eq!(untracked(&["new/sub"]), ["new/sub/"]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
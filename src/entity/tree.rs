@@ -1,11 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
 use crate::entity::index::Index;
+use crate::entity::object::database::ObjectDatabase;
 use crate::entity::object::hash::Hash;
 use crate::entity::object::mode::{BlobType, Mode};
 use crate::entity::object::{GitObject, TreeEntry};
-use anyhow::{bail, Result};
+use crate::error::{Error, Result};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TreeNodeInfo {
@@ -61,7 +62,9 @@ impl TreeNode {
                 tree.insert(name, node);
                 Ok(())
             }
-            _ => bail!("Cannot push node to non-tree node"),
+            _ => Err(Error::InvalidArgument(
+                "Cannot push node to non-tree node".to_string(),
+            )),
         }
     }
 
@@ -87,10 +90,13 @@ impl TreeNode {
 
                 next_node.push_vec_path(path, node)
             }
-            _ => bail!("Cannot push node to non-tree node"),
+            _ => Err(Error::InvalidArgument(
+                "Cannot push node to non-tree node".to_string(),
+            )),
         }
     }
 
+    /// index の entry と同じく、パスは作業ツリーを見ずに blob として扱う
     pub fn push_full_path_blob(
         &mut self,
         path: impl Into<PathBuf>,
@@ -100,11 +106,7 @@ impl TreeNode {
         let path: PathBuf = path.into();
 
         if path.is_absolute() {
-            bail!("Path must be relative")
-        }
-
-        if path.is_dir() {
-            bail!("Path must be a file")
+            return Err(Error::InvalidArgument("Path must be relative".to_string()));
         }
 
         let path = path
@@ -117,7 +119,7 @@ impl TreeNode {
 
     fn post_order_traversal<ReturnValue>(
         &self,
-        mut f: impl FnMut(TraverseNode<ReturnValue>) -> ReturnValue,
+        f: &mut impl FnMut(TraverseNode<ReturnValue>) -> ReturnValue,
     ) -> ReturnValue {
         match &self.tree_info {
             TreeNodeInfo::Blob(_, _) => f(TraverseNode::Blob(self)),
@@ -125,7 +127,7 @@ impl TreeNode {
                 let mut children = HashMap::new();
 
                 for (name, node) in tree {
-                    children.insert(name.clone(), node.post_order_traversal(&mut f));
+                    children.insert(name.clone(), node.post_order_traversal(f));
                 }
 
                 f(TraverseNode::Tree(self, children))
@@ -151,7 +153,10 @@ impl TreeNode {
         }
     }
 
-    fn write_recursive_inner(traverse_node: TraverseNode<(Mode, Hash)>) -> Result<(Mode, Hash)> {
+    fn write_recursive_inner(
+        db: &dyn ObjectDatabase,
+        traverse_node: TraverseNode<(Mode, Hash)>,
+    ) -> Result<(Mode, Hash)> {
         match traverse_node {
             TraverseNode::Blob(node) => {
                 let (blob_type, hash) = match &node.tree_info {
@@ -178,17 +183,11 @@ impl TreeNode {
                 let git_object = GitObject::new_tree(&tree_entries);
                 let hash = git_object.hash();
 
-                let path = hash.get_object_path();
-                if path.exists() {
-                    let existing_object = GitObject::read(&hash)?;
-                    dbg!(existing_object == git_object);
-                    if existing_object != git_object {
-                        dbg!(&path);
-                    }
+                if GitObject::exists(db, &hash)? {
                     return Ok((Mode::Tree, hash));
                 }
 
-                git_object.write()?;
+                git_object.write(db)?;
 
                 let mode = Mode::Tree;
 
@@ -197,25 +196,152 @@ impl TreeNode {
         }
     }
 
-    pub fn write_recursive(&self) -> Result<Hash> {
-        let (_mode, hash) = self.post_order_traversal_or_err(&mut Self::write_recursive_inner)?;
+    pub fn write_recursive(&self, db: &dyn ObjectDatabase) -> Result<Hash> {
+        let (_mode, hash) =
+            self.post_order_traversal_or_err(&mut |node| Self::write_recursive_inner(db, node))?;
 
         Ok(hash)
     }
+
+    /// tree object を再帰的に読み込む
+    pub fn read(db: &dyn ObjectDatabase, hash: &Hash) -> Result<Self> {
+        Self::read_inner(db, "".to_string(), hash)
+    }
+
+    fn read_inner(db: &dyn ObjectDatabase, name: String, hash: &Hash) -> Result<Self> {
+        let object = GitObject::read(db, hash)?;
+
+        let mut node = Self::new_tree(name);
+        for entry in object.parse_tree_body()? {
+            let child = match entry.file_type {
+                Mode::Tree => Self::read_inner(db, entry.name.clone(), &entry.hash)?,
+                Mode::Blob(blob_type) => Self::new_blob(blob_type, entry.name.clone(), entry.hash),
+            };
+
+            node.push_node(entry.name, child)?;
+        }
+
+        Ok(node)
+    }
+
+    /// 全ての blob を `a/b/c` 形式のパスをキーとして列挙する
+    pub fn files(&self) -> BTreeMap<String, (BlobType, Hash)> {
+        self.post_order_traversal(&mut |traverse_node: TraverseNode<BTreeMap<_, _>>| {
+            match traverse_node {
+                TraverseNode::Blob(node) => {
+                    let TreeNodeInfo::Blob(blob_type, hash) = &node.tree_info else {
+                        unreachable!("Expected blob node");
+                    };
+
+                    BTreeMap::from([("".to_string(), (*blob_type, hash.clone()))])
+                }
+                TraverseNode::Tree(_node, children) => children
+                    .into_iter()
+                    .flat_map(|(name, files)| {
+                        files.into_iter().map(move |(path, value)| {
+                            if path.is_empty() {
+                                (name.clone(), value)
+                            } else {
+                                (format!("{}/{}", name, path), value)
+                            }
+                        })
+                    })
+                    .collect(),
+            }
+        })
+    }
 }
-impl From<Index> for TreeNode {
-    fn from(index: Index) -> Self {
+/// tree の中から `a/b/c` 形式のパスにあるエントリを探す (空のパスなら tree 自身)
+pub fn find_entry(db: &dyn ObjectDatabase, tree: &Hash, path: &str) -> Result<Option<TreeEntry>> {
+    let mut components = path.split('/').filter(|c| !c.is_empty()).peekable();
+    if components.peek().is_none() {
+        return Ok(Some(TreeEntry {
+            file_type: Mode::Tree,
+            name: "".to_string(),
+            hash: tree.clone(),
+        }));
+    }
+
+    let mut tree = tree.clone();
+    while let Some(name) = components.next() {
+        let entries = GitObject::read(db, &tree)?.parse_tree_body()?;
+        let Some(entry) = entries.into_iter().find(|entry| entry.name == name) else {
+            return Ok(None);
+        };
+
+        if components.peek().is_none() {
+            return Ok(Some(entry));
+        }
+        if entry.file_type != Mode::Tree {
+            return Ok(None);
+        }
+        tree = entry.hash;
+    }
+
+    unreachable!("path has at least one component")
+}
+
+impl TryFrom<Index> for TreeNode {
+    type Error = Error;
+
+    fn try_from(index: Index) -> Result<Self> {
         let mut root = Self::new_root();
 
-        for entry in index.entries {
+        // intent-to-add の entry はまだ内容が登録されていないので tree には含めない
+        for entry in index
+            .entries
+            .into_iter()
+            .filter(|entry| !entry.intent_to_add)
+        {
             let Mode::Blob(blob_type) = entry.mode else {
-                panic!("Unexpected tree entry in index: {:?}", entry);
+                return Err(Error::CorruptIndex(format!(
+                    "unexpected tree entry in index: {}",
+                    entry.file_name
+                )));
             };
 
-            root.push_full_path_blob(&entry.file_name, blob_type, entry.hash)
-                .unwrap();
+            root.push_full_path_blob(&entry.file_name, blob_type, entry.hash)?;
         }
 
-        root
+        Ok(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::index::IndexEntry;
+
+    #[test]
+    fn test_try_from_index_ignores_work_tree() {
+        // テストのカレントディレクトリには src というディレクトリがあるが、entry は blob として扱う
+        let hash = Hash::hash_bytes(b"target");
+        let mut index = Index::new();
+        index.insert(&[IndexEntry::with_default(
+            Mode::Blob(BlobType::Symlink),
+            hash.clone(),
+            "src",
+        )]);
+
+        let root = TreeNode::try_from(index).unwrap();
+        let TreeNodeInfo::Tree(children) = &root.tree_info else {
+            panic!("root must be a tree");
+        };
+        assert_eq!(
+            children["src"].tree_info,
+            TreeNodeInfo::Blob(BlobType::Symlink, hash)
+        );
+    }
+
+    #[test]
+    fn test_try_from_index_rejects_tree_entry() {
+        let mut index = Index::new();
+        index.insert(&[IndexEntry::with_default(
+            Mode::Tree,
+            Hash::hash_bytes(b"tree"),
+            "dir",
+        )]);
+
+        assert!(TreeNode::try_from(index).is_err());
     }
 }
@@ -0,0 +1,171 @@
+/// `*`, `?`, `[...]`, `**` を使った glob。
+/// pathname なら `*` や `?` は `/` に一致せず、`**` だけがディレクトリをまたぐ
+pub fn wildmatch(pattern: &[u8], text: &[u8], pathname: bool) -> bool {
+    Matcher {
+        pattern,
+        text,
+        pathname,
+    }
+    .matches(0, 0)
+}
+
+struct Matcher<'a> {
+    pattern: &'a [u8],
+    text: &'a [u8],
+    pathname: bool,
+}
+impl Matcher<'_> {
+    /// pathname の時だけ `/` を特別扱いする
+    fn is_separator(&self, c: u8) -> bool {
+        self.pathname && c == b'/'
+    }
+
+    fn matches(&self, mut pi: usize, mut ti: usize) -> bool {
+        let Self { pattern, text, .. } = *self;
+        while pi < pattern.len() {
+            match pattern[pi] {
+                b'*' => {
+                    let mut rest = pi;
+                    while pattern.get(rest) == Some(&b'*') {
+                        rest += 1;
+                    }
+                    let double =
+                        self.pathname && rest - pi >= 2 && (pi == 0 || pattern[pi - 1] == b'/');
+
+                    if double && rest == pattern.len() {
+                        return true;
+                    }
+                    if double && pattern[rest] == b'/' {
+                        // `**/` は 0 個以上のディレクトリに一致する
+                        let after = rest + 1;
+                        return self.matches(after, ti)
+                            || (ti..text.len())
+                                .any(|i| text[i] == b'/' && self.matches(after, i + 1));
+                    }
+
+                    for i in ti..=text.len() {
+                        if self.matches(rest, i) {
+                            return true;
+                        }
+                        if i < text.len() && self.is_separator(text[i]) {
+                            break;
+                        }
+                    }
+                    return false;
+                }
+                b'?' => {
+                    if ti >= text.len() || self.is_separator(text[ti]) {
+                        return false;
+                    }
+                    pi += 1;
+                    ti += 1;
+                }
+                b'[' => {
+                    if ti >= text.len() || self.is_separator(text[ti]) {
+                        return false;
+                    }
+                    match match_class(pattern, pi, text[ti]) {
+                        Some((true, next)) => {
+                            pi = next;
+                            ti += 1;
+                        }
+                        Some((false, _)) => return false,
+                        // 閉じていない `[` はただの文字として扱う
+                        None => {
+                            if text[ti] != b'[' {
+                                return false;
+                            }
+                            pi += 1;
+                            ti += 1;
+                        }
+                    }
+                }
+                c => {
+                    let (c, next) = match c {
+                        b'\\' if pi + 1 < pattern.len() => (pattern[pi + 1], pi + 2),
+                        c => (c, pi + 1),
+                    };
+                    if ti >= text.len() || text[ti] != c {
+                        return false;
+                    }
+                    pi = next;
+                    ti += 1;
+                }
+            }
+        }
+
+        ti == text.len()
+    }
+}
+
+/// `[...]` が c に一致するかと、`]` の次の位置を返す
+fn match_class(pattern: &[u8], start: usize, c: u8) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some(b'!' | b'^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let mut low = *pattern.get(i)?;
+        if low == b']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        if low == b'\\' {
+            i += 1;
+            low = *pattern.get(i)?;
+        }
+        i += 1;
+
+        let high =
+            if pattern.get(i) == Some(&b'-') && pattern.get(i + 1).is_some_and(|&h| h != b']') {
+                let mut high = pattern[i + 1];
+                i += 2;
+                if high == b'\\' {
+                    high = *pattern.get(i)?;
+                    i += 1;
+                }
+                high
+            } else {
+                low
+            };
+
+        if low <= c && c <= high {
+            matched = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildmatch() {
+        assert!(wildmatch(b"*.o", b"main.o", true));
+        assert!(!wildmatch(b"*.o", b"dir/main.o", true));
+        assert!(wildmatch(b"a?c", b"abc", true));
+        assert!(wildmatch(b"[a-c]x", b"bx", true));
+        assert!(!wildmatch(b"[!a-c]x", b"bx", true));
+        assert!(wildmatch(b"[]]", b"]", true));
+        assert!(wildmatch(b"\\*", b"*", true));
+        assert!(wildmatch(b"**/foo", b"foo", true));
+        assert!(wildmatch(b"**/foo", b"a/b/foo", true));
+        assert!(wildmatch(b"a/**/b", b"a/b", true));
+        assert!(wildmatch(b"a/**/b", b"a/x/y/b", true));
+        assert!(wildmatch(b"abc/**", b"abc/x/y", true));
+        assert!(!wildmatch(b"abc/**", b"abc", true));
+        assert!(!wildmatch(b"a**b", b"a/b", true));
+    }
+
+    #[test]
+    fn test_wildmatch_without_pathname() {
+        assert!(wildmatch(b"*.c", b"src/main.c", false));
+        assert!(wildmatch(b"src/?", b"src//", false));
+        assert!(wildmatch(b"a**b", b"a/x/b", false));
+        assert!(!wildmatch(b"*.c", b"main.h", false));
+    }
+}
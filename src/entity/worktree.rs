@@ -0,0 +1,606 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    os::unix::fs::PermissionsExt,
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    consts::GIT_DIRECTORY,
+    entity::{
+        ignore::Ignore,
+        index::{Index, IndexEntry},
+        object::{
+            hash::Hash,
+            mode::{BlobType, Mode},
+            GitObject, ObjectType,
+        },
+        pathspec::Pathspec,
+        repository::Repository,
+        tree::TreeNode,
+    },
+};
+
+pub type TreeFiles = BTreeMap<String, (BlobType, Hash)>;
+
+/// tree の中身を平坦にして返す (tree が None なら空)
+pub fn read_tree_files(repo: &Repository, tree: Option<&Hash>) -> Result<TreeFiles> {
+    match tree {
+        Some(hash) => Ok(TreeNode::read(repo, hash)?.files()),
+        None => Ok(TreeFiles::new()),
+    }
+}
+
+/// 作業ツリー上のファイルを blob として hash した結果を返す (存在しなければ None)
+pub fn hash_file(repo: &Repository, file_name: &str) -> Result<Option<(BlobType, Hash)>> {
+    let path = repo.work_tree_path(file_name)?;
+    let Ok(metadata) = path.symlink_metadata() else {
+        return Ok(None);
+    };
+    if metadata.is_dir() {
+        return Ok(None);
+    }
+
+    let Mode::Blob(blob_type) = Mode::from_file_metadata(&metadata)? else {
+        unreachable!("file mode must be blob");
+    };
+    let content = read_file(&path, blob_type)?;
+    let hash = GitObject::new(ObjectType::Blob, content).hash();
+
+    Ok(Some((blob_type, hash)))
+}
+
+/// 作業ツリー上のファイルの中身 (symlink ならリンク先のパス) を読む
+pub fn read_file(path: &Path, blob_type: BlobType) -> Result<Vec<u8>> {
+    match blob_type {
+        BlobType::Symlink => Ok(std::fs::read_link(path)?
+            .to_string_lossy()
+            .as_bytes()
+            .to_vec()),
+        _ => Ok(std::fs::read(path)?),
+    }
+}
+
+/// blob を作業ツリーに書き出し、その stat 情報を持つ index entry を返す
+pub fn write_file(
+    repo: &Repository,
+    file_name: &str,
+    blob_type: BlobType,
+    hash: &Hash,
+) -> Result<IndexEntry> {
+    let object = GitObject::read(repo, hash)?;
+    if object.type_ != ObjectType::Blob {
+        bail!("{} is not a blob", hash);
+    }
+    write_content(repo, file_name, blob_type, &object.body)?;
+
+    let metadata = repo.work_tree_path(file_name)?.symlink_metadata()?;
+    Ok(IndexEntry::with_file_metadata(
+        Mode::Blob(blob_type),
+        hash.clone(),
+        file_name,
+        &metadata,
+    )?)
+}
+
+/// 内容をそのまま作業ツリーに書き出す (symlink ならリンク先のパスとして扱う)
+pub fn write_content(
+    repo: &Repository,
+    file_name: &str,
+    blob_type: BlobType,
+    content: &[u8],
+) -> Result<()> {
+    let path = repo.work_tree_path(file_name)?;
+    if path.symlink_metadata().is_ok() {
+        if path.is_dir() && !path.is_symlink() {
+            std::fs::remove_dir_all(&path)?;
+        } else {
+            std::fs::remove_file(&path)?;
+        }
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match blob_type {
+        BlobType::Symlink => {
+            let target = std::str::from_utf8(content)?;
+            std::os::unix::fs::symlink(target, &path)?;
+        }
+        BlobType::Plain | BlobType::Executable => {
+            std::fs::write(&path, content)?;
+
+            let permission = if blob_type == BlobType::Executable {
+                0o755
+            } else {
+                0o644
+            };
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(permission))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 作業ツリーからファイルを消し、空になった親ディレクトリも消す
+pub fn remove_file(repo: &Repository, file_name: &str) -> Result<()> {
+    let path = repo.work_tree_path(file_name)?;
+    if path.symlink_metadata().is_ok() {
+        std::fs::remove_file(&path)?;
+    }
+
+    let mut parent = Path::new(file_name).parent();
+    while let Some(directory) = parent {
+        if directory.as_os_str().is_empty()
+            || std::fs::remove_dir(repo.work_tree_path(directory)?).is_err()
+        {
+            break;
+        }
+        parent = directory.parent();
+    }
+
+    Ok(())
+}
+
+/// ディレクトリ以下のファイルを作業ツリーの root からのパスで列挙する (ignore があれば無視されるものは除く)
+pub fn list_files(
+    repo: &Repository,
+    directory: &str,
+    ignore: Option<&Ignore>,
+) -> Result<Vec<String>> {
+    Ok(list_files_and_repositories(repo, directory, ignore)?.0)
+}
+
+/// list_files と同じだが、中に入らなかった入れ子の repository のディレクトリも返す
+pub fn list_files_and_repositories(
+    repo: &Repository,
+    directory: &str,
+    ignore: Option<&Ignore>,
+) -> Result<(Vec<String>, Vec<String>)> {
+    let mut files = Vec::new();
+    let mut repositories = Vec::new();
+    list_files_inner(
+        repo,
+        Path::new(directory),
+        ignore,
+        &mut files,
+        &mut repositories,
+    )?;
+
+    Ok((files, repositories))
+}
+
+/// `.git` を持つディレクトリは別の repository なので、中のファイルは管理しない
+pub fn is_nested_repository(repo: &Repository, directory: impl AsRef<Path>) -> Result<bool> {
+    let directory = directory.as_ref();
+    Ok(!directory.as_os_str().is_empty()
+        && repo
+            .work_tree_path(directory.join(GIT_DIRECTORY))?
+            .symlink_metadata()
+            .is_ok())
+}
+
+fn list_files_inner(
+    repo: &Repository,
+    directory: &Path,
+    ignore: Option<&Ignore>,
+    files: &mut Vec<String>,
+    repositories: &mut Vec<String>,
+) -> Result<()> {
+    let mut children = std::fs::read_dir(repo.work_tree_path(directory)?)?
+        .map(|entry| entry.map(|entry| directory.join(entry.file_name())))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    children.sort();
+
+    for path in children {
+        if path.file_name().is_some_and(|name| name == GIT_DIRECTORY) {
+            continue;
+        }
+
+        let name = path.to_string_lossy().to_string();
+        let is_directory = repo.work_tree_path(&path)?.symlink_metadata()?.is_dir();
+        if let Some(ignore) = ignore {
+            if ignore.is_ignored(&name, is_directory)? {
+                continue;
+            }
+        }
+
+        if is_directory && is_nested_repository(repo, &path)? {
+            repositories.push(name);
+        } else if is_directory {
+            list_files_inner(repo, &path, ignore, files, repositories)?;
+        } else {
+            files.push(name);
+        }
+    }
+
+    Ok(())
+}
+
+/// index と作業ツリーを current から target の tree に切り替える
+///
+/// current (HEAD) と target で内容が同じファイルは index や作業ツリーの変更をそのまま持ち越す。
+/// 内容が異なるファイルにローカルの変更がある場合は何も変更せずにエラーを返す。
+pub fn checkout(repo: &Repository, current: Option<&Hash>, target: Option<&Hash>) -> Result<()> {
+    let current_files = read_tree_files(repo, current)?;
+    let target_files = read_tree_files(repo, target)?;
+    let index = Index::read(repo)?.unwrap_or_else(Index::new);
+    if !index.conflicted_paths().is_empty() {
+        bail!("You need to resolve your current index first");
+    }
+    let index_entries = index
+        .entries
+        .iter()
+        .map(|entry| (entry.file_name.clone(), entry.clone()))
+        .collect::<BTreeMap<_, _>>();
+
+    let paths = current_files
+        .keys()
+        .chain(target_files.keys())
+        .chain(index_entries.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    let mut updates = Vec::new();
+    let mut removals = Vec::new();
+    let mut overwritten = Vec::new();
+    let mut untracked = Vec::new();
+    let mut new_entries = Vec::new();
+
+    for path in paths {
+        let current_file = current_files.get(&path);
+        let target_file = target_files.get(&path);
+        let index_entry = index_entries.get(&path);
+        let index_file = index_entry.map(|entry| (entry_blob_type(entry), entry.hash.clone()));
+
+        if current_file == target_file || index_file.as_ref() == target_file {
+            // 変更の必要がないのでローカルの状態を持ち越す
+            new_entries.extend(index_entry.cloned());
+            continue;
+        }
+
+        // ファイルを置く場所にあるディレクトリは、untracked なファイルを含まない場合だけ消してよい
+        if target_file.is_some() {
+            let files = untracked_in_directory(repo, &path, &index_entries)?;
+            if !files.is_empty() {
+                untracked.extend(files);
+                continue;
+            }
+        }
+
+        let worktree_file = hash_file(repo, &path)?;
+        match (index_entry, current_file) {
+            (Some(_), _) => {
+                if index_file.as_ref() != current_file || worktree_file != index_file {
+                    overwritten.push(path);
+                    continue;
+                }
+            }
+            // 削除が stage されている
+            (None, Some(_)) => {
+                overwritten.push(path);
+                continue;
+            }
+            (None, None) => {
+                if worktree_file.is_some() && worktree_file.as_ref() != target_file {
+                    untracked.push(path);
+                    continue;
+                }
+            }
+        }
+
+        match target_file {
+            Some((blob_type, hash)) => updates.push((path, *blob_type, hash.clone())),
+            None => removals.push(path),
+        }
+    }
+
+    if !overwritten.is_empty() {
+        bail!(
+            "Your local changes to the following files would be overwritten by checkout:\n{}\nPlease commit your changes or stash them before you switch branches.",
+            format_paths(&overwritten)
+        );
+    }
+    if !untracked.is_empty() {
+        bail!(
+            "The following untracked working tree files would be overwritten by checkout:\n{}\nPlease move or remove them before you switch branches.",
+            format_paths(&untracked)
+        );
+    }
+
+    for path in removals {
+        remove_file(repo, &path)?;
+    }
+    for (path, blob_type, hash) in updates {
+        new_entries.push(write_file(repo, &path, blob_type, &hash)?);
+    }
+
+    let mut new_index = Index::new();
+    new_index.version = index.version;
+    new_index.insert(&new_entries);
+    new_index.write(repo)?;
+
+    Ok(())
+}
+
+/// pathspec に一致するパスの index entry を tree の内容に戻す (作業ツリーは変更しない)
+///
+/// 内容が変わらない entry は stat 情報をそのまま残す
+pub fn reset_index(repo: &Repository, tree: Option<&Hash>, pathspec: &Pathspec) -> Result<()> {
+    let tree_files = read_tree_files(repo, tree)?;
+    let mut index = Index::read(repo)?.unwrap_or_else(Index::new);
+    let old_entries = index
+        .entries
+        .iter()
+        .filter(|entry| entry.stage == 0)
+        .map(|entry| (entry.file_name.clone(), entry.clone()))
+        .collect::<BTreeMap<_, _>>();
+
+    let paths = index
+        .entries
+        .iter()
+        .map(|entry| &entry.file_name)
+        .chain(tree_files.keys())
+        .filter(|path| pathspec.matches(path))
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    let mut entries = Vec::new();
+    for path in paths {
+        let Some((blob_type, hash)) = tree_files.get(&path) else {
+            index.remove(&path);
+            continue;
+        };
+
+        let mode = Mode::Blob(*blob_type);
+        match old_entries.get(&path) {
+            Some(entry) if entry.mode == mode && &entry.hash == hash => entries.push(entry.clone()),
+            _ => entries.push(IndexEntry::with_default(mode, hash.clone(), &path)),
+        }
+    }
+    index.insert(&entries);
+    index.write(repo)?;
+
+    Ok(())
+}
+
+/// index と作業ツリーを tree の内容に強制的に合わせる
+///
+/// tracked なファイルへの変更は失われるが、untracked なファイルは残す
+pub fn reset_hard(repo: &Repository, tree: Option<&Hash>) -> Result<()> {
+    let tree_files = read_tree_files(repo, tree)?;
+    let index = Index::read(repo)?.unwrap_or_else(Index::new);
+
+    for entry in &index.entries {
+        if !tree_files.contains_key(&entry.file_name) {
+            remove_file(repo, &entry.file_name)?;
+        }
+    }
+
+    let mut new_entries = Vec::new();
+    for (path, (blob_type, hash)) in &tree_files {
+        let entry = if hash_file(repo, path)?.as_ref() == Some(&(*blob_type, hash.clone())) {
+            let metadata = repo.work_tree_path(path)?.symlink_metadata()?;
+            IndexEntry::with_file_metadata(Mode::Blob(*blob_type), hash.clone(), path, &metadata)?
+        } else {
+            write_file(repo, path, *blob_type, hash)?
+        };
+        new_entries.push(entry);
+    }
+
+    let mut new_index = Index::new();
+    new_index.version = index.version;
+    new_index.insert(&new_entries);
+    new_index.write(repo)?;
+
+    Ok(())
+}
+
+/// path が (symlink ではない) ディレクトリなら、その下にある index にないファイルを返す
+fn untracked_in_directory(
+    repo: &Repository,
+    path: &str,
+    tracked: &BTreeMap<String, IndexEntry>,
+) -> Result<Vec<String>> {
+    let is_directory = repo
+        .work_tree_path(path)?
+        .symlink_metadata()
+        .is_ok_and(|metadata| metadata.is_dir());
+    if !is_directory {
+        return Ok(Vec::new());
+    }
+
+    Ok(list_files(repo, path, None)?
+        .into_iter()
+        .filter(|file| !tracked.contains_key(file))
+        .collect())
+}
+
+fn entry_blob_type(entry: &IndexEntry) -> BlobType {
+    match entry.mode {
+        Mode::Blob(blob_type) => blob_type,
+        Mode::Tree => unreachable!("Unexpected tree entry in index: {:?}", entry),
+    }
+}
+
+fn format_paths(paths: &[String]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("\t{}", path))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init_repo(name: &str) -> Repository {
+        let directory =
+            std::env::temp_dir().join(format!("ssgit-worktree-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+
+        Repository::init(&directory, false, Some("main")).unwrap()
+    }
+
+    /// (パス, 中身) の組から tree を作って書き込む
+    fn write_tree(repo: &Repository, files: &[(&str, &str)]) -> Hash {
+        let mut root = TreeNode::new_root();
+        for (path, content) in files {
+            let blob = GitObject::new(ObjectType::Blob, content.as_bytes().to_vec());
+            let hash = blob.write(repo).unwrap();
+            root.push_full_path_blob(path, BlobType::Plain, hash)
+                .unwrap();
+        }
+
+        root.write_recursive(repo).unwrap()
+    }
+
+    #[test]
+    fn test_list_files_skips_nested_repository() {
+        let repo = init_repo("nested");
+        let root = repo.work_tree().unwrap();
+        std::fs::create_dir_all(root.join("sub/.git")).unwrap();
+        std::fs::write(root.join("sub/file"), "nested").unwrap();
+        std::fs::create_dir_all(root.join("dir")).unwrap();
+        std::fs::write(root.join("dir/file"), "file").unwrap();
+
+        let (files, repositories) = list_files_and_repositories(&repo, "", None).unwrap();
+        assert_eq!(files, vec!["dir/file"]);
+        assert_eq!(repositories, vec!["sub"]);
+        assert!(is_nested_repository(&repo, "sub").unwrap());
+        assert!(!is_nested_repository(&repo, "dir").unwrap());
+        assert!(!is_nested_repository(&repo, "").unwrap());
+    }
+
+    #[test]
+    fn test_reset_index_with_pathspec() {
+        let repo = init_repo("reset-index");
+        let tree = write_tree(
+            &repo,
+            &[("dir/a", "a\n"), ("dir/b", "b\n"), ("other", "o\n")],
+        );
+        checkout(&repo, None, Some(&tree)).unwrap();
+
+        // index だけを変更する
+        let changed = GitObject::new(ObjectType::Blob, b"changed\n".to_vec()).hash();
+        let mut index = Index::read(&repo).unwrap().unwrap();
+        index.remove("dir/b");
+        index.insert(&["dir/a", "dir/new", "other", "top"].map(|path| {
+            IndexEntry::with_default(Mode::Blob(BlobType::Plain), changed.clone(), path)
+        }));
+        index.write(&repo).unwrap();
+
+        let pathspec = Pathspec::parse(&repo, &["dir".to_string()]).unwrap();
+        reset_index(&repo, Some(&tree), &pathspec).unwrap();
+
+        let tree_files = read_tree_files(&repo, Some(&tree)).unwrap();
+        let index = Index::read(&repo).unwrap().unwrap();
+        let entries = index
+            .entries
+            .iter()
+            .map(|entry| (entry.file_name.as_str(), &entry.hash))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                ("dir/a", &tree_files["dir/a"].1),
+                ("dir/b", &tree_files["dir/b"].1),
+                ("other", &changed),
+                ("top", &changed),
+            ]
+        );
+
+        std::fs::remove_dir_all(repo.work_tree().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_checkout_switches_files() {
+        let repo = init_repo("switch");
+        let work_tree = repo.work_tree().unwrap().to_path_buf();
+        let current = write_tree(&repo, &[("a", "a\n"), ("dir/b", "b\n")]);
+        let target = write_tree(&repo, &[("a", "changed\n"), ("dir", "file\n")]);
+
+        checkout(&repo, None, Some(&current)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(work_tree.join("dir/b")).unwrap(),
+            "b\n"
+        );
+
+        // tracked なファイルしかないディレクトリはファイルに置き換えられる
+        checkout(&repo, Some(&current), Some(&target)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(work_tree.join("a")).unwrap(),
+            "changed\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(work_tree.join("dir")).unwrap(),
+            "file\n"
+        );
+
+        let index = Index::read(&repo).unwrap().unwrap();
+        let names = index
+            .entries
+            .iter()
+            .map(|entry| entry.file_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "dir"]);
+
+        std::fs::remove_dir_all(work_tree).unwrap();
+    }
+
+    #[test]
+    fn test_checkout_keeps_local_changes() {
+        let repo = init_repo("local-changes");
+        let work_tree = repo.work_tree().unwrap().to_path_buf();
+        let current = write_tree(&repo, &[("a", "a\n")]);
+        let target = write_tree(&repo, &[("a", "changed\n")]);
+
+        checkout(&repo, None, Some(&current)).unwrap();
+        std::fs::write(work_tree.join("a"), "local\n").unwrap();
+
+        let error = checkout(&repo, Some(&current), Some(&target)).unwrap_err();
+        assert!(error.to_string().contains("would be overwritten"));
+        assert_eq!(
+            std::fs::read_to_string(work_tree.join("a")).unwrap(),
+            "local\n"
+        );
+
+        std::fs::remove_dir_all(work_tree).unwrap();
+    }
+
+    #[test]
+    fn test_checkout_refuses_to_remove_untracked_directory() {
+        let repo = init_repo("untracked-directory");
+        let work_tree = repo.work_tree().unwrap().to_path_buf();
+        let current = write_tree(&repo, &[("a", "a\n")]);
+        let target = write_tree(&repo, &[("a", "a\n"), ("data", "data\n")]);
+
+        checkout(&repo, None, Some(&current)).unwrap();
+        std::fs::create_dir_all(work_tree.join("data/sub")).unwrap();
+        std::fs::write(work_tree.join("data/sub/notes.txt"), "notes\n").unwrap();
+
+        let error = checkout(&repo, Some(&current), Some(&target)).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("untracked working tree files would be overwritten"));
+        assert!(error.to_string().contains("data/sub/notes.txt"));
+        assert_eq!(
+            std::fs::read_to_string(work_tree.join("data/sub/notes.txt")).unwrap(),
+            "notes\n"
+        );
+
+        // 空のディレクトリなら置き換えてよい
+        std::fs::remove_dir_all(work_tree.join("data")).unwrap();
+        std::fs::create_dir_all(work_tree.join("data/empty")).unwrap();
+        checkout(&repo, Some(&current), Some(&target)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(work_tree.join("data")).unwrap(),
+            "data\n"
+        );
+
+        std::fs::remove_dir_all(work_tree).unwrap();
+    }
+}
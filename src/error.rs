@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+
+/// ライブラリとして使うときに種類で分岐できるエラー
+#[derive(Debug)]
+pub enum Error {
+    /// git directory が見つからない
+    NotARepository(String),
+    /// bare repository で作業ツリーが必要な操作をした
+    NoWorkTree,
+    /// object, ref, ファイルなどが存在しない
+    NotFound(String),
+    /// 作ろうとしたものが既に存在する
+    AlreadyExists(String),
+    /// object や pack の中身が壊れている
+    CorruptObject(String),
+    /// index の中身が壊れている
+    CorruptIndex(String),
+    /// ref の名前や中身が不正
+    InvalidRef(String),
+    /// 期待したのと違う種類の object だった
+    TypeMismatch(String),
+    /// 引数や入力の形式が不正
+    InvalidArgument(String),
+    /// 他のプロセスが lock ファイルを持っている
+    LockContention(PathBuf),
+    /// 必要な設定がない、または不正
+    Config(String),
+    Io(std::io::Error),
+    Other(anyhow::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotARepository(message)
+            | Error::NotFound(message)
+            | Error::AlreadyExists(message)
+            | Error::CorruptObject(message)
+            | Error::CorruptIndex(message)
+            | Error::InvalidRef(message)
+            | Error::TypeMismatch(message)
+            | Error::InvalidArgument(message)
+            | Error::Config(message) => write!(f, "{}", message),
+            Error::NoWorkTree => write!(f, "this operation must be run in a work tree"),
+            Error::LockContention(path) => write!(
+                f,
+                "Unable to create '{}': File exists.\n\nAnother ssgit process seems to be running in this repository.",
+                path.display()
+            ),
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Other(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => error.source(),
+            Error::Other(error) => error.chain().nth(1),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// object や index の中の数値や文字列の変換に失敗したときのエラーはそのまま包む
+macro_rules! impl_from_for_other {
+    ($($error:ty),*) => {
+        $(
+            impl From<$error> for Error {
+                fn from(error: $error) -> Self {
+                    Error::Other(error.into())
+                }
+            }
+        )*
+    };
+}
+impl_from_for_other!(
+    std::num::ParseIntError,
+    std::str::Utf8Error,
+    std::string::FromUtf8Error,
+    std::array::TryFromSliceError
+);
+
+/// 内部で anyhow を使っている箇所から戻ってきたエラーは、元が Error ならそれに戻す
+impl From<anyhow::Error> for Error {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<Error>() {
+            Ok(error) => error,
+            Err(error) => match error.downcast::<std::io::Error>() {
+                Ok(error) => Error::Io(error),
+                Err(error) => Error::Other(error),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_anyhow() {
+        let error: anyhow::Error = Error::InvalidRef("Invalid ref: x".to_string()).into();
+        assert!(
+            matches!(Error::from(error), Error::InvalidRef(message) if message == "Invalid ref: x")
+        );
+
+        let error: anyhow::Error = std::io::Error::from(std::io::ErrorKind::NotFound).into();
+        assert!(
+            matches!(Error::from(error), Error::Io(error) if error.kind() == std::io::ErrorKind::NotFound)
+        );
+
+        let error = anyhow::anyhow!("inner").context("outer");
+        let error = Error::from(error);
+        assert_eq!(error.to_string(), "outer");
+        assert_eq!(
+            std::error::Error::source(&error).unwrap().to_string(),
+            "inner"
+        );
+    }
+}
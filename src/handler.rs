@@ -1,15 +1,34 @@
 use crate::parser::{Cli, Commands};
 use anyhow::{bail, Result};
+use ssgit::entity::repository::Repository;
 
 mod add;
 mod branch;
 mod cat_file;
+mod check_ignore;
+mod check_ref_format;
 mod commit;
 mod commit_tree;
+mod config;
+mod diff;
+mod for_each_ref;
 mod hash_object;
 mod init;
+mod log;
 mod ls_files;
+mod merge;
+mod mv;
+mod pack_refs;
+mod reflog;
+mod repack;
+mod reset;
+mod rev_parse;
+mod rm;
+mod show_ref;
+mod status;
 mod switch;
+mod symbolic_ref;
+mod tag;
 mod update_index;
 mod update_ref;
 mod write_tree;
@@ -19,18 +38,57 @@ pub fn handle(cli: &Cli) -> Result<()> {
         bail!("No command provided");
     };
 
+    // -C は順に適用され、以降の相対パスはそこからの相対になる
+    for directory in &cli.directory {
+        std::env::set_current_dir(directory)?;
+    }
+    if let Some(git_dir) = &cli.git_dir {
+        std::env::set_var("GIT_DIR", git_dir);
+    }
+    if let Some(work_tree) = &cli.work_tree {
+        std::env::set_var("GIT_WORK_TREE", work_tree);
+    }
+
+    // repository が無くても動くコマンド
+    match commands {
+        Commands::HashObject(args) => return hash_object::handle(args),
+        Commands::Init(args) => return init::handle(args),
+        Commands::Config(args) => return config::handle(args),
+        Commands::CheckRefFormat(args) => return check_ref_format::handle(args),
+        _ => {}
+    }
+
+    let repo = Repository::discover()?;
     match commands {
-        Commands::CatFile(args) => cat_file::handle(args),
-        Commands::HashObject(args) => hash_object::handle(args),
-        Commands::UpdateIndex(args) => update_index::handle(args),
-        Commands::WriteTree(args) => write_tree::handle(args),
-        Commands::LsFiles(args) => ls_files::handle(args),
-        Commands::CommitTree(args) => commit_tree::handle(args),
-        Commands::UpdateRef(args) => update_ref::handle(args),
-        Commands::Init(args) => init::handle(args),
-        Commands::Add(args) => add::handle(args),
-        Commands::Commit(args) => commit::handle(args),
-        Commands::Switch(args) => switch::handle(args),
-        Commands::Branch(args) => branch::handle(args),
+        Commands::CatFile(args) => cat_file::handle(&repo, args),
+        Commands::UpdateIndex(args) => update_index::handle(&repo, args),
+        Commands::WriteTree(args) => write_tree::handle(&repo, args),
+        Commands::LsFiles(args) => ls_files::handle(&repo, args),
+        Commands::CommitTree(args) => commit_tree::handle(&repo, args),
+        Commands::UpdateRef(args) => update_ref::handle(&repo, args),
+        Commands::Add(args) => add::handle(&repo, args),
+        Commands::Commit(args) => commit::handle(&repo, args),
+        Commands::Switch(args) => switch::handle(&repo, args),
+        Commands::Branch(args) => branch::handle(&repo, args),
+        Commands::Repack(args) => repack::handle(&repo, args),
+        Commands::Tag(args) => tag::handle(&repo, args),
+        Commands::Status(args) => status::handle(&repo, args),
+        Commands::Log(args) => log::handle(&repo, args),
+        Commands::RevParse(args) => rev_parse::handle(&repo, args),
+        Commands::Diff(args) => diff::handle(&repo, args),
+        Commands::Merge(args) => merge::handle(&repo, args),
+        Commands::CheckIgnore(args) => check_ignore::handle(&repo, args),
+        Commands::Reset(args) => reset::handle(&repo, args),
+        Commands::Rm(args) => rm::handle(&repo, args),
+        Commands::Mv(args) => mv::handle(&repo, args),
+        Commands::Reflog(args) => reflog::handle(&repo, args),
+        Commands::PackRefs(args) => pack_refs::handle(&repo, args),
+        Commands::ForEachRef(args) => for_each_ref::handle(&repo, args),
+        Commands::ShowRef(args) => show_ref::handle(&repo, args),
+        Commands::SymbolicRef(args) => symbolic_ref::handle(&repo, args),
+        Commands::HashObject(_)
+        | Commands::Init(_)
+        | Commands::Config(_)
+        | Commands::CheckRefFormat(_) => unreachable!(),
     }
 }
@@ -1,58 +1,103 @@
-use std::path::Path;
-
-use crate::{
-    entity::{
-        index::{Index, IndexEntry},
-        object::{mode::Mode, GitObject, ObjectType},
-    },
-    parser::AddArgs,
-};
-use anyhow::{bail, Result};
+use std::collections::BTreeSet;
 
-pub fn handle(args: &AddArgs) -> Result<()> {
-    dbg!(args);
+use crate::parser::AddArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{
+    ignore::Ignore,
+    index::{Index, IndexEntry},
+    object::{database::ObjectDatabase, mode::Mode, GitObject, ObjectType},
+    pathspec::Pathspec,
+    repository::Repository,
+    worktree,
+};
 
-    if args.files.is_empty() {
+pub fn handle(repo: &Repository, args: &AddArgs) -> Result<()> {
+    if args.files.is_empty() && !args.all && !args.update {
         println!("Nothing specified, nothing added.");
         return Ok(());
     }
 
-    let mut entries = Vec::with_capacity(args.files.len());
-    for file_name in &args.files {
-        let file_path = Path::new(file_name);
-        if !file_path.exists() {
-            bail!("File {} does not exist", file_name);
-        }
+    // -A, -u でパスを省略したら作業ツリー全体が対象
+    let pathspec = if args.files.is_empty() {
+        Pathspec::parse(repo, &[":/".to_string()])?
+    } else {
+        Pathspec::parse(repo, &args.files)?
+    };
 
-        let metadata = file_path.metadata()?;
-        if metadata.is_dir() {
-            bail!("Cannot add directory {}", file_name);
-        }
+    let ignore = Ignore::read(repo)?;
+    let mut index = Index::read(repo)?.unwrap_or_else(Index::new);
+    let tracked = index
+        .entries
+        .iter()
+        .map(|entry| entry.file_name.clone())
+        .collect::<BTreeSet<_>>();
 
-        let mode = Mode::from_file_metadata(&metadata)?;
+    // tracked なファイルは変更されていれば追加し直し、消えていれば index から消す
+    let mut files = tracked
+        .iter()
+        .filter(|name| pathspec.matches(name))
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    if !args.update {
+        let walk_ignore = (!args.force).then_some(&ignore);
+        let (walked, repositories) = worktree::list_files_and_repositories(repo, "", walk_ignore)?;
+        files.extend(walked.into_iter().filter(|name| pathspec.matches(name)));
+        // gitlink は扱えないので、入れ子の repository は中身ごと追加しない
+        for repository in repositories.iter().filter(|name| pathspec.matches(name)) {
+            eprintln!("warning: skipping embedded git repository: {}", repository);
+        }
+    }
 
-        let content = std::fs::read(file_name)?;
-        let object = GitObject::new(ObjectType::Blob, content);
-        let hash = object.hash();
+    // 明示的に指定された無視されるパスは追加せずに報告する
+    let mut ignored = Vec::new();
+    for item in pathspec.items.iter().filter(|item| !item.exclude) {
+        if files.iter().any(|name| item.matches(name)) {
+            continue;
+        }
 
-        if !hash.get_object_path().exists() {
-            object.write()?;
+        let metadata = match repo.work_tree_path(&item.path)?.symlink_metadata() {
+            Ok(metadata) if !args.update => metadata,
+            _ => bail!("pathspec '{}' did not match any files", item.original),
+        };
+        if !args.force && ignore.is_ignored(&item.path, metadata.is_dir())? {
+            ignored.push(item.original.clone());
+        } else if !metadata.is_dir() {
+            bail!("pathspec '{}' did not match any files", item.original);
         }
+    }
 
-        let entry = IndexEntry::with_file_metadata(mode, hash, file_name, &metadata)?;
+    let mut entries = Vec::with_capacity(files.len());
+    for file in &files {
+        let file_path = repo.work_tree_path(file)?;
+        let metadata = match file_path.symlink_metadata() {
+            Ok(metadata) if !metadata.is_dir() => metadata,
+            _ => {
+                index.remove(file);
+                continue;
+            }
+        };
+
+        let mode = Mode::from_file_metadata(&metadata)?;
+        let Mode::Blob(blob_type) = mode else {
+            unreachable!("file mode must be blob");
+        };
+        let content = worktree::read_file(&file_path, blob_type)?;
+        let hash = repo.write(&GitObject::new(ObjectType::Blob, content))?;
 
+        let entry = IndexEntry::with_file_metadata(mode, hash, file, &metadata)?;
         entries.push(entry);
     }
 
-    let mut index = if let Some(index) = Index::read()? {
-        index
-    } else {
-        Index::new()
-    };
-
     index.insert(&entries);
 
-    index.write()?;
+    index.write(repo)?;
+
+    if !ignored.is_empty() {
+        bail!(
+            "The following paths are ignored by one of your .gitignore files:\n{}\nhint: Use -f if you really want to add them.",
+            ignored.join("\n")
+        );
+    }
 
     Ok(())
 }
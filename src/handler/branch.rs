@@ -1,24 +1,20 @@
-use crate::{
-    entity::{head::Head, refs::Ref},
-    parser::BranchArgs,
-};
+use crate::parser::BranchArgs;
 use anyhow::{bail, Result};
+use ssgit::entity::{head::Head, refs::Ref, repository::Repository};
 
-pub fn handle(args: &BranchArgs) -> Result<()> {
-    dbg!(args);
-
+pub fn handle(repo: &Repository, args: &BranchArgs) -> Result<()> {
     match (&args.options.create, &args.options.delete) {
         (None, None) => {
             let branches = {
-                let mut branches = Ref::branch_names()?;
+                let mut branches = Ref::branch_names(repo)?;
                 branches.sort();
                 branches
             };
-            let head = Head::read()?;
+            let head = Head::read(repo)?;
             let head_branch = match head {
                 Head::Ref(ref_) => match ref_ {
                     Ref::Branch(branch) => Some(branch),
-                    Ref::Tag(_) => None,
+                    _ => None,
                 },
                 Head::Detached(_) => None,
             };
@@ -38,31 +34,31 @@ pub fn handle(args: &BranchArgs) -> Result<()> {
         }
         (Some(branch_name), None) => {
             let ref_ = Ref::Branch(branch_name.clone());
-            if ref_.to_path().exists() {
+            if ref_.check_format().is_err() {
+                bail!("'{}' is not a valid branch name", branch_name);
+            }
+            if ref_.exists(repo)? {
                 bail!("Branch {} already exists", branch_name);
             }
 
-            let head = Head::read()?;
-            let hash = match head {
-                Head::Ref(ref_) => match ref_ {
-                    Ref::Branch(_) => ref_.read_hash()?,
-                    Ref::Tag(_) => bail!("Tag is not supported"),
-                },
-                Head::Detached(_) => None,
+            let head = Head::read(repo)?;
+            let (hash, head_name) = match head {
+                Head::Ref(ref_) => (ref_.read_commit_hash(repo)?, ref_.short_name()),
+                Head::Detached(_) => (None, "HEAD".to_string()),
             };
 
             let Some(hash) = hash else {
                 bail!("No commit on branch \"{}\"", branch_name);
             };
 
-            ref_.write_hash(&hash)?;
-        },
+            ref_.update(repo, &hash, &format!("branch: Created from {}", head_name))?;
+        }
         (None, Some(branch_name)) => {
-            let head = Head::read()?;
+            let head = Head::read(repo)?;
             let head_branch = match head {
                 Head::Ref(ref_) => match ref_ {
                     Ref::Branch(branch) => Some(branch),
-                    Ref::Tag(_) => None,
+                    _ => None,
                 },
                 Head::Detached(_) => None,
             };
@@ -72,13 +68,12 @@ pub fn handle(args: &BranchArgs) -> Result<()> {
             }
 
             let ref_ = Ref::Branch(branch_name.clone());
-            let path = ref_.to_path();
-            if !path.exists() {
+            if !ref_.exists(repo)? {
                 bail!("Branch {} does not exist", branch_name);
             }
 
-            std::fs::remove_file(path)?;
-        },
+            ref_.delete(repo)?;
+        }
         _ => {
             unreachable!()
         }
@@ -1,26 +1,54 @@
-use std::str::FromStr;
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufWriter, Write},
+    str::FromStr,
+};
 
-use crate::entity::object::ObjectType;
-use crate::entity::object::{hash::Hash, GitObject};
 use crate::parser::CatFileArgs;
 use anyhow::{bail, Result};
+use ssgit::entity::object::database::{CompositeStore, ObjectDatabase};
+use ssgit::entity::object::hash::Hash;
+use ssgit::entity::object::mode::{BlobType, Mode};
+use ssgit::entity::object::GitObject;
+use ssgit::entity::object::ObjectType;
+use ssgit::entity::repository::Repository;
+use ssgit::entity::revision;
 
-pub fn handle(args: &CatFileArgs) -> Result<()> {
-    dbg!(args);
+/// `git` と同じく symlink を辿る回数の上限
+const MAX_SYMLINK_DEPTH: usize = 40;
 
-    let hash = Hash::from_str(&args.hash)?;
+pub fn handle(repo: &Repository, args: &CatFileArgs) -> Result<()> {
+    let options = &args.options;
+    if let Some(format) = &options.batch {
+        return batch(repo, args, format, BatchMode::Contents);
+    }
+    if let Some(format) = &options.batch_check {
+        return batch(repo, args, format, BatchMode::Check);
+    }
+    if let Some(format) = &options.batch_command {
+        return batch(repo, args, format, BatchMode::Command);
+    }
+    if args.batch_all_objects {
+        bail!("'--batch-all-objects' requires a batch mode");
+    }
 
-    let path = hash.get_object_path();
+    let name = args
+        .hash
+        .as_deref()
+        .expect("object is required without batch modes");
 
     if args.options.exists {
-        if path.exists() {
+        if revision::resolve(repo, name)
+            .is_ok_and(|hash| GitObject::exists(repo, &hash).unwrap_or(false))
+        {
             return Ok(());
         } else {
             bail!("")
         }
     }
 
-    let objects = GitObject::read(&hash)?;
+    let hash = revision::resolve(repo, name)?;
+    let objects = GitObject::read(repo, &hash)?;
 
     match (args.options.pretty, args.options.type_, args.options.size) {
         (true, _, _) => match objects.type_ {
@@ -35,6 +63,9 @@ pub fn handle(args: &CatFileArgs) -> Result<()> {
             ObjectType::Commit => {
                 println!("{}", objects.parse_commit_body()?);
             }
+            ObjectType::Tag => {
+                print!("{}", objects.parse_tag_body()?);
+            }
         },
         (_, true, _) => {
             println!("{}", objects.type_);
@@ -45,7 +76,372 @@ pub fn handle(args: &CatFileArgs) -> Result<()> {
         _ => unreachable!(),
     }
 
-    dbg!(objects.hash());
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BatchMode {
+    /// `--batch`: 情報の後に中身も出力する
+    Contents,
+    /// `--batch-check`: 情報だけを出力する
+    Check,
+    /// `--batch-command`: `contents <object>` か `info <object>` を 1 行ずつ読む
+    Command,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FormatItem {
+    Literal(String),
+    ObjectName,
+    ObjectType,
+    ObjectSize,
+    /// 入力の行で object の名前の後に続く部分
+    Rest,
+}
+
+/// object の名前を解決した結果
+#[derive(Debug, PartialEq, Eq)]
+enum Lookup {
+    Found(Hash, GitObject),
+    Missing,
+    Ambiguous,
+    /// `--follow-symlinks` で辿った先が見つからない
+    Dangling,
+    Loop,
+    /// 途中のパスが tree ではない
+    NotDir,
+    /// tree の外を指している (外に出た部分を持つ)
+    OutOfTree(String),
+}
+
+/// 標準入力から object の名前を 1 行ずつ読んで、`<oid> <type> <size>` (と中身) を出力する
+fn batch(repo: &Repository, args: &CatFileArgs, format: &str, mode: BatchMode) -> Result<()> {
+    let format = parse_format(format)?;
+    let uses_rest = format.contains(&FormatItem::Rest);
+    let db = &*repo.objects()?;
+    let mut out = BufWriter::new(std::io::stdout().lock());
+
+    if args.batch_all_objects {
+        if mode == BatchMode::Command {
+            bail!("'--batch-all-objects' requires a batch mode");
+        }
+        let mut hashes = db.hashes()?;
+        hashes.sort_by_key(|hash| hash.to_string());
+        hashes.dedup();
+
+        for hash in hashes {
+            let object = GitObject::read(db, &hash)?;
+            let name = hash.to_string();
+            let lookup = Lookup::Found(hash, object);
+            write_record(&mut out, &format, &name, "", &lookup, mode)?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+
+        let (mode, input) = match mode {
+            BatchMode::Command => match line.split_once(' ') {
+                Some(("contents", input)) => (BatchMode::Contents, input),
+                Some(("info", input)) => (BatchMode::Check, input),
+                None if line == "flush" => {
+                    if !args.buffer {
+                        bail!("flush is only for --buffer mode");
+                    }
+                    out.flush()?;
+                    continue;
+                }
+                _ => bail!("unknown command: '{}'", line),
+            },
+            mode => (mode, line.as_str()),
+        };
+
+        // %(rest) を使うときだけ最初の空白で名前と残りに分ける
+        let (name, rest) = match input.split_once(char::is_whitespace) {
+            Some((name, rest)) if uses_rest => (name, rest.trim_start()),
+            _ => (input, ""),
+        };
+
+        let lookup = if args.follow_symlinks {
+            follow_symlinks(repo, db, name)?
+        } else {
+            lookup(repo, db, name)?
+        };
+        write_record(&mut out, &format, name, rest, &lookup, mode)?;
+        if !args.buffer {
+            out.flush()?;
+        }
+    }
+    out.flush()?;
 
     Ok(())
 }
+
+fn parse_format(format: &str) -> Result<Vec<FormatItem>> {
+    let mut items = Vec::new();
+    let mut rest = format;
+
+    while let Some(start) = rest.find("%(") {
+        if start > 0 {
+            items.push(FormatItem::Literal(rest[..start].to_string()));
+        }
+        let after = &rest[start + 2..];
+        let Some(end) = after.find(')') else {
+            bail!("format element '{}' does not end in ')'", rest);
+        };
+        items.push(match &after[..end] {
+            "objectname" => FormatItem::ObjectName,
+            "objecttype" => FormatItem::ObjectType,
+            "objectsize" => FormatItem::ObjectSize,
+            "rest" => FormatItem::Rest,
+            atom => bail!("unknown format element: {}", atom),
+        });
+        rest = &after[end + 1..];
+    }
+    if !rest.is_empty() {
+        items.push(FormatItem::Literal(rest.to_string()));
+    }
+
+    Ok(items)
+}
+
+fn write_record(
+    out: &mut impl Write,
+    format: &[FormatItem],
+    name: &str,
+    rest: &str,
+    lookup: &Lookup,
+    mode: BatchMode,
+) -> Result<()> {
+    let (kind, message) = match lookup {
+        Lookup::Found(hash, object) => {
+            for item in format {
+                match item {
+                    FormatItem::Literal(literal) => write!(out, "{}", literal)?,
+                    FormatItem::ObjectName => write!(out, "{}", hash)?,
+                    FormatItem::ObjectType => write!(out, "{}", object.type_)?,
+                    FormatItem::ObjectSize => write!(out, "{}", object.size())?,
+                    FormatItem::Rest => write!(out, "{}", rest)?,
+                }
+            }
+            writeln!(out)?;
+            if mode == BatchMode::Contents {
+                out.write_all(&object.body)?;
+                writeln!(out)?;
+            }
+            return Ok(());
+        }
+        Lookup::Missing => {
+            writeln!(out, "{} missing", name)?;
+            return Ok(());
+        }
+        Lookup::Ambiguous => {
+            writeln!(out, "{} ambiguous", name)?;
+            return Ok(());
+        }
+        Lookup::Dangling => ("dangling", name),
+        Lookup::Loop => ("loop", name),
+        Lookup::NotDir => ("notdir", name),
+        Lookup::OutOfTree(target) => ("symlink", target.as_str()),
+    };
+
+    writeln!(out, "{} {}\n{}", kind, message.len(), message)?;
+
+    Ok(())
+}
+
+fn lookup(repo: &Repository, db: &CompositeStore, name: &str) -> Result<Lookup> {
+    // revision としては空文字列も HEAD になるが、ここでは名前がないものとして扱う
+    if name.is_empty() {
+        return Ok(Lookup::Missing);
+    }
+
+    let is_hex = !name.is_empty() && name.chars().all(|c| c.is_ascii_hexdigit());
+    let hash = if is_hex && name.len() == 40 {
+        Hash::from_str(&name.to_ascii_lowercase())?
+    } else {
+        if is_hex && name.len() >= 4 && db.find_by_prefix(&name.to_ascii_lowercase())?.len() > 1 {
+            return Ok(Lookup::Ambiguous);
+        }
+        match revision::resolve(repo, name) {
+            Ok(hash) => hash,
+            Err(_) => return Ok(Lookup::Missing),
+        }
+    };
+
+    Ok(match db.read(&hash)? {
+        Some(object) => Lookup::Found(hash, object),
+        None => Lookup::Missing,
+    })
+}
+
+/// `<tree-ish>:<path>` のパスの途中や最後にある symlink を tree の中で辿る
+fn follow_symlinks(repo: &Repository, db: &CompositeStore, name: &str) -> Result<Lookup> {
+    let Some((revision, path)) = name
+        .split_once(':')
+        .filter(|(revision, _)| !revision.is_empty())
+    else {
+        return lookup(repo, db, name);
+    };
+    let Ok(hash) = revision::resolve(repo, revision) else {
+        return Ok(Lookup::Missing);
+    };
+    let Ok((root, _)) = GitObject::peel(db, &hash, ObjectType::Tree) else {
+        return Ok(Lookup::Missing);
+    };
+
+    follow_tree_path(db, root, path)
+}
+
+/// root の tree から path を辿る。symlink は tree の中のパスとして解決する
+fn follow_tree_path(db: &dyn ObjectDatabase, root: Hash, path: &str) -> Result<Lookup> {
+    // 入ったディレクトリの tree を順に積む
+    let mut directories: Vec<Hash> = Vec::new();
+    let mut components = path
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .map(str::to_string)
+        .collect::<VecDeque<_>>();
+    let mut depth = 0;
+
+    while let Some(component) = components.pop_front() {
+        match component.as_str() {
+            "." => continue,
+            ".." => {
+                if directories.pop().is_none() {
+                    components.push_front(component);
+                    return Ok(Lookup::OutOfTree(
+                        components.into_iter().collect::<Vec<_>>().join("/"),
+                    ));
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let tree = directories.last().unwrap_or(&root);
+        let entry = GitObject::read(db, tree)?
+            .parse_tree_body()?
+            .into_iter()
+            .find(|entry| entry.name == component);
+        let Some(entry) = entry else {
+            return Ok(if depth == 0 {
+                Lookup::Missing
+            } else {
+                Lookup::Dangling
+            });
+        };
+
+        match entry.file_type {
+            Mode::Blob(BlobType::Symlink) => {
+                depth += 1;
+                if depth > MAX_SYMLINK_DEPTH {
+                    return Ok(Lookup::Loop);
+                }
+                let target = String::from_utf8(GitObject::read(db, &entry.hash)?.body)?;
+                if target.starts_with('/') {
+                    return Ok(Lookup::OutOfTree(target));
+                }
+                for component in target.split('/').filter(|c| !c.is_empty()).rev() {
+                    components.push_front(component.to_string());
+                }
+            }
+            Mode::Tree => directories.push(entry.hash),
+            _ if !components.is_empty() => return Ok(Lookup::NotDir),
+            _ => {
+                let object = GitObject::read(db, &entry.hash)?;
+                return Ok(Lookup::Found(entry.hash, object));
+            }
+        }
+    }
+
+    let hash = directories.pop().unwrap_or(root);
+    let object = GitObject::read(db, &hash)?;
+    Ok(Lookup::Found(hash, object))
+}
+
+#[cfg(test)]
+mod tests {
+    use ssgit::entity::{object::database::MemoryStore, tree::TreeNode};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            parse_format("%(objectname) %(objecttype) %(objectsize)").unwrap(),
+            vec![
+                FormatItem::ObjectName,
+                FormatItem::Literal(" ".to_string()),
+                FormatItem::ObjectType,
+                FormatItem::Literal(" ".to_string()),
+                FormatItem::ObjectSize,
+            ]
+        );
+        assert_eq!(
+            parse_format("<%(rest)>%").unwrap(),
+            vec![
+                FormatItem::Literal("<".to_string()),
+                FormatItem::Rest,
+                FormatItem::Literal(">%".to_string()),
+            ]
+        );
+        assert_eq!(parse_format("").unwrap(), vec![]);
+        assert_eq!(
+            parse_format("%(objectname").unwrap_err().to_string(),
+            "format element '%(objectname' does not end in ')'"
+        );
+        assert_eq!(
+            parse_format("%(deltabase)").unwrap_err().to_string(),
+            "unknown format element: deltabase"
+        );
+    }
+
+    #[test]
+    fn test_follow_tree_path() {
+        let db = MemoryStore::new();
+        let mut root = TreeNode::new_root();
+        let mut blobs = Vec::new();
+        for (path, blob_type, content) in [
+            ("a", BlobType::Plain, "hello\n"),
+            ("dir/b", BlobType::Plain, "b\n"),
+            ("dir/up", BlobType::Symlink, "../a"),
+            ("link", BlobType::Symlink, "dir/b"),
+            ("dangling", BlobType::Symlink, "nothing"),
+            ("loop1", BlobType::Symlink, "loop2"),
+            ("loop2", BlobType::Symlink, "loop1"),
+            ("file-link", BlobType::Symlink, "a"),
+            ("out", BlobType::Symlink, "dir/../../outside"),
+            ("absolute", BlobType::Symlink, "/etc/passwd"),
+        ] {
+            let blob = GitObject::new(ObjectType::Blob, content.as_bytes().to_vec());
+            let hash = blob.write(&db).unwrap();
+            root.push_full_path_blob(path, blob_type, hash).unwrap();
+            blobs.push((path, blob));
+        }
+        let root = root.write_recursive(&db).unwrap();
+        let follow = |path: &str| follow_tree_path(&db, root.clone(), path).unwrap();
+        let found = |path: &str| {
+            let (_, blob) = blobs.iter().find(|(name, _)| *name == path).unwrap();
+            Lookup::Found(blob.hash(), blob.clone())
+        };
+
+        assert_eq!(follow("link"), found("dir/b"));
+        assert_eq!(follow("dir/up"), found("a"));
+        assert_eq!(follow("./dir/../dir/up"), found("a"));
+        assert_eq!(follow("missing"), Lookup::Missing);
+        assert_eq!(follow("dangling"), Lookup::Dangling);
+        assert_eq!(follow("loop1"), Lookup::Loop);
+        assert_eq!(follow("file-link/x"), Lookup::NotDir);
+        assert_eq!(follow("a/x"), Lookup::NotDir);
+        assert_eq!(follow("out"), Lookup::OutOfTree("../outside".to_string()));
+        assert_eq!(follow("../x"), Lookup::OutOfTree("../x".to_string()));
+        assert_eq!(
+            follow("absolute"),
+            Lookup::OutOfTree("/etc/passwd".to_string())
+        );
+        assert!(matches!(follow(""), Lookup::Found(hash, _) if hash == root));
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+
+use crate::parser::CheckIgnoreArgs;
+use anyhow::Result;
+use ssgit::entity::{ignore::Ignore, index::Index, repository::Repository};
+
+pub fn handle(repo: &Repository, args: &CheckIgnoreArgs) -> Result<()> {
+    let ignore = Ignore::read(repo)?;
+    let index = if args.no_index {
+        None
+    } else {
+        Index::read(repo)?
+    };
+    // index に載っているパスは無視されない
+    let tracked = index
+        .iter()
+        .flat_map(|index| index.entries.iter())
+        .map(|entry| entry.file_name.as_str())
+        .collect::<HashSet<_>>();
+
+    let mut matched = false;
+    for path in &args.paths {
+        let name = repo.relative_path(path)?;
+        let is_directory = path.ends_with('/') || repo.work_tree_path(&name)?.is_dir();
+
+        let pattern = if tracked.contains(name.as_str()) {
+            None
+        } else {
+            ignore.check(&name, is_directory)?
+        };
+        // -v では否定パターンに一致したものも表示する
+        let pattern = pattern.filter(|pattern| args.verbose || !pattern.negated);
+        matched |= pattern.is_some();
+
+        if args.quiet {
+            continue;
+        }
+        match pattern {
+            Some(pattern) if args.verbose => println!(
+                "{}:{}:{}\t{}",
+                pattern.source, pattern.line_number, pattern.pattern, path
+            ),
+            Some(_) => println!("{}", path),
+            None if args.non_matching => println!("::\t{}", path),
+            None => {}
+        }
+    }
+
+    if !matched {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
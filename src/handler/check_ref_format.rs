@@ -0,0 +1,45 @@
+use crate::parser::CheckRefFormatArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::refs::{check_ref_format, Ref};
+
+/// 名前が正しければ何も表示せず、不正なら終了コード 1 で終わる
+pub fn handle(args: &CheckRefFormatArgs) -> Result<()> {
+    let name = args.refname.as_str();
+
+    if args.branch {
+        if name.starts_with('-')
+            || name == "HEAD"
+            || Ref::Branch(name.to_string()).check_format().is_err()
+        {
+            bail!("'{}' is not a valid branch name", name);
+        }
+        println!("{}", name);
+        return Ok(());
+    }
+
+    let name = if args.normalize {
+        normalize(name)
+    } else {
+        name.to_string()
+    };
+    if check_ref_format(&name, args.allow_onelevel, args.refspec_pattern).is_err() {
+        std::process::exit(1);
+    }
+    if args.normalize {
+        println!("{}", name);
+    }
+
+    Ok(())
+}
+
+/// 先頭の `/` を除き、連続する `/` を 1 つにまとめる
+fn normalize(name: &str) -> String {
+    let mut result = String::new();
+    for c in name.trim_start_matches('/').chars() {
+        if !(c == '/' && result.ends_with('/')) {
+            result.push(c);
+        }
+    }
+
+    result
+}
@@ -1,42 +1,54 @@
-use std::str::FromStr;
-
-use crate::{
-    entity::{
-        head::Head,
-        index::Index,
-        object::{hash::Hash, GitObject, User},
-        refs::Ref,
-        tree::TreeNode,
-    },
-    parser::CommitArgs,
-};
+use crate::parser::CommitArgs;
 use anyhow::{bail, Result};
+use ssgit::entity::{
+    head::Head,
+    index::Index,
+    merge::MergeState,
+    object::{GitObject, User},
+    repository::Repository,
+    tree::TreeNode,
+};
 
-pub fn handle(args: &CommitArgs) -> Result<()> {
-    dbg!(args);
+pub fn handle(repo: &Repository, args: &CommitArgs) -> Result<()> {
+    let merge_state = MergeState::read(repo)?;
 
-    let message = if args.message.is_empty() {
-        std::io::stdin()
+    let message = if let (true, Some(merge_state)) = (args.message.is_empty(), &merge_state) {
+        // マージ中は MERGE_MSG からコメント行を除いたものを使う
+        merge_state
+            .message
             .lines()
-            .map(|line| line.map_err(|e| e.into()))
-            .collect::<Result<String>>()?
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string()
+    } else if args.message.is_empty() {
+        std::io::read_to_string(std::io::stdin())?
     } else {
         args.message.join("\n\n")
     };
+    let message = message.trim_end();
     if message.is_empty() && !args.allow_empty_message {
         bail!("Aborting commit due to empty commit message.");
     }
+    // git と同じく末尾の空白を落として改行で終える
+    let message = if message.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", message)
+    };
 
-    let index = Index::read()?;
-    let node = TreeNode::from(index.unwrap());
-    let tree_hash = node.write_recursive()?;
-
-    let now = chrono::Local::now();
-    let now = now.with_timezone(now.offset());
+    let index = Index::read(repo)?.unwrap_or_else(Index::new);
+    if !index.conflicted_paths().is_empty() {
+        bail!("Committing is not possible because you have unmerged files.");
+    }
+    let node = TreeNode::try_from(index)?;
+    let tree_hash = node.write_recursive(repo)?;
 
-    let committer = User::read_from_git(now)?;
+    let author = User::author(repo)?;
+    let committer = User::committer(repo)?;
 
-    let head = Head::read()?;
+    let head = Head::read(repo)?;
     if let Head::Detached(_) = head {
         println!("Detached HEAD is not supported");
         return Ok(());
@@ -44,32 +56,42 @@ pub fn handle(args: &CommitArgs) -> Result<()> {
     let Head::Ref(ref_) = head else {
         unreachable!();
     };
-    if let Ref::Tag(_) = ref_ {
-        println!("Tag is not supported");
-        return Ok(());
-    }
 
     let parent = ref_
-        .read_hash()?
-        .map(|hash| Hash::from_str(&hash))
-        .transpose()?;
+        .read_commit_hash(repo)?
+        .into_iter()
+        .chain(
+            merge_state
+                .as_ref()
+                .map(|merge_state| merge_state.head.clone()),
+        )
+        .collect::<Vec<_>>();
+    let reflog_message = format!(
+        "commit{}: {}",
+        match parent.len() {
+            0 => " (initial)",
+            1 => "",
+            _ => " (merge)",
+        },
+        message.lines().next().unwrap_or_default()
+    );
 
-    let commit = crate::entity::object::Commit {
+    let commit = ssgit::entity::object::Commit {
         tree: tree_hash,
-        parent: parent.into_iter().collect(),
-        author: committer.clone(),
+        parent,
+        author,
         committer,
         rest_of_header: "".to_string(),
         message,
     };
 
     let git_object = GitObject::from_commit(&commit);
-    let path = git_object.hash().get_object_path();
-    if !path.exists() {
-        git_object.write()?;
+    if !GitObject::exists(repo, &git_object.hash())? {
+        git_object.write(repo)?;
     }
 
-    ref_.write_hash(&git_object.hash().to_string())?;
+    ref_.update(repo, &git_object.hash(), &reflog_message)?;
+    MergeState::remove(repo)?;
 
     Ok(())
 }
@@ -1,37 +1,36 @@
-use std::str::FromStr;
-
 use anyhow::Result;
 
-use crate::{
-    entity::object::{hash::Hash, GitObject, User},
-    parser::CommitTreeArgs,
+use crate::parser::CommitTreeArgs;
+use ssgit::entity::{
+    object::{GitObject, ObjectType, User},
+    repository::Repository,
+    revision,
 };
 
-pub fn handle(args: &CommitTreeArgs) -> Result<()> {
+pub fn handle(repo: &Repository, args: &CommitTreeArgs) -> Result<()> {
     let message = if args.message.is_empty() {
-        std::io::stdin()
-            .lines()
-            .map(|line| line.map_err(|e| e.into()))
-            .collect::<Result<String>>()?
+        std::io::read_to_string(std::io::stdin())?
     } else {
-        args.message.join("\n\n")
+        args.message.join("\n\n") + "\n"
     };
 
-    let now = chrono::Local::now();
-    let now = now.with_timezone(now.offset());
-
-    let committer = User::read_from_git(now)?;
+    let author = User::author(repo)?;
+    let committer = User::committer(repo)?;
 
-    let tree_hash = Hash::from_str(&args.tree)?;
+    let (tree_hash, _) = GitObject::peel(
+        repo,
+        &revision::resolve(repo, &args.tree)?,
+        ObjectType::Tree,
+    )?;
 
-    let commit = crate::entity::object::Commit {
+    let commit = ssgit::entity::object::Commit {
         tree: tree_hash,
         parent: args
             .parent
             .iter()
-            .map(|s| Hash::from_str(s))
+            .map(|s| revision::resolve_commit(repo, s))
             .collect::<Result<Vec<_>>>()?,
-        author: committer.clone(),
+        author,
         committer,
         rest_of_header: "".to_string(),
         message,
@@ -41,15 +40,12 @@ pub fn handle(args: &CommitTreeArgs) -> Result<()> {
 
     let hash = git_object.hash();
 
-    let path = hash.get_object_path();
-    if path.exists() {
-        let existing_content = std::fs::read(&path)?;
-        dbg!(existing_content == git_object.to_raw()?);
+    if GitObject::exists(repo, &hash)? {
         println!("{}", hash);
         return Ok(());
     }
 
-    git_object.write()?;
+    git_object.write(repo)?;
 
     println!("{}", hash);
 
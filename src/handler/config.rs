@@ -0,0 +1,76 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+use crate::parser::ConfigArgs;
+use ssgit::entity::{
+    config::{self, Config, ConfigName, ConfigScope},
+    repository::Repository,
+};
+
+pub fn handle(args: &ConfigArgs) -> Result<()> {
+    let repo = Repository::discover().ok();
+    let scope = if args.global {
+        Some(ConfigScope::Global)
+    } else if args.system {
+        Some(ConfigScope::System)
+    } else if args.local {
+        Some(ConfigScope::Local)
+    } else {
+        None
+    };
+
+    let read = || match (&args.file, scope) {
+        (Some(file), _) => Config::read_file(repo.as_ref(), Path::new(file)),
+        (None, Some(scope)) => Config::read_scope(repo.as_ref(), scope),
+        (None, None) => Config::read(repo.as_ref()),
+    };
+
+    if args.list {
+        for entry in read()?.entries {
+            match entry.value {
+                Some(value) => println!("{}={}", entry.name, value),
+                None => println!("{}", entry.name),
+            }
+        }
+        return Ok(());
+    }
+
+    let Some(name) = &args.name else {
+        bail!("no action specified");
+    };
+    let name = ConfigName::parse(name)?;
+
+    if args.get || args.get_all || (args.value.is_none() && !args.unset) {
+        let config = read()?;
+        let entries = config.get_all(&name);
+        if entries.is_empty() {
+            bail!("key '{}' not found", name);
+        }
+
+        // --get は最後の値だけを出す
+        let skip = if args.get_all { 0 } else { entries.len() - 1 };
+        for entry in entries.into_iter().skip(skip) {
+            println!("{}", entry.value.as_deref().unwrap_or(""));
+        }
+        return Ok(());
+    }
+
+    let path = match (&args.file, scope) {
+        (Some(file), _) => PathBuf::from(file),
+        (None, scope) => {
+            let scope = scope.unwrap_or(ConfigScope::Local);
+            if scope == ConfigScope::Local && repo.is_none() {
+                bail!("not in a git directory");
+            }
+            scope.write_path(repo.as_ref())?
+        }
+    };
+
+    let value = if args.unset {
+        None
+    } else {
+        args.value.as_deref()
+    };
+    config::write_value(&path, &name, value)
+}
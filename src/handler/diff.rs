@@ -0,0 +1,506 @@
+use std::io::{BufWriter, Write};
+
+use crate::parser::DiffArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{
+    diff::{self, Algorithm, Edit, FileChange},
+    head::Head,
+    index::Index,
+    object::{
+        hash::Hash,
+        mode::{BlobType, Mode},
+        GitObject, ObjectType,
+    },
+    pathspec::Pathspec,
+    repository::Repository,
+    rev_walk, revision,
+    status::{self, ChangeKind},
+    worktree::{self, TreeFiles},
+};
+
+const ABBREV_LENGTH: usize = 7;
+const STAT_WIDTH: usize = 80;
+/// 関数名として hunk ヘッダに載せる行の最大長
+const FUNCNAME_LENGTH: usize = 80;
+
+pub fn handle(repo: &Repository, args: &DiffArgs) -> Result<()> {
+    let (old, new, new_in_worktree) = read_sides(repo, args)?;
+    let pathspec = Pathspec::parse(repo, &args.paths)?;
+    let changes = diff::diff_files(&old, &new)
+        .into_iter()
+        .filter(|change| pathspec.matches(&change.path))
+        .collect::<Vec<_>>();
+
+    let algorithm = if args.patience {
+        Algorithm::Patience
+    } else {
+        args.diff_algorithm.into()
+    };
+    let mut out = BufWriter::new(std::io::stdout().lock());
+
+    if args.name_only {
+        for change in &changes {
+            writeln!(out, "{}", change.path)?;
+        }
+    } else if args.name_status {
+        for change in &changes {
+            let kind = ChangeKind::between(change.old.as_ref(), change.new.as_ref())
+                .expect("changed path must have a kind");
+            writeln!(out, "{}\t{}", kind.short_code(), change.path)?;
+        }
+    } else if args.stat {
+        write_stat(repo, &mut out, &changes, new_in_worktree, algorithm)?;
+    } else {
+        for change in &changes {
+            write_patch(
+                repo,
+                &mut out,
+                change,
+                new_in_worktree,
+                args.unified,
+                algorithm,
+            )?;
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// 比較する 2 つの状態と、新しい側が作業ツリーかどうかを返す
+fn read_sides(repo: &Repository, args: &DiffArgs) -> Result<(TreeFiles, TreeFiles, bool)> {
+    let revisions = match args.revisions.as_slice() {
+        [range] if range.contains("...") => {
+            let (left, right) = range.split_once("...").unwrap();
+            let left = revision::resolve_commit(repo, or_head(left))?;
+            let right = revision::resolve_commit(repo, or_head(right))?;
+            let Some(base) = rev_walk::merge_bases(repo, &left, &right)?
+                .into_iter()
+                .next()
+            else {
+                bail!("{}: no merge base", range);
+            };
+            vec![base, right]
+        }
+        [range] if range.contains("..") => {
+            let (left, right) = range.split_once("..").unwrap();
+            vec![
+                revision::resolve(repo, or_head(left))?,
+                revision::resolve(repo, or_head(right))?,
+            ]
+        }
+        revisions => revisions
+            .iter()
+            .map(|revision| revision::resolve(repo, revision))
+            .collect::<Result<Vec<_>>>()?,
+    };
+
+    let index = Index::read(repo)?.unwrap_or_else(Index::new);
+    if args.cached {
+        let old = match revisions.as_slice() {
+            [] => {
+                worktree::read_tree_files(repo, Head::read(repo)?.read_tree_hash(repo)?.as_ref())?
+            }
+            [revision] => read_tree_files(repo, revision)?,
+            _ => bail!("Too many revisions for --cached"),
+        };
+        return Ok((old, status::read_index_files(&index), false));
+    }
+
+    match revisions.as_slice() {
+        [] => Ok((
+            status::read_index_files(&index),
+            status::read_worktree_files(repo, &index)?,
+            true,
+        )),
+        [revision] => Ok((
+            read_tree_files(repo, revision)?,
+            status::read_worktree_files(repo, &index)?,
+            true,
+        )),
+        [old, new] => Ok((
+            read_tree_files(repo, old)?,
+            read_tree_files(repo, new)?,
+            false,
+        )),
+        _ => bail!("Too many revisions"),
+    }
+}
+
+fn or_head(revision: &str) -> &str {
+    if revision.is_empty() {
+        "HEAD"
+    } else {
+        revision
+    }
+}
+
+fn read_tree_files(repo: &Repository, hash: &Hash) -> Result<TreeFiles> {
+    let (tree, _) = GitObject::peel(repo, hash, ObjectType::Tree)?;
+    worktree::read_tree_files(repo, Some(&tree))
+}
+
+fn read_content(
+    repo: &Repository,
+    path: &str,
+    file: &(BlobType, Hash),
+    in_worktree: bool,
+) -> Result<Vec<u8>> {
+    let (blob_type, hash) = file;
+    if in_worktree {
+        worktree::read_file(&repo.work_tree_path(path)?, *blob_type)
+    } else {
+        Ok(GitObject::read(repo, hash)?.body)
+    }
+}
+
+fn abbrev(repo: &Repository, file: Option<&(BlobType, Hash)>) -> Result<String> {
+    match file {
+        Some((_, hash)) => revision::shortest_unique_prefix(repo, hash, ABBREV_LENGTH),
+        None => Ok("0".repeat(ABBREV_LENGTH)),
+    }
+}
+
+fn write_patch(
+    repo: &Repository,
+    out: &mut impl Write,
+    change: &FileChange,
+    new_in_worktree: bool,
+    context: usize,
+    algorithm: Algorithm,
+) -> Result<()> {
+    let (old, new) = (change.old.as_ref(), change.new.as_ref());
+
+    // symlink と通常ファイルの入れ替わりは削除と追加に分けて表示する
+    if ChangeKind::between(old, new) == Some(ChangeKind::TypeChanged) {
+        let write_file_patch = |out: &mut _, old, new| {
+            write_file_patch(
+                repo,
+                out,
+                &change.path,
+                old,
+                new,
+                new_in_worktree,
+                context,
+                algorithm,
+            )
+        };
+        write_file_patch(out, old, None)?;
+        return write_file_patch(out, None, new);
+    }
+
+    write_file_patch(
+        repo,
+        out,
+        &change.path,
+        old,
+        new,
+        new_in_worktree,
+        context,
+        algorithm,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_file_patch(
+    repo: &Repository,
+    out: &mut impl Write,
+    path: &str,
+    old: Option<&(BlobType, Hash)>,
+    new: Option<&(BlobType, Hash)>,
+    new_in_worktree: bool,
+    context: usize,
+    algorithm: Algorithm,
+) -> Result<()> {
+    writeln!(out, "diff --git a/{} b/{}", path, path)?;
+    match (old, new) {
+        (None, Some((blob_type, _))) => {
+            writeln!(out, "new file mode {}", Mode::Blob(*blob_type))?;
+        }
+        (Some((blob_type, _)), None) => {
+            writeln!(out, "deleted file mode {}", Mode::Blob(*blob_type))?;
+        }
+        (Some((old_type, _)), Some((new_type, _))) if old_type != new_type => {
+            writeln!(out, "old mode {}", Mode::Blob(*old_type))?;
+            writeln!(out, "new mode {}", Mode::Blob(*new_type))?;
+        }
+        _ => {}
+    }
+
+    if old.map(|(_, hash)| hash) == new.map(|(_, hash)| hash) {
+        // モードだけの変更
+        return Ok(());
+    }
+    match (old, new) {
+        (Some((old_type, _)), Some((new_type, _))) if old_type == new_type => writeln!(
+            out,
+            "index {}..{} {}",
+            abbrev(repo, old)?,
+            abbrev(repo, new)?,
+            Mode::Blob(*new_type)
+        )?,
+        _ => writeln!(out, "index {}..{}", abbrev(repo, old)?, abbrev(repo, new)?)?,
+    }
+
+    let old_content = match old {
+        Some(old) => read_content(repo, path, old, false)?,
+        None => Vec::new(),
+    };
+    let new_content = match new {
+        Some(new) => read_content(repo, path, new, new_in_worktree)?,
+        None => Vec::new(),
+    };
+    let old_name = match old {
+        Some(_) => format!("a/{}", path),
+        None => "/dev/null".to_string(),
+    };
+    let new_name = match new {
+        Some(_) => format!("b/{}", path),
+        None => "/dev/null".to_string(),
+    };
+
+    if diff::is_binary(&old_content) || diff::is_binary(&new_content) {
+        writeln!(out, "Binary files {} and {} differ", old_name, new_name)?;
+        return Ok(());
+    }
+
+    let old_lines = diff::split_lines(&old_content);
+    let new_lines = diff::split_lines(&new_content);
+    let edits = diff::diff_lines(&old_lines, &new_lines, algorithm);
+    let hunks = diff::hunks(&edits, context);
+    if hunks.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "--- {}", old_name)?;
+    writeln!(out, "+++ {}", new_name)?;
+    for hunk in hunks {
+        match funcname(&old_lines[..hunk.old_start]) {
+            Some(funcname) => {
+                write!(out, "{} ", hunk.header())?;
+                out.write_all(funcname)?;
+                writeln!(out)?;
+            }
+            None => writeln!(out, "{}", hunk.header())?,
+        }
+
+        for edit in &hunk.edits {
+            let (prefix, line) = match *edit {
+                Edit::Equal(i, _) => (b' ', old_lines[i]),
+                Edit::Delete(i) => (b'-', old_lines[i]),
+                Edit::Insert(j) => (b'+', new_lines[j]),
+            };
+            out.write_all(&[prefix])?;
+            out.write_all(line)?;
+            if !line.ends_with(b"\n") {
+                writeln!(out, "\n\\ No newline at end of file")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// hunk より前で最後に現れる、英字か `_`, `$` で始まる行 (git のデフォルトの funcname)
+fn funcname<'a>(lines: &[&'a [u8]]) -> Option<&'a [u8]> {
+    lines.iter().rev().find_map(|line| {
+        let first = *line.first()?;
+        if !(first.is_ascii_alphabetic() || first == b'_' || first == b'$') {
+            return None;
+        }
+
+        let line = &line[..line.len().min(FUNCNAME_LENGTH)];
+        let end = line
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .map_or(0, |i| i + 1);
+        Some(&line[..end])
+    })
+}
+
+enum StatCount {
+    Text { added: usize, deleted: usize },
+    Binary { old_size: usize, new_size: usize },
+}
+
+fn count_changes(
+    repo: &Repository,
+    change: &FileChange,
+    new_in_worktree: bool,
+    algorithm: Algorithm,
+) -> Result<StatCount> {
+    let old_content = match &change.old {
+        Some(old) => read_content(repo, &change.path, old, false)?,
+        None => Vec::new(),
+    };
+    let new_content = match &change.new {
+        Some(new) => read_content(repo, &change.path, new, new_in_worktree)?,
+        None => Vec::new(),
+    };
+
+    if diff::is_binary(&old_content) || diff::is_binary(&new_content) {
+        return Ok(StatCount::Binary {
+            old_size: old_content.len(),
+            new_size: new_content.len(),
+        });
+    }
+
+    let edits = diff::diff_lines(
+        &diff::split_lines(&old_content),
+        &diff::split_lines(&new_content),
+        algorithm,
+    );
+    let added = edits
+        .iter()
+        .filter(|edit| matches!(edit, Edit::Insert(_)))
+        .count();
+    let deleted = edits
+        .iter()
+        .filter(|edit| matches!(edit, Edit::Delete(_)))
+        .count();
+
+    Ok(StatCount::Text { added, deleted })
+}
+
+/// git の diffstat と同じ幅の計算で `path | N +++--` を出力する
+fn write_stat(
+    repo: &Repository,
+    out: &mut impl Write,
+    changes: &[FileChange],
+    new_in_worktree: bool,
+    algorithm: Algorithm,
+) -> Result<()> {
+    let counts = changes
+        .iter()
+        .map(|change| count_changes(repo, change, new_in_worktree, algorithm))
+        .collect::<Result<Vec<_>>>()?;
+
+    let decimal_width = |n: usize| n.to_string().len();
+    let mut max_change = 0;
+    let mut number_width = 0;
+    let mut bin_width = 0;
+    for count in &counts {
+        match *count {
+            StatCount::Text { added, deleted } => max_change = max_change.max(added + deleted),
+            StatCount::Binary { old_size, new_size } => {
+                bin_width = bin_width.max(14 + decimal_width(old_size) + decimal_width(new_size));
+                number_width = 3;
+            }
+        }
+    }
+    number_width = number_width.max(decimal_width(max_change));
+
+    let width = STAT_WIDTH.max(16 + 6 + number_width);
+    let mut graph_width = if max_change + 4 > bin_width {
+        max_change
+    } else {
+        bin_width - 4
+    };
+    let mut name_width = changes
+        .iter()
+        .map(|change| change.path.chars().count())
+        .max()
+        .unwrap_or(0);
+    if name_width + number_width + 6 + graph_width > width {
+        if graph_width > (width * 3 / 8).saturating_sub(number_width + 6) {
+            graph_width = (width * 3 / 8).saturating_sub(number_width + 6).max(6);
+        }
+        if name_width > width - number_width - 6 - graph_width {
+            name_width = width - number_width - 6 - graph_width;
+        } else {
+            graph_width = width - number_width - 6 - name_width;
+        }
+    }
+
+    let scale = |n: usize| {
+        if n == 0 {
+            0
+        } else {
+            1 + n * (graph_width - 1) / max_change
+        }
+    };
+
+    let (mut insertions, mut deletions) = (0, 0);
+    for (change, count) in changes.iter().zip(&counts) {
+        let chars = change.path.chars().collect::<Vec<_>>();
+        let name = if chars.len() > name_width {
+            // 長すぎるパスは先頭を `...` で省略し、できればディレクトリの区切りから始める
+            let rest = chars[chars.len() - (name_width - 3)..]
+                .iter()
+                .collect::<String>();
+            let rest = match rest.find('/') {
+                Some(i) => rest[i..].to_string(),
+                None => rest,
+            };
+            format!("...{}", rest)
+        } else {
+            change.path.clone()
+        };
+        write!(out, " {:<name_width$} | ", name)?;
+
+        match *count {
+            StatCount::Binary { old_size, new_size } => {
+                write!(out, "{:>number_width$}", "Bin")?;
+                if old_size != 0 || new_size != 0 {
+                    write!(out, " {} -> {} bytes", old_size, new_size)?;
+                }
+                writeln!(out)?;
+            }
+            StatCount::Text { added, deleted } => {
+                insertions += added;
+                deletions += deleted;
+
+                let total = added + deleted;
+                write!(out, "{:>number_width$}", total)?;
+                if total != 0 {
+                    write!(out, " ")?;
+                }
+
+                let (mut added, mut deleted) = (added, deleted);
+                if graph_width <= max_change {
+                    let mut total = scale(total);
+                    if total < 2 && added != 0 && deleted != 0 {
+                        total = 2;
+                    }
+                    if added < deleted {
+                        added = scale(added);
+                        deleted = total - added;
+                    } else {
+                        deleted = scale(deleted);
+                        added = total - deleted;
+                    }
+                }
+                writeln!(out, "{}{}", "+".repeat(added), "-".repeat(deleted))?;
+            }
+        }
+    }
+
+    if changes.is_empty() {
+        return Ok(());
+    }
+    write!(
+        out,
+        " {} file{} changed",
+        changes.len(),
+        if changes.len() == 1 { "" } else { "s" }
+    )?;
+    if insertions != 0 || deletions == 0 {
+        write!(
+            out,
+            ", {} insertion{}(+)",
+            insertions,
+            if insertions == 1 { "" } else { "s" }
+        )?;
+    }
+    if deletions != 0 || insertions == 0 {
+        write!(
+            out,
+            ", {} deletion{}(-)",
+            deletions,
+            if deletions == 1 { "" } else { "s" }
+        )?;
+    }
+    writeln!(out)?;
+
+    Ok(())
+}
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use crate::parser::ForEachRefArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{
+    config::Config,
+    head::Head,
+    object::{hash::Hash, GitObject, ObjectType, User},
+    packed_refs::peel_tag,
+    refs::{read_ref, ref_names},
+    repository::Repository,
+    rev_walk, revision,
+    wildmatch::wildmatch,
+};
+use ssgit::error::Error;
+
+const ABBREV_LENGTH: usize = 7;
+const DATE_FORMAT: &str = "%a %b %-d %H:%M:%S %Y %z";
+const DEFAULT_FORMAT: &str = "%(objectname) %(objecttype)\t%(refname)";
+
+/// 表示や並べ替えに使う ref の情報
+struct RefInfo {
+    name: String,
+    hash: Hash,
+    object: GitObject,
+    /// tag を剥がした先が commit ならその hash
+    commit: Option<Hash>,
+}
+
+pub fn handle(repo: &Repository, args: &ForEachRefArgs) -> Result<()> {
+    let format = args.format.as_deref().unwrap_or(DEFAULT_FORMAT);
+    let context = Context::read(repo)?;
+
+    let points_at = args
+        .points_at
+        .as_deref()
+        .map(|object| revision::resolve(repo, object))
+        .transpose()?;
+    let contains = args
+        .contains
+        .as_deref()
+        .map(|commit| revision::resolve_commit(repo, commit))
+        .transpose()?;
+    let merged = match &args.merged {
+        Some(commit) => Some(rev_walk::ancestors(
+            repo,
+            &[revision::resolve_commit(repo, commit)?],
+        )?),
+        None => None,
+    };
+
+    let mut reachable = HashMap::new();
+    let mut refs = Vec::new();
+    for name in ref_names(repo, "refs/")? {
+        if !args.patterns.is_empty()
+            && !args
+                .patterns
+                .iter()
+                .any(|pattern| matches_pattern(pattern, &name))
+        {
+            continue;
+        }
+        let Some(hash) = read_ref(repo, &name)? else {
+            continue;
+        };
+        let object = GitObject::read(repo, &hash)?;
+        let commit = match GitObject::peel(repo, &hash, ObjectType::Commit) {
+            Ok((hash, _)) => Some(hash),
+            // commit を指していない ref
+            Err(Error::TypeMismatch(_)) => None,
+            Err(error) => return Err(error.into()),
+        };
+        let info = RefInfo {
+            name,
+            hash,
+            object,
+            commit,
+        };
+
+        if let Some(points_at) = &points_at {
+            if &info.hash != points_at && peel_tag(repo, &info.hash)?.as_ref() != Some(points_at) {
+                continue;
+            }
+        }
+        if let Some(contains) = &contains {
+            let Some(commit) = &info.commit else {
+                continue;
+            };
+            if !rev_walk::can_reach(repo, commit, contains, &mut reachable)? {
+                continue;
+            }
+        }
+        if let Some(merged) = &merged {
+            if !info
+                .commit
+                .as_ref()
+                .is_some_and(|commit| merged.contains(commit))
+            {
+                continue;
+            }
+        }
+
+        refs.push(info);
+    }
+
+    // 後に指定されたキーほど優先されるように、前のキーから順に安定ソートする
+    for key in &args.sort {
+        let (atom, reverse) = match key.strip_prefix('-') {
+            Some(atom) => (atom, true),
+            None => (key.as_str(), false),
+        };
+        let mut keyed = refs
+            .into_iter()
+            .map(|info| Ok((sort_key(&context, &info, atom)?, info)))
+            .collect::<Result<Vec<_>>>()?;
+        keyed.sort_by(|(a, _), (b, _)| {
+            let ordering = a.cmp(b);
+            if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        refs = keyed.into_iter().map(|(_, info)| info).collect();
+    }
+
+    for info in refs.iter().take(args.count.unwrap_or(usize::MAX)) {
+        println!("{}", expand_format(&context, format, info)?);
+    }
+
+    Ok(())
+}
+
+/// glob を含めば refname 全体に、含まなければ `/` 区切りの前方一致で比べる
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    if pattern.contains(['*', '?', '[']) {
+        return wildmatch(pattern.as_bytes(), name.as_bytes(), true);
+    }
+
+    let pattern = pattern.trim_end_matches('/');
+    name == pattern
+        || name
+            .strip_prefix(pattern)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// 全ての ref で共通して使う HEAD と config
+struct Context {
+    head: Option<String>,
+    config: Config,
+}
+impl Context {
+    fn read(repo: &Repository) -> Result<Self> {
+        let head = match Head::read(repo)? {
+            Head::Ref(ref_) => Some(ref_.to_string()),
+            Head::Detached(_) => None,
+        };
+
+        Ok(Self {
+            head,
+            config: Config::read(Some(repo))?,
+        })
+    }
+
+    /// `branch.<name>.remote` と `branch.<name>.merge` から追跡している ref を求める
+    fn upstream(&self, name: &str) -> Option<String> {
+        let branch = name.strip_prefix("refs/heads/")?;
+        let remote = self.config.get(&format!("branch.{}.remote", branch))?;
+        let merge = self.config.get(&format!("branch.{}.merge", branch))?;
+
+        if remote == "." {
+            return Some(merge.to_string());
+        }
+        let merge = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+        Some(format!("refs/remotes/{}/{}", remote, merge))
+    }
+}
+
+/// `%(atom)` と `%%` を展開する
+fn expand_format(context: &Context, format: &str, info: &RefInfo) -> Result<String> {
+    let mut result = String::new();
+    let mut rest = format;
+
+    while let Some(start) = rest.find('%') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("%%") {
+            result.push('%');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("%(") {
+            let Some(end) = after.find(')') else {
+                bail!("malformed format string {}", format);
+            };
+            result.push_str(&atom_value(context, info, &after[..end])?);
+            rest = &after[end + 1..];
+        } else {
+            result.push('%');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+fn atom_value(context: &Context, info: &RefInfo, atom: &str) -> Result<String> {
+    let (name, modifier) = match atom.split_once(':') {
+        Some((name, modifier)) => (name, Some(modifier)),
+        None => (atom, None),
+    };
+
+    let value = match name {
+        "refname" => format_refname(&info.name, modifier),
+        "objectname" => match modifier {
+            Some("short") => info.hash.to_string()[..ABBREV_LENGTH].to_string(),
+            _ => info.hash.to_string(),
+        },
+        "objecttype" => info.object.type_.to_string(),
+        "HEAD" => {
+            if context.head.as_deref() == Some(info.name.as_str()) {
+                "*".to_string()
+            } else {
+                " ".to_string()
+            }
+        }
+        "upstream" => context
+            .upstream(&info.name)
+            .map(|upstream| format_refname(&upstream, modifier))
+            .unwrap_or_default(),
+        "authordate" | "committerdate" | "authorname" | "authoremail" => {
+            let Some(user) = user(info, name)? else {
+                return Ok(String::new());
+            };
+            match name {
+                "authorname" => user.name,
+                "authoremail" => format!("<{}>", user.email),
+                _ => format_date(&user, modifier)?,
+            }
+        }
+        "subject" => subject(info)?,
+        _ => bail!("unknown field name: {}", name),
+    };
+
+    Ok(value)
+}
+
+fn format_refname(name: &str, modifier: Option<&str>) -> String {
+    if modifier != Some("short") {
+        return name.to_string();
+    }
+
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+        .to_string()
+}
+
+/// commit を指す ref の author か committer (他の種類の object なら None)
+fn user(info: &RefInfo, name: &str) -> Result<Option<User>> {
+    if info.object.type_ != ObjectType::Commit {
+        return Ok(None);
+    }
+
+    let commit = info.object.parse_commit_body()?;
+    Ok(Some(if name == "committerdate" {
+        commit.committer
+    } else {
+        commit.author
+    }))
+}
+
+fn format_date(user: &User, modifier: Option<&str>) -> Result<String> {
+    let time = &user.time;
+    Ok(match modifier {
+        None => time.format(DATE_FORMAT).to_string(),
+        Some("short") => time.format("%Y-%m-%d").to_string(),
+        Some("iso") => time.format("%Y-%m-%d %H:%M:%S %z").to_string(),
+        Some("iso-strict") => time.to_rfc3339(),
+        Some("unix") => time.timestamp().to_string(),
+        Some("raw") => time.format("%s %z").to_string(),
+        Some(modifier) => bail!("unknown date format {}", modifier),
+    })
+}
+
+/// commit か tag のメッセージの最初の段落を 1 行にしたもの
+fn subject(info: &RefInfo) -> Result<String> {
+    let message = match info.object.type_ {
+        ObjectType::Commit => info.object.parse_commit_body()?.message,
+        ObjectType::Tag => info.object.parse_tag_body()?.message,
+        _ => return Ok(String::new()),
+    };
+
+    Ok(message
+        .split("\n\n")
+        .next()
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// 日付は時刻で、それ以外は展開した文字列で比べる
+fn sort_key(context: &Context, info: &RefInfo, atom: &str) -> Result<(i64, String)> {
+    match atom {
+        "authordate" | "committerdate" => Ok((
+            user(info, atom)?
+                .map(|user| user.time.timestamp())
+                .unwrap_or_default(),
+            String::new(),
+        )),
+        atom => Ok((0, atom_value(context, info, atom)?)),
+    }
+}
@@ -1,31 +1,32 @@
-use crate::entity::object::GitObject;
-use crate::parser::{HashObjectArgs, ObjectType};
-use anyhow::Result;
+use crate::parser::HashObjectArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::object::{GitObject, ObjectType};
+use ssgit::entity::repository::Repository;
 
 pub fn handle(args: &HashObjectArgs) -> Result<()> {
-    dbg!(args);
-
-    if args.type_ != ObjectType::Blob {
-        todo!();
-    }
-
     let file_content = std::fs::read(args.file.as_str())?;
 
     let object = GitObject::new(args.type_.into(), file_content);
-    let hash = object.hash();
-    let object_bytes = object.to_raw()?;
 
-    let path = hash.get_object_path();
-
-    if path.exists() {
-        let existing_content = std::fs::read(&path)?;
-        dbg!(existing_content == object_bytes);
-        println!("{}", hash);
-        return Ok(());
+    // blob 以外は git と同じく、形式が正しくなければ object を作らない
+    let valid = match object.type_ {
+        ObjectType::Blob => true,
+        ObjectType::Tree => object.parse_tree_body().is_ok(),
+        ObjectType::Commit => object.parse_commit_body().is_ok(),
+        ObjectType::Tag => object.parse_tag_body().is_ok(),
+    };
+    if !valid {
+        bail!("corrupt {}", object.type_);
     }
 
+    let hash = object.hash();
+
+    // 書き込まないなら repository の外でも hash だけ計算できる
     if args.write {
-        object.write()?;
+        let repo = Repository::discover()?;
+        if !GitObject::exists(&repo, &hash)? {
+            object.write(&repo)?;
+        }
     }
 
     println!("{}", hash);
@@ -0,0 +1,476 @@
+use std::fmt::Write;
+
+use crate::parser::LogArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{
+    date::parse_date,
+    object::{hash::Hash, Commit, User},
+    pathspec::Pathspec,
+    repository::Repository,
+    rev_walk::{Order, RevWalk, WalkedCommit},
+};
+
+const ABBREV_LENGTH: usize = 7;
+const DATE_FORMAT: &str = "%a %b %-d %H:%M:%S %Y %z";
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Pretty {
+    Oneline,
+    Short,
+    Medium,
+    Full,
+    Fuller,
+    Format(String),
+}
+impl Pretty {
+    fn parse(s: &str) -> Self {
+        match s {
+            "oneline" => Self::Oneline,
+            "short" => Self::Short,
+            "medium" => Self::Medium,
+            "full" => Self::Full,
+            "fuller" => Self::Fuller,
+            _ => {
+                let format = s
+                    .strip_prefix("format:")
+                    .or(s.strip_prefix("tformat:"))
+                    .unwrap_or(s);
+                Self::Format(format.to_string())
+            }
+        }
+    }
+
+    /// commit 間に空行を入れる形式かどうか
+    fn is_multiline(&self) -> bool {
+        !matches!(self, Self::Oneline | Self::Format(_))
+    }
+}
+
+pub fn handle(repo: &Repository, args: &LogArgs) -> Result<()> {
+    let mut walk = RevWalk {
+        order: if args.topo_order {
+            Order::Topo
+        } else if args.date_order {
+            Order::Date
+        } else if args.graph {
+            Order::Topo
+        } else {
+            Order::Default
+        },
+        first_parent: args.first_parent,
+        since: args.since.as_deref().map(parse_date).transpose()?,
+        until: args.until.as_deref().map(parse_date).transpose()?,
+        pathspec: Pathspec::parse(repo, &args.paths)?,
+        max_count: args.max_count,
+        ..Default::default()
+    };
+
+    if args.revisions.is_empty() {
+        walk.push_revision(repo, "HEAD")?;
+    }
+    for revision in &args.revisions {
+        walk.push_revision(repo, revision)?;
+    }
+    if walk.include.is_empty() {
+        bail!("No revisions to show");
+    }
+
+    let pretty = match (&args.format, args.oneline) {
+        (Some(format), _) => Pretty::parse(format),
+        (None, true) => Pretty::Oneline,
+        (None, false) => Pretty::Medium,
+    };
+
+    let commits = walk.walk(repo)?;
+    let mut graph = args.graph.then(Graph::default);
+
+    for (i, walked) in commits.iter().enumerate() {
+        let lines = format_commit(walked, &pretty);
+
+        let Some(graph) = graph.as_mut() else {
+            if i > 0 && pretty.is_multiline() {
+                println!();
+            }
+            for line in lines {
+                println!("{}", line);
+            }
+            continue;
+        };
+
+        let parents = walked
+            .commit
+            .parent
+            .iter()
+            .take(if args.first_parent { 1 } else { usize::MAX })
+            .filter(|parent| commits.iter().any(|c| &c.hash == *parent))
+            .cloned()
+            .collect::<Vec<_>>();
+        let rendered = graph.next(&walked.hash, &parents);
+
+        for line in rendered.before {
+            println!("{}", line);
+        }
+
+        // commit の次の行から、分岐や合流の線を前置きとして使う
+        let mut after = rendered.after.into_iter();
+        let mut print_line = |line: &str| {
+            let prefix = after
+                .next()
+                .unwrap_or_else(|| rendered.continuation_prefix.clone());
+            println!("{}", format!("{}{}", prefix, line).trim_end());
+        };
+
+        println!(
+            "{}",
+            format!("{}{}", rendered.commit_prefix, lines[0]).trim_end()
+        );
+        for line in &lines[1..] {
+            print_line(line);
+        }
+        if pretty.is_multiline() && i + 1 < commits.len() {
+            print_line("");
+        }
+        for line in after {
+            println!("{}", line.trim_end());
+        }
+    }
+
+    Ok(())
+}
+
+fn abbrev(hash: &Hash) -> String {
+    hash.to_string()[..ABBREV_LENGTH].to_string()
+}
+
+fn format_date(user: &User) -> String {
+    user.time.format(DATE_FORMAT).to_string()
+}
+
+fn subject(commit: &Commit) -> String {
+    commit
+        .message
+        .split("\n\n")
+        .next()
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn body(commit: &Commit) -> String {
+    commit
+        .message
+        .split_once("\n\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default()
+}
+
+fn format_commit(walked: &WalkedCommit, pretty: &Pretty) -> Vec<String> {
+    let WalkedCommit { hash, commit } = walked;
+
+    let mut lines = Vec::new();
+    match pretty {
+        Pretty::Oneline => lines.push(format!("{} {}", abbrev(hash), subject(commit))),
+        Pretty::Format(format) => lines.extend(
+            expand_format(format, hash, commit)
+                .lines()
+                .map(String::from),
+        ),
+        Pretty::Short | Pretty::Medium | Pretty::Full | Pretty::Fuller => {
+            lines.push(format!("commit {}", hash));
+            if commit.parent.len() > 1 {
+                let parents = commit.parent.iter().map(abbrev).collect::<Vec<_>>();
+                lines.push(format!("Merge: {}", parents.join(" ")));
+            }
+
+            let author = format!("{} <{}>", commit.author.name, commit.author.email);
+            let committer = format!("{} <{}>", commit.committer.name, commit.committer.email);
+            match pretty {
+                Pretty::Short => lines.push(format!("Author: {}", author)),
+                Pretty::Medium => {
+                    lines.push(format!("Author: {}", author));
+                    lines.push(format!("Date:   {}", format_date(&commit.author)));
+                }
+                Pretty::Full => {
+                    lines.push(format!("Author: {}", author));
+                    lines.push(format!("Commit: {}", committer));
+                }
+                _ => {
+                    lines.push(format!("Author:     {}", author));
+                    lines.push(format!("AuthorDate: {}", format_date(&commit.author)));
+                    lines.push(format!("Commit:     {}", committer));
+                    lines.push(format!("CommitDate: {}", format_date(&commit.committer)));
+                }
+            }
+
+            lines.push("".to_string());
+            if *pretty == Pretty::Short {
+                lines.push(format!("    {}", subject(commit)));
+            } else {
+                lines.extend(
+                    commit
+                        .message
+                        .trim_end()
+                        .lines()
+                        .map(|line| format!("    {}", line).trim_end().to_string()),
+                );
+            }
+        }
+    }
+
+    lines
+}
+
+/// `%H` などのプレースホルダーを展開する
+fn expand_format(format: &str, hash: &Hash, commit: &Commit) -> String {
+    let mut result = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+
+        let Some(placeholder) = chars.next() else {
+            result.push('%');
+            break;
+        };
+        let user = match placeholder {
+            'a' => Some(&commit.author),
+            'c' => Some(&commit.committer),
+            _ => None,
+        };
+
+        match (placeholder, user) {
+            ('H', _) => result.push_str(&hash.to_string()),
+            ('h', _) => result.push_str(&abbrev(hash)),
+            ('T', _) => result.push_str(&commit.tree.to_string()),
+            ('t', _) => result.push_str(&abbrev(&commit.tree)),
+            ('P', _) => {
+                let parents = commit.parent.iter().map(Hash::to_string);
+                result.push_str(&parents.collect::<Vec<_>>().join(" "))
+            }
+            ('p', _) => {
+                let parents = commit.parent.iter().map(abbrev);
+                result.push_str(&parents.collect::<Vec<_>>().join(" "))
+            }
+            ('s', _) => result.push_str(&subject(commit)),
+            ('b', _) => result.push_str(&body(commit)),
+            ('B', _) => result.push_str(&commit.message),
+            ('n', _) => result.push('\n'),
+            ('%', _) => result.push('%'),
+            (_, Some(user)) => match chars.next() {
+                Some('n') => result.push_str(&user.name),
+                Some('e') => result.push_str(&user.email),
+                Some('d') => result.push_str(&format_date(user)),
+                Some('t') => {
+                    let _ = write!(result, "{}", user.time.timestamp());
+                }
+                Some('I') => result.push_str(&user.time.to_rfc3339()),
+                Some(other) => {
+                    let _ = write!(result, "%{}{}", placeholder, other);
+                }
+                None => {
+                    let _ = write!(result, "%{}", placeholder);
+                }
+            },
+            (other, None) => {
+                let _ = write!(result, "%{}", other);
+            }
+        }
+    }
+
+    result
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct RenderedGraph {
+    before: Vec<String>,
+    commit_prefix: String,
+    continuation_prefix: String,
+    after: Vec<String>,
+}
+
+/// `--graph` の描画状態。各列は次に現れるはずの commit を持つ
+#[derive(Debug, Default)]
+struct Graph {
+    columns: Vec<Hash>,
+}
+impl Graph {
+    fn next(&mut self, hash: &Hash, parents: &[Hash]) -> RenderedGraph {
+        let mut before = Vec::new();
+
+        // 同じ commit を待っている列が複数あれば、commit の前に合流させる
+        let merged = match self.columns.iter().position(|column| column == hash) {
+            Some(first) => (first + 1..self.columns.len())
+                .filter(|&i| self.columns[i] == *hash)
+                .collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+        if !merged.is_empty() {
+            before.push(self.render_shift(&merged));
+            for &i in merged.iter().rev() {
+                self.columns.remove(i);
+            }
+        }
+
+        let index = match self.columns.iter().position(|column| column == hash) {
+            Some(index) => index,
+            None => {
+                self.columns.push(hash.clone());
+                self.columns.len() - 1
+            }
+        };
+
+        let mut commit_prefix = self.render(|i| if i == index { '*' } else { '|' });
+
+        let mut after = Vec::new();
+        match parents.split_first() {
+            Some((first, rest)) => {
+                self.columns[index] = first.clone();
+                for (offset, parent) in rest.iter().enumerate() {
+                    self.columns.insert(index + 1 + offset, parent.clone());
+                }
+
+                if !rest.is_empty() {
+                    // 新しい列が分岐し、右側の列は押し出される
+                    let right = self.columns.len() - index - 1 - rest.len();
+                    after.push(format!(
+                        "{}|{}{}",
+                        "| ".repeat(index),
+                        "\\".repeat(rest.len()),
+                        " \\".repeat(right)
+                    ));
+                    // 分岐後の列の位置にメッセージを揃える
+                    commit_prefix = format!("{:width$}", commit_prefix, width = self.width());
+                }
+            }
+            None => {
+                self.columns.remove(index);
+                if index < self.columns.len() {
+                    after.push(self.render_shift(&[index]));
+                }
+            }
+        }
+
+        // 親が既に他の列で待たれているなら、その列まで線を寄せて合流させる
+        while let Some(duplicated) =
+            (0..self.columns.len()).find(|&i| self.columns[..i].contains(&self.columns[i]))
+        {
+            let target = self
+                .columns
+                .iter()
+                .position(|column| *column == self.columns[duplicated])
+                .unwrap();
+            after.extend(self.render_move(duplicated, target));
+            self.columns.remove(duplicated);
+        }
+
+        let width = self.width().max(commit_prefix.len());
+        RenderedGraph {
+            before,
+            commit_prefix: format!("{:width$} ", commit_prefix),
+            continuation_prefix: format!("{:width$} ", self.render(|_| '|')),
+            after: after
+                .into_iter()
+                .map(|line| format!("{:width$} ", line))
+                .collect(),
+        }
+    }
+
+    fn width(&self) -> usize {
+        (self.columns.len() * 2).saturating_sub(1)
+    }
+
+    fn render(&self, f: impl Fn(usize) -> char) -> String {
+        (0..self.columns.len())
+            .map(|i| f(i).to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// from の列が 1 行に 1 列ずつ左に寄って to の列に合流する線 (右側の列は最初の行で左に寄る)
+    fn render_move(&self, from: usize, to: usize) -> Vec<String> {
+        (to + 1..=from)
+            .rev()
+            .map(|position| {
+                let mut line = vec![' '; self.columns.len() * 2];
+                for i in 0..self.columns.len() {
+                    if i < from {
+                        line[i * 2] = '|';
+                    } else if i > from && position == from {
+                        line[i * 2 - 1] = '/';
+                    } else if i > from {
+                        line[(i - 1) * 2] = '|';
+                    }
+                }
+                line[position * 2 - 1] = '/';
+
+                line.into_iter().collect::<String>().trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// removed の列が消え、右側の列が左に寄る線
+    fn render_shift(&self, removed: &[usize]) -> String {
+        let mut line = vec![' '; self.columns.len() * 2];
+        let mut shifting = false;
+        for i in 0..self.columns.len() {
+            if removed.contains(&i) {
+                shifting = true;
+            }
+
+            if shifting && i > 0 {
+                line[i * 2 - 1] = '/';
+            } else if !removed.contains(&i) {
+                line[i * 2] = '|';
+            }
+        }
+
+        line.into_iter().collect::<String>().trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_all(graph: &mut Graph, hash: &Hash, parents: &[Hash]) -> Vec<String> {
+        let rendered = graph.next(hash, parents);
+        rendered
+            .before
+            .into_iter()
+            .chain(std::iter::once(rendered.commit_prefix))
+            .chain(rendered.after)
+            .map(|line| line.trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_graph_merge_into_existing_column() {
+        let [a, b, c, d, m] = [1, 2, 3, 4, 5].map(|i| Hash::from([i; 20]));
+        let mut graph = Graph::default();
+
+        assert_eq!(
+            render_all(&mut graph, &d, std::slice::from_ref(&c)),
+            vec!["*"]
+        );
+        // 2 番目の親 c は既に列 0 で待たれている
+        assert_eq!(
+            render_all(&mut graph, &m, &[b.clone(), c.clone()]),
+            vec!["| *", "| |\\", "| |/", "|/|"]
+        );
+        assert_eq!(graph.columns, vec![c.clone(), b.clone()]);
+        assert_eq!(
+            render_all(&mut graph, &c, std::slice::from_ref(&a)),
+            vec!["* |"]
+        );
+        assert_eq!(
+            render_all(&mut graph, &b, std::slice::from_ref(&a)),
+            vec!["| *", "|/"]
+        );
+        assert_eq!(graph.columns, vec![a]);
+    }
+}
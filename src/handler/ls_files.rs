@@ -1,22 +1,37 @@
-use crate::{entity::index::Index, parser::LsFilesArgs};
+use crate::parser::LsFilesArgs;
 use anyhow::Result;
+use ssgit::entity::{index::Index, pathspec::Pathspec, repository::Repository};
 
-pub fn handle(args: &LsFilesArgs) -> Result<()> {
-    dbg!(args);
-
-    let Some(index) = Index::read()? else {
+pub fn handle(repo: &Repository, args: &LsFilesArgs) -> Result<()> {
+    let Some(index) = Index::read(repo)? else {
         return Ok(());
     };
+    // パスを省略したらカレントディレクトリ以下を表示する
+    let pathspec = if args.paths.is_empty() {
+        Pathspec::parse(repo, &[".".to_string()])?
+    } else {
+        Pathspec::parse(repo, &args.paths)?
+    };
+    let entries = index
+        .entries
+        .into_iter()
+        .filter(|entry| pathspec.matches(&entry.file_name));
 
     if !args.stage {
-        for entry in index.entries {
-            println!("{}", entry.file_name);
+        for entry in entries {
+            println!("{}", repo.display_path(&entry.file_name));
         }
         return Ok(());
     }
 
-    for entry in index.entries {
-        println!("{} {} 0\t{}", entry.mode, entry.hash, entry.file_name);
+    for entry in entries {
+        println!(
+            "{} {} {}\t{}",
+            entry.mode,
+            entry.hash,
+            entry.stage,
+            repo.display_path(&entry.file_name)
+        );
     }
 
     Ok(())
@@ -0,0 +1,348 @@
+use std::{collections::BTreeSet, path::Path};
+
+use crate::parser::MergeArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{
+    head::Head,
+    index::{Index, IndexEntry},
+    merge::{self, ConflictReason, ConflictStyle, Labels, MergeState},
+    object::{hash::Hash, mode::Mode, Commit, GitObject, ObjectType, User},
+    pathspec::Pathspec,
+    refs::Ref,
+    repository::Repository,
+    rev_walk, revision,
+    status::Status,
+    tree::TreeNode,
+    worktree::{self, TreeFiles},
+};
+
+pub fn handle(repo: &Repository, args: &MergeArgs) -> Result<()> {
+    if MergeState::read(repo)?.is_some() {
+        bail!("You have not concluded your merge (MERGE_HEAD exists).\nPlease, commit your changes before you merge.");
+    }
+    let mut index = Index::read(repo)?.unwrap_or_else(Index::new);
+    if !index.conflicted_paths().is_empty() {
+        bail!("Merging is not possible because you have unmerged files.");
+    }
+
+    let head = Head::read(repo)?;
+    let theirs = revision::resolve_commit(repo, &args.commit)?;
+    let Some(ours) = head.read_hash(repo)? else {
+        // まだ commit がなければそのまま取り込む
+        worktree::checkout(repo, None, Some(&tree_of(repo, &theirs)?))?;
+        let message = format!("merge {}: Fast-forward", args.commit);
+        return Ok(head.update(repo, &theirs, &message)?);
+    };
+
+    let bases = rev_walk::merge_bases(repo, &ours, &theirs)?;
+    if bases.is_empty() {
+        bail!("refusing to merge unrelated histories");
+    }
+    if bases.contains(&theirs) {
+        println!("Already up to date.");
+        return Ok(());
+    }
+    if bases.contains(&ours) && !args.no_ff {
+        println!("Updating {}..{}", short(&ours), short(&theirs));
+        println!("Fast-forward");
+        worktree::checkout(
+            repo,
+            Some(&tree_of(repo, &ours)?),
+            Some(&tree_of(repo, &theirs)?),
+        )?;
+        let message = format!("merge {}: Fast-forward", args.commit);
+        return Ok(head.update(repo, &theirs, &message)?);
+    }
+
+    let style = ConflictStyle::from(args.conflict);
+    let labels = Labels {
+        base: match bases.as_slice() {
+            [base] => short(base),
+            _ => "merged common ancestors".to_string(),
+        },
+        ours: "HEAD".to_string(),
+        theirs: args.commit.clone(),
+    };
+    let base_files = merge_base_files(repo, &bases, style)?;
+    let ours_files = worktree::read_tree_files(repo, Some(&tree_of(repo, &ours)?))?;
+    let theirs_files = worktree::read_tree_files(repo, Some(&tree_of(repo, &theirs)?))?;
+    let result = merge::merge_trees(
+        repo,
+        &base_files,
+        &ours_files,
+        &theirs_files,
+        &labels,
+        style,
+    )?;
+
+    let conflicted = result
+        .conflicts
+        .iter()
+        .map(|conflict| conflict.path.as_str())
+        .collect::<BTreeSet<_>>();
+    let changed = ours_files
+        .keys()
+        .chain(result.files.keys())
+        .filter(|path| ours_files.get(*path) != result.files.get(*path))
+        .map(String::as_str)
+        .chain(conflicted.iter().copied())
+        .collect::<BTreeSet<_>>();
+    let removed = changed
+        .iter()
+        .copied()
+        .filter(|path| !conflicted.contains(path) && !result.files.contains_key(*path))
+        .collect::<BTreeSet<_>>();
+    check_local_changes(repo, &ours_files, &changed, &removed)?;
+
+    // ファイルとディレクトリが入れ替わることがあるので、消す方を先に済ませる
+    for path in &removed {
+        worktree::remove_file(repo, path)?;
+        index.remove(path);
+    }
+    let mut new_entries = Vec::new();
+    for path in &changed {
+        if let Some((blob_type, hash)) = result.files.get(*path) {
+            new_entries.push(worktree::write_file(repo, path, *blob_type, hash)?);
+        }
+    }
+    for conflict in &result.conflicts {
+        let (blob_type, content) = &conflict.worktree;
+        worktree::write_content(repo, &conflict.path, *blob_type, content)?;
+
+        let stages = [&conflict.base, &conflict.ours, &conflict.theirs];
+        for (stage, file) in (1..).zip(stages) {
+            if let Some((blob_type, hash)) = file {
+                let mut entry =
+                    IndexEntry::with_default(Mode::Blob(*blob_type), hash.clone(), &conflict.path);
+                entry.stage = stage;
+                new_entries.push(entry);
+            }
+        }
+    }
+    index.insert(&new_entries);
+    index.write(repo)?;
+
+    print_messages(&result.auto_merged, &result.conflicts, &args.commit);
+
+    let message = match &args.message {
+        Some(message) => message.clone(),
+        None => default_message(repo, &head, &args.commit)?,
+    };
+    if !result.conflicts.is_empty() || args.no_commit {
+        let mut message = format!("{}\n", message);
+        if !conflicted.is_empty() {
+            message.push_str("\n# Conflicts:\n");
+            for path in &conflicted {
+                message.push_str(&format!("#\t{}\n", path));
+            }
+        }
+        MergeState {
+            head: theirs,
+            message,
+            no_ff: args.no_ff,
+        }
+        .write(repo)?;
+
+        if !result.conflicts.is_empty() {
+            bail!("Automatic merge failed; fix conflicts and then commit the result.");
+        }
+        println!("Automatic merge went well; stopped before committing as requested");
+        return Ok(());
+    }
+
+    let tree = TreeNode::try_from(index)?.write_recursive(repo)?;
+    let author = User::author(repo)?;
+    let committer = User::committer(repo)?;
+    let commit = Commit {
+        tree,
+        parent: vec![ours, theirs],
+        author,
+        committer,
+        rest_of_header: "".to_string(),
+        message: format!("{}\n", message),
+    };
+
+    let git_object = GitObject::from_commit(&commit);
+    if !GitObject::exists(repo, &git_object.hash())? {
+        git_object.write(repo)?;
+    }
+    head.update(
+        repo,
+        &git_object.hash(),
+        &format!("merge {}: Merge made by the 'ort' strategy.", args.commit),
+    )?;
+
+    println!("Merge made by the 'ort' strategy.");
+
+    Ok(())
+}
+
+fn tree_of(repo: &Repository, commit: &Hash) -> Result<Hash> {
+    let (tree, _) = GitObject::peel(repo, commit, ObjectType::Tree)?;
+    Ok(tree)
+}
+
+fn short(hash: &Hash) -> String {
+    hash.to_string()[..7].to_string()
+}
+
+/// merge base が複数あればそれらを再帰的にマージしたものを base とする
+fn merge_base_files(repo: &Repository, bases: &[Hash], style: ConflictStyle) -> Result<TreeFiles> {
+    let Some((first, rest)) = bases.split_first() else {
+        return Ok(TreeFiles::new());
+    };
+
+    let mut files = worktree::read_tree_files(repo, Some(&tree_of(repo, first)?))?;
+    for base in rest {
+        let inner_base = merge_base_files(repo, &rev_walk::merge_bases(repo, first, base)?, style)?;
+        let other = worktree::read_tree_files(repo, Some(&tree_of(repo, base)?))?;
+        let labels = Labels {
+            base: "merged common ancestors".to_string(),
+            ours: "Temporary merge branch 1".to_string(),
+            theirs: "Temporary merge branch 2".to_string(),
+        };
+        let result = merge::merge_trees(repo, &inner_base, &files, &other, &labels, style)?;
+
+        // 衝突はマーカー入りの内容のまま base として使う
+        files = result.files;
+        for conflict in result.conflicts {
+            let (blob_type, content) = conflict.worktree;
+            let object = GitObject::new(ObjectType::Blob, content);
+            if !GitObject::exists(repo, &object.hash())? {
+                object.write(repo)?;
+            }
+            files.insert(conflict.path, (blob_type, object.hash()));
+        }
+    }
+
+    Ok(files)
+}
+
+/// マージで書き換えるファイルにローカルの変更があれば、作業ツリーに触れる前に中断する
+fn check_local_changes(
+    repo: &Repository,
+    ours_files: &TreeFiles,
+    changed: &BTreeSet<&str>,
+    removed: &BTreeSet<&str>,
+) -> Result<()> {
+    let status = Status::read(repo, &Pathspec::default())?;
+
+    let dirty = status
+        .entries
+        .iter()
+        .filter(|entry| {
+            entry.staged().is_some()
+                || (entry.unstaged().is_some() && changed.contains(entry.path.as_str()))
+        })
+        .map(|entry| format!("\t{}", entry.path))
+        .collect::<Vec<_>>();
+    if !dirty.is_empty() {
+        bail!(
+            "Your local changes to the following files would be overwritten by merge:\n{}\nPlease commit your changes or stash them before you merge.",
+            dirty.join("\n")
+        );
+    }
+
+    let mut untracked = BTreeSet::new();
+    for path in changed {
+        if removed.contains(path) {
+            continue;
+        }
+        let work_tree_path = repo.work_tree_path(path)?;
+        if work_tree_path.is_dir() && !work_tree_path.is_symlink() {
+            // 書き出す場所にあるディレクトリは、管理外のファイルを含まなければ消してよい
+            for file in worktree::list_files(repo, path, None)? {
+                if !ours_files.contains_key(&file) || !removed.contains(file.as_str()) {
+                    untracked.insert(file);
+                }
+            }
+        } else if !ours_files.contains_key(*path) && work_tree_path.symlink_metadata().is_ok() {
+            untracked.insert(path.to_string());
+        }
+
+        // 親ディレクトリの場所にファイルがあれば、それが消される場合しか書き出せない
+        for directory in Path::new(path).ancestors().skip(1) {
+            let name = directory.to_string_lossy();
+            if name.is_empty() || removed.contains(name.as_ref()) {
+                continue;
+            }
+            let work_tree_directory = repo.work_tree_path(directory)?;
+            if work_tree_directory.symlink_metadata().is_ok()
+                && (work_tree_directory.is_symlink() || !work_tree_directory.is_dir())
+            {
+                untracked.insert(name.to_string());
+            }
+        }
+    }
+    let untracked = untracked
+        .iter()
+        .map(|path| format!("\t{}", path))
+        .collect::<Vec<_>>();
+    if !untracked.is_empty() {
+        bail!(
+            "The following untracked working tree files would be overwritten by merge:\n{}\nPlease move or remove them before you merge.",
+            untracked.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+fn print_messages(auto_merged: &[String], conflicts: &[merge::Conflict], theirs_label: &str) {
+    let paths = auto_merged
+        .iter()
+        .chain(conflicts.iter().map(|conflict| &conflict.path))
+        .collect::<BTreeSet<_>>();
+
+    for path in paths {
+        if auto_merged.contains(path) {
+            println!("Auto-merging {}", path);
+        }
+        let Some(conflict) = conflicts.iter().find(|conflict| &conflict.path == path) else {
+            continue;
+        };
+        match conflict.reason {
+            ConflictReason::Content => println!("CONFLICT (content): Merge conflict in {}", path),
+            ConflictReason::AddAdd => println!("CONFLICT (add/add): Merge conflict in {}", path),
+            ConflictReason::ModifyDelete { deleted_in_ours } => {
+                let (deleted_in, modified_in) = if deleted_in_ours {
+                    ("HEAD", theirs_label)
+                } else {
+                    (theirs_label, "HEAD")
+                };
+                println!(
+                    "CONFLICT (modify/delete): {} deleted in {} and modified in {}.  Version {} of {} left in tree.",
+                    path, deleted_in, modified_in, modified_in, path
+                );
+            }
+            ConflictReason::DirectoryFile { file_in_ours } => {
+                let label = if file_in_ours { "HEAD" } else { theirs_label };
+                let original = path
+                    .rsplit_once('~')
+                    .map_or(path.as_str(), |(original, _)| original);
+                println!(
+                    "CONFLICT (file/directory): directory in the way of {} from {}; moving it to {} instead.",
+                    original, label, path
+                );
+            }
+        }
+    }
+}
+
+/// git と同じく main/master 以外に取り込むときは ` into <branch>` を付ける
+fn default_message(repo: &Repository, head: &Head, name: &str) -> Result<String> {
+    let message = if Ref::branch_names(repo)?.iter().any(|branch| branch == name) {
+        format!("Merge branch '{}'", name)
+    } else if Ref::tag_names(repo)?.iter().any(|tag| tag == name) {
+        format!("Merge tag '{}'", name)
+    } else {
+        format!("Merge commit '{}'", name)
+    };
+
+    match head {
+        Head::Ref(Ref::Branch(branch)) if branch != "main" && branch != "master" => {
+            Ok(format!("{} into {}", message, branch))
+        }
+        _ => Ok(message),
+    }
+}
@@ -0,0 +1,260 @@
+use std::path::Path;
+
+use crate::parser::MvArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{index::Index, repository::Repository};
+
+pub fn handle(repo: &Repository, args: &MvArgs) -> Result<()> {
+    let destination = repo.relative_path(&args.destination)?;
+    let sources = args
+        .sources
+        .iter()
+        .map(|source| repo.relative_path(source))
+        .collect::<ssgit::Result<Vec<_>>>()?;
+
+    // 複数の source を指定するか、destination が既存のディレクトリならその中に移動する
+    let into_directory = repo.work_tree_path(&destination)?.is_dir();
+    if sources.len() > 1 && !into_directory {
+        bail!("destination '{}' is not a directory", destination);
+    }
+
+    let mut index = Index::read(repo)?.unwrap_or_else(Index::new);
+    let mut renames = Vec::new();
+    for source in &sources {
+        let target = if into_directory {
+            let base_name = Path::new(source)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            join_path(&destination, &base_name)
+        } else {
+            destination.clone()
+        };
+
+        check_rename(repo, &index, source, &target, args.force)?;
+        renames.push((source.clone(), target));
+    }
+
+    for (source, target) in renames {
+        if args.dry_run {
+            println!("Checking rename of '{}' to '{}'", source, target);
+        }
+        let files = tracked_files(&index, &source);
+        for file in &files {
+            let new_name = join_path(&target, &file[source.len()..]);
+            if args.dry_run {
+                println!("Renaming {} to {}", file, new_name);
+                continue;
+            }
+
+            let mut entry = index.hash_map()[file.as_str()].clone();
+            entry.file_name = new_name;
+            index.remove(file);
+            index.insert(&[entry]);
+        }
+        if args.dry_run {
+            continue;
+        }
+
+        let target_path = repo.work_tree_path(&target)?;
+        if let Some(parent) = target_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(repo.work_tree_path(&source)?, target_path)?;
+    }
+
+    if !args.dry_run {
+        index.write(repo)?;
+    }
+
+    Ok(())
+}
+
+/// source 自身か、ディレクトリなら以下にある tracked なファイル
+fn tracked_files(index: &Index, source: &str) -> Vec<String> {
+    let mut files = index
+        .entries
+        .iter()
+        .map(|entry| entry.file_name.clone())
+        .filter(|name| {
+            name == source
+                || name
+                    .strip_prefix(source)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+        .collect::<Vec<_>>();
+    files.dedup();
+
+    files
+}
+
+fn check_rename(
+    repo: &Repository,
+    index: &Index,
+    source: &str,
+    target: &str,
+    force: bool,
+) -> Result<()> {
+    let fail = |reason: &str| -> Result<()> {
+        bail!("{}, source={}, destination={}", reason, source, target)
+    };
+
+    let source_path = repo.work_tree_path(source)?;
+    let Ok(metadata) = source_path.symlink_metadata() else {
+        return fail("bad source");
+    };
+    let files = tracked_files(index, source);
+    if files.is_empty() {
+        return fail(if metadata.is_dir() {
+            "source directory is empty"
+        } else {
+            "not under version control"
+        });
+    }
+    if metadata.is_dir()
+        && target
+            .strip_prefix(source)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    {
+        return fail("can not move directory into itself");
+    }
+    if index
+        .entries
+        .iter()
+        .any(|entry| entry.stage != 0 && files.contains(&entry.file_name))
+    {
+        return fail("conflicted");
+    }
+
+    let target_path = repo.work_tree_path(target)?;
+    if target_path.symlink_metadata().is_ok() {
+        // ファイルは -f で上書きできるが、ディレクトリは上書きしない
+        if !force || metadata.is_dir() || target_path.is_dir() {
+            return fail("destination exists");
+        }
+    }
+
+    Ok(())
+}
+
+fn join_path(directory: &str, name: &str) -> String {
+    let name = name.trim_start_matches('/');
+    if directory.is_empty() {
+        name.to_string()
+    } else if name.is_empty() {
+        directory.to_string()
+    } else {
+        format!("{}/{}", directory, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ssgit::entity::{
+        object::{mode::BlobType, GitObject, ObjectType},
+        tree::TreeNode,
+        worktree,
+    };
+
+    use super::*;
+
+    /// files を同じ内容で index と作業ツリーに置く
+    fn init_repo(name: &str, files: &[&str]) -> Repository {
+        let directory =
+            std::env::temp_dir().join(format!("ssgit-mv-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let repo = Repository::init(&directory, false, Some("main")).unwrap();
+
+        let mut root = TreeNode::new_root();
+        for file in files {
+            let blob = GitObject::new(ObjectType::Blob, format!("{}\n", file).into_bytes());
+            root.push_full_path_blob(file, BlobType::Plain, blob.write(&repo).unwrap())
+                .unwrap();
+        }
+        let tree = root.write_recursive(&repo).unwrap();
+        worktree::checkout(&repo, None, Some(&tree)).unwrap();
+
+        repo
+    }
+
+    fn args(sources: &[&str], destination: &str) -> MvArgs {
+        MvArgs {
+            sources: sources.iter().map(|source| source.to_string()).collect(),
+            destination: destination.to_string(),
+            force: false,
+            dry_run: false,
+        }
+    }
+
+    fn index_names(repo: &Repository) -> Vec<String> {
+        Index::read(repo)
+            .unwrap()
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|entry| entry.file_name)
+            .collect()
+    }
+
+    #[test]
+    fn test_move_directory() {
+        let repo = init_repo("directory", &["dir/a", "dir/sub/b", "other/c", "d"]);
+
+        handle(&repo, &args(&["dir"], "renamed")).unwrap();
+        assert_eq!(
+            index_names(&repo),
+            ["d", "other/c", "renamed/a", "renamed/sub/b"]
+        );
+        assert!(!repo.work_tree_path("dir").unwrap().exists());
+        assert_eq!(
+            std::fs::read_to_string(repo.work_tree_path("renamed/sub/b").unwrap()).unwrap(),
+            "dir/sub/b\n"
+        );
+
+        // 既存のディレクトリへはその中に移動する
+        handle(&repo, &args(&["renamed", "d"], "other")).unwrap();
+        assert_eq!(
+            index_names(&repo),
+            [
+                "other/c",
+                "other/d",
+                "other/renamed/a",
+                "other/renamed/sub/b"
+            ]
+        );
+
+        std::fs::remove_dir_all(repo.work_tree().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_move_refusals() {
+        let repo = init_repo("refusals", &["dir/a", "b", "c"]);
+        let error = |sources: &[&str], destination: &str| {
+            handle(&repo, &args(sources, destination))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error(&["dir"], "dir/inner"),
+            "can not move directory into itself, source=dir, destination=dir/inner"
+        );
+        assert_eq!(
+            error(&["b", "c"], "missing"),
+            "destination 'missing' is not a directory"
+        );
+        assert_eq!(
+            error(&["b"], "c"),
+            "destination exists, source=b, destination=c"
+        );
+        std::fs::write(repo.work_tree_path("untracked").unwrap(), "").unwrap();
+        assert_eq!(
+            error(&["untracked"], "d"),
+            "not under version control, source=untracked, destination=d"
+        );
+        assert_eq!(index_names(&repo), ["b", "c", "dir/a"]);
+
+        std::fs::remove_dir_all(repo.work_tree().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,9 @@
+use crate::parser::PackRefsArgs;
+use anyhow::Result;
+use ssgit::entity::{packed_refs, repository::Repository};
+
+pub fn handle(repo: &Repository, args: &PackRefsArgs) -> Result<()> {
+    packed_refs::pack_refs(repo, args.all, !args.no_prune)?;
+
+    Ok(())
+}
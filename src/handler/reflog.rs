@@ -0,0 +1,139 @@
+use std::str::FromStr;
+
+use crate::parser::{ReflogArgs, ReflogCommands};
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, FixedOffset};
+use ssgit::entity::{
+    config::Config, date::parse_date, reflog::Reflog, repository::Repository, revision,
+};
+
+/// gc.reflogExpire がない場合に残す期間
+const DEFAULT_EXPIRE: &str = "90 days ago";
+
+pub fn handle(repo: &Repository, args: &ReflogArgs) -> Result<()> {
+    match &args.command {
+        None => show(repo, args.ref_.as_deref()),
+        Some(ReflogCommands::Show { ref_ }) => show(repo, ref_.as_deref()),
+        Some(ReflogCommands::Expire {
+            expire,
+            all,
+            dry_run,
+            refs,
+        }) => expire_reflogs(repo, expire.as_deref(), *all, *dry_run, refs),
+        Some(ReflogCommands::Delete { dry_run, entries }) => {
+            delete_entries(repo, *dry_run, entries)
+        }
+    }
+}
+
+/// 新しい順に `<hash> <ref>@{n}: <message>` の形で表示する
+fn show(repo: &Repository, ref_: Option<&str>) -> Result<()> {
+    let (name, start) = parse_selector(ref_.unwrap_or("HEAD"))?;
+    let name = if name.is_empty() { "HEAD" } else { name };
+
+    let log_name = Reflog::resolve_name(repo, name)?;
+    if !Reflog::exists(repo, &log_name) && revision::resolve(repo, name).is_err() {
+        bail!(
+            "ambiguous argument '{}': unknown revision or path not in the working tree.",
+            name
+        );
+    }
+
+    let reflog = Reflog::read(repo, &log_name)?;
+    for (n, entry) in reflog
+        .entries
+        .iter()
+        .rev()
+        .enumerate()
+        .skip(start.unwrap_or(0))
+    {
+        println!(
+            "{} {}@{{{}}}: {}",
+            &entry.new.to_string()[..7],
+            name,
+            n,
+            entry.message
+        );
+    }
+
+    Ok(())
+}
+
+fn expire_reflogs(
+    repo: &Repository,
+    expire: Option<&str>,
+    all: bool,
+    dry_run: bool,
+    refs: &[String],
+) -> Result<()> {
+    let expire = match expire {
+        Some(expire) => expire.to_string(),
+        None => Config::read(Some(repo))?
+            .get("gc.reflogExpire")
+            .unwrap_or(DEFAULT_EXPIRE)
+            .to_string(),
+    };
+    let Some(time) = parse_expire(&expire)? else {
+        return Ok(());
+    };
+
+    let names = if all {
+        Reflog::names(repo)?
+    } else {
+        refs.iter()
+            .map(|ref_| Reflog::resolve_name(repo, ref_))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    for name in names {
+        let mut reflog = Reflog::read(repo, &name)?;
+        let expired = match &time {
+            Some(time) => reflog.expire(time),
+            None => std::mem::take(&mut reflog.entries).len(),
+        };
+        if expired > 0 && !dry_run {
+            reflog.write(repo)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `all`, `now` なら全て (Some(None))、`never`, `false` なら何も消さない (None)
+fn parse_expire(expire: &str) -> Result<Option<Option<DateTime<FixedOffset>>>> {
+    Ok(match expire {
+        "never" | "false" => None,
+        "all" | "now" => Some(None),
+        expire => Some(Some(parse_date(expire)?)),
+    })
+}
+
+fn delete_entries(repo: &Repository, dry_run: bool, entries: &[String]) -> Result<()> {
+    for entry in entries {
+        let (name, n) = parse_selector(entry)?;
+        let n = n.ok_or(anyhow!("not a reflog: {}", entry))?;
+
+        let mut reflog = Reflog::read(repo, &Reflog::resolve_name(repo, name)?)?;
+        if reflog.remove_nth_latest(n).is_none() {
+            bail!("no reflog for '{}'", entry);
+        }
+        if !dry_run {
+            reflog.write(repo)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `<ref>@{n}` を ref と n に分ける
+fn parse_selector(selector: &str) -> Result<(&str, Option<usize>)> {
+    let Some((name, n)) = selector.split_once("@{") else {
+        return Ok((selector, None));
+    };
+    let n = n
+        .strip_suffix('}')
+        .and_then(|n| usize::from_str(n).ok())
+        .ok_or(anyhow!("invalid reflog selector '{}'", selector))?;
+
+    Ok((name, Some(n)))
+}
@@ -0,0 +1,98 @@
+use std::{collections::HashSet, str::FromStr};
+
+use crate::parser::RepackArgs;
+use anyhow::Result;
+use ssgit::{
+    consts::PACK_DIRECTORY,
+    entity::{
+        head::Head,
+        index::Index,
+        object::{hash::Hash, pack::Pack, GitObject, ObjectType},
+        refs::Ref,
+        repository::Repository,
+    },
+};
+
+pub fn handle(repo: &Repository, _args: &RepackArgs) -> Result<()> {
+    let mut roots = Vec::new();
+
+    if let Head::Detached(hash) = Head::read(repo)? {
+        roots.push(hash);
+    }
+    for ref_ in Ref::all(repo)? {
+        if let Some(hash) = ref_.read_hash(repo)? {
+            roots.push(Hash::from_str(&hash)?);
+        }
+    }
+    if let Some(index) = Index::read(repo)? {
+        roots.extend(index.entries.into_iter().map(|entry| entry.hash));
+    }
+
+    let loose_objects = collect_reachable_loose_objects(repo, roots)?;
+    if loose_objects.is_empty() {
+        println!("Nothing new to pack.");
+        return Ok(());
+    }
+
+    let pack = Pack::write(repo.git_path(PACK_DIRECTORY), &loose_objects)?;
+    repo.refresh_objects();
+
+    for object in &loose_objects {
+        let path = object.hash().get_object_path(repo);
+        std::fs::remove_file(&path)?;
+
+        // 空になったディレクトリも消す
+        let directory = path.parent().unwrap();
+        if std::fs::read_dir(directory)?.next().is_none() {
+            std::fs::remove_dir(directory)?;
+        }
+    }
+
+    println!(
+        "Packed {} objects into {}",
+        loose_objects.len(),
+        pack.pack_path.display()
+    );
+
+    Ok(())
+}
+
+/// roots から辿れるオブジェクトのうち、loose なものを集める
+fn collect_reachable_loose_objects(repo: &Repository, roots: Vec<Hash>) -> Result<Vec<GitObject>> {
+    let mut visited = HashSet::new();
+    let mut stack = roots;
+    let mut loose_objects = Vec::new();
+
+    while let Some(hash) = stack.pop() {
+        if !visited.insert(hash.clone()) {
+            continue;
+        }
+
+        let object = GitObject::read(repo, &hash)?;
+        match object.type_ {
+            ObjectType::Commit => {
+                let commit = object.parse_commit_body()?;
+                stack.push(commit.tree);
+                stack.extend(commit.parent);
+            }
+            ObjectType::Tree => {
+                stack.extend(
+                    object
+                        .parse_tree_body()?
+                        .into_iter()
+                        .map(|entry| entry.hash),
+                );
+            }
+            ObjectType::Tag => {
+                stack.push(object.parse_tag_body()?.object);
+            }
+            ObjectType::Blob => {}
+        }
+
+        if hash.get_object_path(repo).exists() {
+            loose_objects.push(object);
+        }
+    }
+
+    Ok(loose_objects)
+}
@@ -0,0 +1,108 @@
+use crate::parser::ResetArgs;
+use anyhow::{bail, Result};
+use ssgit::{
+    consts::ORIG_HEAD_PATH,
+    entity::{
+        head::Head,
+        merge::MergeState,
+        object::{hash::Hash, GitObject, ObjectType},
+        pathspec::Pathspec,
+        repository::Repository,
+        revision,
+        status::Status,
+        worktree,
+    },
+};
+
+pub fn handle(repo: &Repository, args: &ResetArgs) -> Result<()> {
+    let head = Head::read(repo)?;
+
+    // -- <paths> は HEAD (または指定された commit) の内容で index の entry だけを戻す
+    if !args.paths.is_empty() {
+        if args.soft || args.hard {
+            bail!(
+                "Cannot do {} reset with paths.",
+                if args.soft { "soft" } else { "hard" }
+            );
+        }
+
+        let commit = match &args.revision {
+            Some(revision) => Some(revision::resolve_commit(repo, revision)?),
+            None => head.read_hash(repo)?,
+        };
+        let pathspec = Pathspec::parse(repo, &args.paths)?;
+        worktree::reset_index(repo, tree_of(repo, commit.as_ref())?.as_ref(), &pathspec)?;
+        print_unstaged(repo)?;
+
+        return Ok(());
+    }
+
+    if args.soft && MergeState::read(repo)?.is_some() {
+        bail!("Cannot do a soft reset in the middle of a merge.");
+    }
+
+    // まだ commit がない branch で commit を省略したら空の tree に戻す
+    let current = head.read_hash(repo)?;
+    let target = match &args.revision {
+        Some(revision) => Some(revision::resolve_commit(repo, revision)?),
+        None => current.clone(),
+    };
+
+    if let Some(current) = &current {
+        std::fs::write(repo.git_path(ORIG_HEAD_PATH), format!("{}\n", current))?;
+    }
+    if let Some(target) = &target {
+        let message = format!(
+            "reset: moving to {}",
+            args.revision.as_deref().unwrap_or("HEAD")
+        );
+        head.update(repo, target, &message)?;
+    }
+
+    let tree = tree_of(repo, target.as_ref())?;
+    if args.hard {
+        worktree::reset_hard(repo, tree.as_ref())?;
+    } else if !args.soft {
+        worktree::reset_index(repo, tree.as_ref(), &Pathspec::default())?;
+    }
+    MergeState::remove(repo)?;
+
+    if args.hard {
+        match &target {
+            Some(hash) => {
+                let commit = GitObject::read(repo, hash)?.parse_commit_body()?;
+                let subject = commit.message.lines().next().unwrap_or_default();
+                println!("HEAD is now at {} {}", &hash.to_string()[..7], subject);
+            }
+            None => println!("HEAD is now at (no commit)"),
+        }
+    } else if !args.soft {
+        print_unstaged(repo)?;
+    }
+
+    Ok(())
+}
+
+fn tree_of(repo: &Repository, commit: Option<&Hash>) -> Result<Option<Hash>> {
+    commit
+        .map(|hash| Ok(GitObject::peel(repo, hash, ObjectType::Tree)?.0))
+        .transpose()
+}
+
+fn print_unstaged(repo: &Repository) -> Result<()> {
+    let status = Status::read(repo, &Pathspec::default())?;
+    let unstaged = status
+        .entries
+        .iter()
+        .filter_map(|entry| entry.unstaged().map(|kind| (kind, &entry.path)))
+        .collect::<Vec<_>>();
+
+    if !unstaged.is_empty() {
+        println!("Unstaged changes after reset:");
+        for (kind, path) in unstaged {
+            println!("{}\t{}", kind.short_code(), path);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use crate::parser::RevParseArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{object::hash::Hash, repository::Repository, revision};
+
+pub fn handle(repo: &Repository, args: &RevParseArgs) -> Result<()> {
+    if args.verify {
+        let [revision] = args.revisions.as_slice() else {
+            bail!("Needed a single revision");
+        };
+        let Ok(hash) = revision::resolve(repo, revision) else {
+            bail!("Needed a single revision");
+        };
+
+        println!("{}", format_hash(repo, &hash, args)?);
+        return Ok(());
+    }
+
+    for revision in &args.revisions {
+        if let Some(revision) = revision.strip_prefix('^') {
+            println!(
+                "^{}",
+                format_hash(repo, &revision::resolve(repo, revision)?, args)?
+            );
+        } else if let Some((left, right)) = revision.split_once("..") {
+            let left = if left.is_empty() { "HEAD" } else { left };
+            let right = if right.is_empty() { "HEAD" } else { right };
+
+            println!(
+                "{}",
+                format_hash(repo, &revision::resolve(repo, right)?, args)?
+            );
+            println!(
+                "^{}",
+                format_hash(repo, &revision::resolve(repo, left)?, args)?
+            );
+        } else {
+            println!(
+                "{}",
+                format_hash(repo, &revision::resolve(repo, revision)?, args)?
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn format_hash(repo: &Repository, hash: &Hash, args: &RevParseArgs) -> Result<String> {
+    match args.short {
+        Some(length) => revision::shortest_unique_prefix(repo, hash, length),
+        None => Ok(hash.to_string()),
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::BTreeSet;
+
+use crate::parser::RmArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{
+    head::Head, index::Index, object::mode::Mode, pathspec::Pathspec, repository::Repository,
+    worktree,
+};
+
+pub fn handle(repo: &Repository, args: &RmArgs) -> Result<()> {
+    let pathspec = Pathspec::parse(repo, &args.paths)?;
+    let mut index = Index::read(repo)?.unwrap_or_else(Index::new);
+
+    let files = index
+        .entries
+        .iter()
+        .map(|entry| entry.file_name.clone())
+        .filter(|name| pathspec.matches(name))
+        .collect::<BTreeSet<_>>();
+
+    for item in pathspec.items.iter().filter(|item| !item.exclude) {
+        let matched = files
+            .iter()
+            .filter(|name| item.matches(name))
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            bail!("pathspec '{}' did not match any files", item.original);
+        }
+        // ディレクトリの指定で中のファイルを消すには -r が必要
+        if !args.recursive && !item.has_glob() && matched.iter().all(|name| **name != item.path) {
+            bail!("not removing '{}' recursively without -r", item.original);
+        }
+    }
+
+    if !args.force {
+        check_local_changes(repo, &index, &files, args.cached)?;
+    }
+
+    for file in &files {
+        index.remove(file);
+        if !args.cached {
+            worktree::remove_file(repo, file)?;
+        }
+        if !args.quiet {
+            println!("rm '{}'", file);
+        }
+    }
+    index.write(repo)?;
+
+    Ok(())
+}
+
+/// 消すと失われる変更があればエラーにする
+///
+/// --cached では index の内容が HEAD とも作業ツリーとも異なる場合だけエラーにする
+fn check_local_changes(
+    repo: &Repository,
+    index: &Index,
+    files: &BTreeSet<String>,
+    cached: bool,
+) -> Result<()> {
+    let head_files =
+        worktree::read_tree_files(repo, Head::read(repo)?.read_tree_hash(repo)?.as_ref())?;
+    let entries = index.hash_map();
+
+    let mut both = Vec::new();
+    let mut staged = Vec::new();
+    let mut modified = Vec::new();
+    for file in files {
+        let entry = entries[file.as_str()];
+        // 衝突中のパスは消して解消できるようにする
+        if entry.stage != 0 {
+            continue;
+        }
+        // 作業ツリーから消えているなら失われる変更はない
+        let Some(worktree_file) = worktree::hash_file(repo, file)? else {
+            continue;
+        };
+
+        let Mode::Blob(blob_type) = entry.mode else {
+            unreachable!("Unexpected tree entry in index: {:?}", entry);
+        };
+        let index_file = (blob_type, entry.hash.clone());
+        let is_staged = head_files.get(file) != Some(&index_file);
+        let is_modified = worktree_file != index_file;
+
+        if is_staged && is_modified {
+            both.push(file.as_str());
+        } else if cached {
+            continue;
+        } else if is_staged {
+            staged.push(file.as_str());
+        } else if is_modified {
+            modified.push(file.as_str());
+        }
+    }
+
+    let mut messages = Vec::new();
+    if !both.is_empty() {
+        messages.push(format_error(
+            &both,
+            "staged content different from both the file and the HEAD",
+            "(use -f to force removal)",
+        ));
+    }
+    if !staged.is_empty() {
+        messages.push(format_error(
+            &staged,
+            "changes staged in the index",
+            "(use --cached to keep the file, or -f to force removal)",
+        ));
+    }
+    if !modified.is_empty() {
+        messages.push(format_error(
+            &modified,
+            "local modifications",
+            "(use --cached to keep the file, or -f to force removal)",
+        ));
+    }
+    if !messages.is_empty() {
+        bail!("{}", messages.join("\n"));
+    }
+
+    Ok(())
+}
+
+fn format_error(files: &[&str], reason: &str, hint: &str) -> String {
+    let subject = if files.len() == 1 {
+        "file has"
+    } else {
+        "files have"
+    };
+    let files = files
+        .iter()
+        .map(|file| format!("    {}", file))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!("the following {} {}:\n{}\n{}", subject, reason, files, hint)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone};
+    use ssgit::entity::{
+        index::IndexEntry,
+        object::{mode::BlobType, Commit, GitObject, ObjectType, User},
+        tree::TreeNode,
+    };
+
+    use super::*;
+
+    /// files を同じ内容で HEAD の commit と index と作業ツリーに置く
+    fn init_repo(name: &str, files: &[&str]) -> Repository {
+        let directory =
+            std::env::temp_dir().join(format!("ssgit-rm-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let repo = Repository::init(&directory, false, Some("main")).unwrap();
+
+        let mut root = TreeNode::new_root();
+        for file in files {
+            let blob = GitObject::new(ObjectType::Blob, format!("{}\n", file).into_bytes());
+            root.push_full_path_blob(file, BlobType::Plain, blob.write(&repo).unwrap())
+                .unwrap();
+        }
+        let tree = root.write_recursive(&repo).unwrap();
+        let user = User::new(
+            "a".to_string(),
+            "a@a".to_string(),
+            FixedOffset::east_opt(0)
+                .unwrap()
+                .timestamp_opt(1_700_000_000, 0)
+                .unwrap(),
+        );
+        let commit = GitObject::from_commit(&Commit {
+            tree: tree.clone(),
+            parent: Vec::new(),
+            author: user.clone(),
+            committer: user,
+            rest_of_header: String::new(),
+            message: "initial\n".to_string(),
+        })
+        .write(&repo)
+        .unwrap();
+        Head::read(&repo)
+            .unwrap()
+            .update(&repo, &commit, "commit (initial): initial")
+            .unwrap();
+        worktree::checkout(&repo, None, Some(&tree)).unwrap();
+
+        repo
+    }
+
+    /// 作業ツリーのファイルを書き換え、stage なら index にも入れる
+    fn modify(repo: &Repository, file: &str, content: &str, stage: bool) {
+        std::fs::write(repo.work_tree_path(file).unwrap(), content).unwrap();
+        if stage {
+            let (blob_type, hash) = worktree::hash_file(repo, file).unwrap().unwrap();
+            let mut index = Index::read(repo).unwrap().unwrap();
+            index.insert(&[IndexEntry::with_default(Mode::Blob(blob_type), hash, file)]);
+            index.write(repo).unwrap();
+        }
+    }
+
+    fn check(repo: &Repository, file: &str, cached: bool) -> Result<()> {
+        let index = Index::read(repo).unwrap().unwrap();
+        check_local_changes(repo, &index, &BTreeSet::from([file.to_string()]), cached)
+    }
+
+    fn args(paths: &[&str], recursive: bool) -> RmArgs {
+        RmArgs {
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            cached: false,
+            recursive,
+            force: false,
+            quiet: true,
+        }
+    }
+
+    #[test]
+    fn test_check_local_changes() {
+        let repo = init_repo(
+            "changes",
+            &["clean", "modified", "staged", "both", "deleted"],
+        );
+        modify(&repo, "modified", "changed\n", false);
+        modify(&repo, "staged", "changed\n", true);
+        modify(&repo, "both", "changed\n", true);
+        modify(&repo, "both", "changed again\n", false);
+        std::fs::remove_file(repo.work_tree_path("deleted").unwrap()).unwrap();
+        modify(&repo, "new", "new\n", true);
+
+        let error = |file: &str, cached: bool| {
+            check(&repo, file, cached)
+                .err()
+                .map(|error| error.to_string().lines().next().unwrap().to_string())
+        };
+
+        for file in ["clean", "deleted"] {
+            assert_eq!(error(file, false), None);
+            assert_eq!(error(file, true), None);
+        }
+        assert_eq!(
+            error("modified", false).as_deref(),
+            Some("the following file has local modifications:")
+        );
+        assert_eq!(error("modified", true), None);
+        for file in ["staged", "new"] {
+            assert_eq!(
+                error(file, false).as_deref(),
+                Some("the following file has changes staged in the index:")
+            );
+            assert_eq!(error(file, true), None);
+        }
+        for cached in [false, true] {
+            assert_eq!(
+                error("both", cached).as_deref(),
+                Some(
+                    "the following file has staged content different from both the file and the HEAD:"
+                )
+            );
+        }
+
+        std::fs::remove_dir_all(repo.work_tree().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_remove_directory_requires_recursive() {
+        let repo = init_repo("recursive", &["dir/a", "dir/sub/b", "c"]);
+
+        let error = handle(&repo, &args(&["dir"], false)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "not removing 'dir' recursively without -r"
+        );
+        assert!(repo.work_tree_path("dir/a").unwrap().exists());
+
+        handle(&repo, &args(&["dir"], true)).unwrap();
+        let index = Index::read(&repo).unwrap().unwrap();
+        let names = index
+            .entries
+            .iter()
+            .map(|entry| entry.file_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["c"]);
+        assert!(!repo.work_tree_path("dir").unwrap().exists());
+
+        std::fs::remove_dir_all(repo.work_tree().unwrap()).unwrap();
+    }
+}
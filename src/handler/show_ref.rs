@@ -0,0 +1,82 @@
+use crate::parser::ShowRefArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{
+    object::hash::Hash,
+    packed_refs::peel_tag,
+    refs::{read_ref, ref_names},
+    repository::Repository,
+};
+
+pub fn handle(repo: &Repository, args: &ShowRefArgs) -> Result<()> {
+    let mut refs = Vec::new();
+
+    if args.verify {
+        // --verify では完全な名前を 1 つずつ確かめる
+        for name in &args.patterns {
+            let hash = if name == "HEAD" || name.starts_with("refs/") {
+                read_ref(repo, name)?
+            } else {
+                None
+            };
+            match hash {
+                Some(hash) => refs.push((name.clone(), hash)),
+                None if args.quiet => std::process::exit(1),
+                None => bail!("'{}' - not a valid ref", name),
+            }
+        }
+    } else {
+        if args.head {
+            if let Some(hash) = read_ref(repo, "HEAD")? {
+                refs.push(("HEAD".to_string(), hash));
+            }
+        }
+        for name in ref_names(repo, "refs/")? {
+            let selected = (!args.heads && !args.tags)
+                || (args.heads && name.starts_with("refs/heads/"))
+                || (args.tags && name.starts_with("refs/tags/"));
+            if !selected || !matches_pattern(&args.patterns, &name) {
+                continue;
+            }
+            if let Some(hash) = read_ref(repo, &name)? {
+                refs.push((name, hash));
+            }
+        }
+    }
+
+    if refs.is_empty() {
+        std::process::exit(1);
+    }
+    if args.quiet {
+        return Ok(());
+    }
+
+    for (name, hash) in refs {
+        print_ref(&name, &hash, args.hash);
+        if args.dereference {
+            if let Some(peeled) = peel_tag(repo, &hash)? {
+                print_ref(&format!("{}^{{}}", name), &peeled, args.hash);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// パターンは refname の末尾の `/` 区切りの部分に一致すればよい
+fn matches_pattern(patterns: &[String], name: &str) -> bool {
+    patterns.is_empty()
+        || patterns.iter().any(|pattern| {
+            name == pattern
+                || name
+                    .strip_suffix(pattern.as_str())
+                    .is_some_and(|rest| rest.ends_with('/'))
+        })
+}
+
+fn print_ref(name: &str, hash: &Hash, hash_only: bool) {
+    if hash_only {
+        println!("{}", hash);
+    } else {
+        println!("{} {}", hash, name);
+    }
+}
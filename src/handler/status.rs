@@ -0,0 +1,172 @@
+use crate::parser::{PorcelainVersion, StatusArgs};
+use anyhow::Result;
+use ssgit::entity::{
+    head::Head,
+    merge::MergeState,
+    object::{hash::Hash, mode::Mode},
+    pathspec::Pathspec,
+    refs::Ref,
+    repository::Repository,
+    status::{Status, StatusEntry},
+};
+
+const ZERO_HASH: &str = "0000000000000000000000000000000000000000";
+
+pub fn handle(repo: &Repository, args: &StatusArgs) -> Result<()> {
+    let pathspec = Pathspec::parse(repo, &args.paths)?;
+    let status = Status::read(repo, &pathspec)?;
+
+    match args.porcelain {
+        Some(PorcelainVersion::V1) => print_porcelain_v1(&status),
+        Some(PorcelainVersion::V2) => print_porcelain_v2(&status),
+        None => print_long(repo, &status)?,
+    }
+
+    Ok(())
+}
+
+fn print_long(repo: &Repository, status: &Status) -> Result<()> {
+    let head = Head::read(repo)?;
+    match &head {
+        Head::Ref(Ref::Branch(branch)) => println!("On branch {}", branch),
+        Head::Ref(ref_) => println!("On {}", ref_),
+        Head::Detached(hash) => println!("HEAD detached at {}", &hash.to_string()[..7]),
+    }
+    if MergeState::read(repo)?.is_some() {
+        if status.unmerged.is_empty() {
+            println!("All conflicts fixed but you are still merging.");
+        } else {
+            println!("You have unmerged paths.");
+        }
+    }
+    if head.read_hash(repo)?.is_none() {
+        println!("\nNo commits yet");
+    }
+
+    let staged = status
+        .entries
+        .iter()
+        .filter_map(|entry| entry.staged().map(|kind| (kind, &entry.path)))
+        .collect::<Vec<_>>();
+    if !staged.is_empty() {
+        println!("\nChanges to be committed:");
+        for (kind, path) in &staged {
+            println!("\t{:<12}{}", format!("{}:", kind), path);
+        }
+    }
+
+    if !status.unmerged.is_empty() {
+        println!("\nUnmerged paths:");
+        for entry in &status.unmerged {
+            println!("\t{:<17}{}", format!("{}:", entry.kind()), entry.path);
+        }
+    }
+
+    let unstaged = status
+        .entries
+        .iter()
+        .filter_map(|entry| entry.unstaged().map(|kind| (kind, &entry.path)))
+        .collect::<Vec<_>>();
+    if !unstaged.is_empty() {
+        println!("\nChanges not staged for commit:");
+        for (kind, path) in &unstaged {
+            println!("\t{:<12}{}", format!("{}:", kind), path);
+        }
+    }
+
+    if !status.untracked.is_empty() {
+        println!("\nUntracked files:");
+        for path in &status.untracked {
+            println!("\t{}", path);
+        }
+    }
+
+    println!();
+    if status.is_clean() {
+        println!("nothing to commit, working tree clean");
+    } else if staged.is_empty() {
+        if unstaged.is_empty() && status.unmerged.is_empty() {
+            println!("nothing added to commit but untracked files present");
+        } else {
+            println!("no changes added to commit");
+        }
+    }
+
+    Ok(())
+}
+
+fn short_codes(entry: &StatusEntry, unchanged: char) -> String {
+    let staged = entry.staged().map_or(unchanged, |kind| kind.short_code());
+    let unstaged = entry.unstaged().map_or(unchanged, |kind| kind.short_code());
+
+    format!("{}{}", staged, unstaged)
+}
+
+fn print_porcelain_v1(status: &Status) {
+    // 衝突中のパスも他の変更と同じくパス順に並べる
+    let mut lines = status
+        .entries
+        .iter()
+        .map(|entry| {
+            (
+                &entry.path,
+                format!("{} {}", short_codes(entry, ' '), entry.path),
+            )
+        })
+        .chain(status.unmerged.iter().map(|entry| {
+            (
+                &entry.path,
+                format!("{} {}", entry.kind().short_code(), entry.path),
+            )
+        }))
+        .collect::<Vec<_>>();
+    lines.sort();
+    for (_, line) in lines {
+        println!("{}", line);
+    }
+    for path in &status.untracked {
+        println!("?? {}", path);
+    }
+}
+
+fn print_porcelain_v2(status: &Status) {
+    let format_mode = |file: Option<&(_, Hash)>| {
+        file.map_or("000000".to_string(), |(blob_type, _)| {
+            format!("{:06o}", u32::from(Mode::Blob(*blob_type)))
+        })
+    };
+    let format_hash =
+        |file: Option<&(_, Hash)>| file.map_or(ZERO_HASH.to_string(), |(_, hash)| hash.to_string());
+
+    for entry in &status.entries {
+        println!(
+            "1 {} N... {} {} {} {} {} {}",
+            short_codes(entry, '.'),
+            format_mode(entry.head.as_ref()),
+            format_mode(entry.index.as_ref()),
+            format_mode(entry.worktree.as_ref()),
+            format_hash(entry.head.as_ref()),
+            format_hash(entry.index.as_ref()),
+            entry.path
+        );
+    }
+    // 衝突中のパスは通常の変更の後にまとめて出す
+    for entry in &status.unmerged {
+        let [base, ours, theirs] = &entry.stages;
+        println!(
+            "u {} N... {} {} {} {} {} {} {} {}",
+            entry.kind().short_code(),
+            format_mode(base.as_ref()),
+            format_mode(ours.as_ref()),
+            format_mode(theirs.as_ref()),
+            format_mode(entry.worktree.as_ref()),
+            format_hash(base.as_ref()),
+            format_hash(ours.as_ref()),
+            format_hash(theirs.as_ref()),
+            entry.path
+        );
+    }
+    for path in &status.untracked {
+        println!("? {}", path);
+    }
+}
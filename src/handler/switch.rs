@@ -1,15 +1,16 @@
-use std::str::FromStr;
-
-use crate::{
-    entity::{head::Head, object::hash::Hash, refs::Ref},
-    parser::SwitchArgs,
-};
+use crate::parser::SwitchArgs;
 use anyhow::{bail, Result};
+use ssgit::entity::{
+    head::Head,
+    object::{hash::Hash, GitObject, ObjectType},
+    refs::Ref,
+    repository::Repository,
+    revision, worktree,
+};
 
-pub fn handle(args: &SwitchArgs) -> Result<()> {
-    dbg!(args);
-
-    // TODO: ブランチに応じたファイル内容の変更
+pub fn handle(repo: &Repository, args: &SwitchArgs) -> Result<()> {
+    let current_head = Head::read(repo)?;
+    let current_tree = current_head.read_tree_hash(repo)?;
 
     match (
         &args.options.switch,
@@ -19,57 +20,57 @@ pub fn handle(args: &SwitchArgs) -> Result<()> {
     ) {
         (Some(branch), None, None, None) => {
             let ref_ = Ref::Branch(branch.clone());
-            if !ref_.to_path().exists() {
+            if !ref_.exists(repo)? {
                 bail!("Branch {} does not exist", branch);
             }
 
+            let target_tree = tree_of(repo, ref_.read_commit_hash(repo)?)?;
+            worktree::checkout(repo, current_tree.as_ref(), target_tree.as_ref())?;
+
             let head = Head::Ref(ref_);
-            head.write()?;
+            head.switch(repo, &moving_message(&current_head, branch))?;
         }
         // create
         (None, Some(branch), None, None) => {
             let ref_ = Ref::Branch(branch.clone());
-            if ref_.to_path().exists() {
+            if ref_.check_format().is_err() {
+                bail!("'{}' is not a valid branch name", branch);
+            }
+            if ref_.exists(repo)? {
                 bail!("Branch {} already exists", branch);
             }
 
-            let current_head = Head::read()?;
-            let hash = {
-                if let Head::Ref(ref_) = current_head {
-                    match &ref_ {
-                        Ref::Branch(_) => ref_.read_hash()?,
-                        Ref::Tag(_) => {
-                            bail!("Tag is not supported");
-                        }
-                    }
-                } else {
-                    None
-                }
-            };
-
-            if let Some(hash) = hash {
-                ref_.write_hash(&hash)?;
+            if let Some(hash) = current_head.read_hash(repo)? {
+                ref_.update(repo, &hash, "branch: Created from HEAD")?;
             }
 
             let head = Head::Ref(ref_);
-            head.write()?;
+            head.switch(repo, &moving_message(&current_head, branch))?;
         }
         // orphan
         (None, None, Some(branch), None) => {
             let ref_ = Ref::Branch(branch.clone());
-            if ref_.to_path().exists() {
+            if ref_.check_format().is_err() {
+                bail!("'{}' is not a valid branch name", branch);
+            }
+            if ref_.exists(repo)? {
                 bail!("Branch {} already exists", branch);
             }
 
+            worktree::checkout(repo, current_tree.as_ref(), None)?;
+
             let head = Head::Ref(ref_);
-            head.write()?;
+            head.switch(repo, &moving_message(&current_head, branch))?;
         }
         // detach
-        (None, None, None, Some(hash)) => {
-            let hash = Hash::from_str(hash)?;
-            let head = Head::Detached(hash);
+        (None, None, None, Some(revision)) => {
+            let hash = revision::resolve_commit(repo, revision)?;
+
+            let target_tree = tree_of(repo, Some(hash.clone()))?;
+            worktree::checkout(repo, current_tree.as_ref(), target_tree.as_ref())?;
 
-            head.write()?;
+            let head = Head::Detached(hash);
+            head.switch(repo, &moving_message(&current_head, revision))?;
         }
         _ => {
             bail!("Invalid options");
@@ -78,3 +79,21 @@ pub fn handle(args: &SwitchArgs) -> Result<()> {
 
     Ok(())
 }
+
+/// HEAD の reflog に残すメッセージ (detached なら元の commit の hash から)
+fn moving_message(current: &Head, to: &str) -> String {
+    let from = match current {
+        Head::Ref(Ref::Branch(branch)) => branch.clone(),
+        Head::Ref(ref_) => ref_.to_string(),
+        Head::Detached(hash) => hash.to_string(),
+    };
+
+    format!("checkout: moving from {} to {}", from, to)
+}
+
+fn tree_of(repo: &Repository, commit: Option<Hash>) -> Result<Option<Hash>> {
+    let tree = commit
+        .map(|hash| GitObject::peel(repo, &hash, ObjectType::Tree).map(|(hash, _)| hash))
+        .transpose()?;
+    Ok(tree)
+}
@@ -0,0 +1,71 @@
+use std::str::FromStr;
+
+use crate::parser::SymbolicRefArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{
+    refs::{check_ref_format, delete_symref, read_symref, resolve_symref, write_symref, Ref},
+    repository::Repository,
+};
+
+pub fn handle(repo: &Repository, args: &SymbolicRefArgs) -> Result<()> {
+    let name = args.name.as_str();
+    if check_ref_format(name, true, false).is_err() {
+        bail!("invalid ref name '{}'", name);
+    }
+
+    if args.delete {
+        if name == "HEAD" {
+            bail!("deleting '{}' is not allowed", name);
+        }
+        if read_symref(repo, name)?.is_none() {
+            return not_symbolic(name, args.quiet);
+        }
+        delete_symref(repo, name)?;
+        return Ok(());
+    }
+
+    let Some(target) = &args.target else {
+        // 既定では symref を最後まで辿り、--no-recurse なら 1 段だけ辿る
+        let target = if args.no_recurse {
+            read_symref(repo, name)?
+        } else {
+            Some(resolve_symref(repo, name)?).filter(|target| target != name)
+        };
+        let Some(target) = target else {
+            return not_symbolic(name, args.quiet);
+        };
+
+        if args.short {
+            match Ref::from_str(&target) {
+                Ok(ref_) => println!("{}", ref_.short_name()),
+                Err(_) => println!("{}", target),
+            }
+        } else {
+            println!("{}", target);
+        }
+        return Ok(());
+    };
+
+    if name == "HEAD" && !target.starts_with("refs/") {
+        bail!("Refusing to point HEAD outside of refs/");
+    }
+    if check_ref_format(target, true, false).is_err() {
+        bail!("Refusing to set '{}' to invalid ref '{}'", name, target);
+    }
+    write_symref(
+        repo,
+        name,
+        target,
+        args.message.as_deref().unwrap_or_default(),
+    )?;
+
+    Ok(())
+}
+
+/// -q なら何も表示せずに終了コード 1 で終わる
+fn not_symbolic(name: &str, quiet: bool) -> Result<()> {
+    if quiet {
+        std::process::exit(1);
+    }
+    bail!("ref {} is not a symbolic ref", name);
+}
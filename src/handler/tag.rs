@@ -0,0 +1,79 @@
+use std::str::FromStr;
+
+use crate::parser::TagArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{
+    head::Head,
+    object::{hash::Hash, GitObject, Tag, User},
+    refs::Ref,
+    repository::Repository,
+};
+
+pub fn handle(repo: &Repository, args: &TagArgs) -> Result<()> {
+    let Some(name) = args.name.as_ref().filter(|_| !args.list) else {
+        let mut tags = Ref::tag_names(repo)?;
+        tags.sort();
+        for tag in tags {
+            println!("{}", tag);
+        }
+        return Ok(());
+    };
+
+    let ref_ = Ref::Tag(name.clone());
+
+    if args.delete {
+        let Some(hash) = ref_.read_hash(repo)? else {
+            bail!("tag '{}' not found.", name);
+        };
+        ref_.delete(repo)?;
+        println!("Deleted tag '{}' (was {})", name, &hash[..7]);
+        return Ok(());
+    }
+
+    if ref_.check_format().is_err() {
+        bail!("'{}' is not a valid tag name.", name);
+    }
+    if ref_.exists(repo)? && !args.force {
+        bail!("tag '{}' already exists", name);
+    }
+
+    let target = match &args.object {
+        Some(object) => Hash::from_str(object)?,
+        None => Head::read(repo)?
+            .read_hash(repo)?
+            .ok_or(anyhow::anyhow!("Failed to resolve 'HEAD' as a valid ref."))?,
+    };
+    let target_object = GitObject::read(repo, &target)?;
+
+    let hash = if args.annotate || !args.message.is_empty() {
+        let message = if args.message.is_empty() {
+            std::io::stdin()
+                .lines()
+                .map(|line| line.map(|line| line + "\n").map_err(|e| e.into()))
+                .collect::<Result<String>>()?
+        } else {
+            args.message.join("\n\n") + "\n"
+        };
+
+        let tag = Tag {
+            object: target,
+            type_: target_object.type_,
+            tag: name.clone(),
+            tagger: Some(User::committer(repo)?),
+            message,
+        };
+
+        let git_object = GitObject::from_tag(&tag);
+        if !GitObject::exists(repo, &git_object.hash())? {
+            git_object.write(repo)?;
+        }
+
+        git_object.hash()
+    } else {
+        target
+    };
+
+    ref_.write_hash(repo, &hash.to_string())?;
+
+    Ok(())
+}
@@ -1,26 +1,20 @@
 use std::{path::Path, str::FromStr};
 
-use crate::{
-    entity::{
-        index::{Index, IndexEntry},
-        object::{hash::Hash, mode::Mode, GitObject},
-    },
-    parser::UpdateIndexArgs,
-};
+use crate::parser::UpdateIndexArgs;
 use anyhow::{bail, Result};
+use ssgit::entity::{
+    index::{Index, IndexEntry},
+    object::{hash::Hash, mode::Mode, GitObject},
+    pathspec::Pathspec,
+    repository::Repository,
+};
 
-pub fn handle(args: &UpdateIndexArgs) -> Result<()> {
-    dbg!(args);
-
-    if args.remove && args.add {
+pub fn handle(repo: &Repository, args: &UpdateIndexArgs) -> Result<()> {
+    if (args.remove || args.force_remove) && args.add {
         bail!("Cannot use --add and --remove together")
     }
 
-    if args.remove {
-        unimplemented!();
-    }
-
-    let mut index = if let Some(index) = Index::read()? {
+    let mut index = if let Some(index) = Index::read(repo)? {
         index
     } else {
         Index::new()
@@ -35,35 +29,42 @@ pub fn handle(args: &UpdateIndexArgs) -> Result<()> {
         let mode = Mode::from_str(&cache_info[0])?;
         let hash = Hash::from_str(&cache_info[1])?;
         let file_name = &cache_info[2];
+        let name = repo.relative_path(file_name)?;
 
         let file_path = Path::new(file_name);
         let entry = if file_path.exists() {
-            IndexEntry::with_default(mode, hash, file_name)
+            IndexEntry::with_default(mode, hash, &name)
         } else {
             let metadata = file_path.metadata()?;
-            IndexEntry::with_file_metadata(mode, hash, file_name, &metadata)?
+            IndexEntry::with_file_metadata(mode, hash, &name, &metadata)?
         };
 
         entries.push(entry);
     }
 
-    for file_name in &args.file {
-        let file_path = Path::new(file_name);
+    let pathspec = Pathspec::parse(repo, &args.file)?;
+    for name in expand_paths(&index, &pathspec) {
+        let file_path = repo.work_tree_path(&name)?;
+        // --remove は作業ツリーから消えているものだけ、--force-remove は常に index から取り除く
+        if args.force_remove || (args.remove && file_path.symlink_metadata().is_err()) {
+            index.remove(&name);
+            continue;
+        }
         if !file_path.exists() {
-            bail!("File {} does not exist", file_name);
+            bail!("File {} does not exist", name);
         }
 
         let metadata = file_path.metadata()?;
         if metadata.is_dir() {
-            bail!("Cannot add directory {}", file_name);
+            bail!("Cannot add directory {}", name);
         }
 
         let mode = Mode::from_file_metadata(&metadata)?;
 
-        let content = std::fs::read(file_name)?;
-        let hash = GitObject::new(crate::entity::object::ObjectType::Blob, content).hash();
+        let content = std::fs::read(&file_path)?;
+        let hash = GitObject::new(ssgit::entity::object::ObjectType::Blob, content).hash();
 
-        let entry = IndexEntry::with_file_metadata(mode, hash, file_name, &metadata)?;
+        let entry = IndexEntry::with_file_metadata(mode, hash, &name, &metadata)?;
 
         entries.push(entry);
     }
@@ -84,7 +85,29 @@ pub fn handle(args: &UpdateIndexArgs) -> Result<()> {
 
     index.insert(&entries);
 
-    index.write()?;
+    index.write(repo)?;
 
     Ok(())
 }
+
+/// glob などを含む指定は index にあるパスに展開し、それ以外は指定されたパスをそのまま使う
+fn expand_paths(index: &Index, pathspec: &Pathspec) -> Vec<String> {
+    let mut names = Vec::new();
+    for item in pathspec.items.iter().filter(|item| !item.exclude) {
+        if item.has_glob() || item.icase || item.path.is_empty() {
+            names.extend(
+                index
+                    .entries
+                    .iter()
+                    .map(|entry| &entry.file_name)
+                    .filter(|name| item.matches(name) && pathspec.matches(name))
+                    .cloned(),
+            );
+        } else if pathspec.matches(&item.path) {
+            names.push(item.path.clone());
+        }
+    }
+    names.dedup();
+
+    names
+}
@@ -1,51 +1,115 @@
-use std::str::FromStr;
+use std::io::BufRead;
 
-use crate::{
-    entity::{head::Head, object::hash::Hash, refs::Ref},
-    parser::UpdateRefArgs,
+use crate::parser::UpdateRefArgs;
+use ssgit::entity::{
+    object::hash::Hash, ref_transaction::RefTransaction, refs::Ref, repository::Repository,
+    revision,
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 
-#[derive(Debug, Clone)]
-enum RefType {
-    Head,
-    Ref(Ref),
-}
-impl FromStr for RefType {
-    type Err = anyhow::Error;
+pub fn handle(repo: &Repository, args: &UpdateRefArgs) -> Result<()> {
+    let message = args.message.as_deref().unwrap_or_default();
+    let mut transaction = RefTransaction::new();
 
-    fn from_str(s: &str) -> Result<Self> {
-        if s == "HEAD" {
-            Ok(RefType::Head)
+    if args.stdin {
+        for line in std::io::stdin().lock().lines() {
+            let line = line?;
+            if !line.is_empty() {
+                parse_command(repo, &mut transaction, &line, message)?;
+            }
+        }
+    } else {
+        let name = args
+            .ref_
+            .as_deref()
+            .expect("ref is required without --stdin");
+        check_name(name)?;
+
+        if args.delete {
+            if args.oldvalue.is_some() {
+                bail!("usage: ssgit update-ref -d <refname> [<old-val>]");
+            }
+            let old = args
+                .newvalue
+                .as_deref()
+                .map(|old| parse_value(repo, old))
+                .transpose()?;
+            transaction.delete(name, old, message);
         } else {
-            Ok(RefType::Ref(s.parse()?))
+            let new = args.newvalue.as_deref().ok_or(anyhow!(
+                "usage: ssgit update-ref <refname> <new-val> [<old-val>]"
+            ))?;
+            let new = parse_value(repo, new)?.ok_or(anyhow!("{}: not a valid SHA1", new))?;
+            let old = args
+                .oldvalue
+                .as_deref()
+                .map(|old| parse_value(repo, old))
+                .transpose()?;
+            transaction.update(name, new, old, message);
         }
     }
+
+    transaction.commit(repo)?;
+
+    Ok(())
 }
 
-pub fn handle(args: &UpdateRefArgs) -> Result<()> {
-    let ref_type: RefType = args.ref_.parse()?;
-    let new_hash: Hash = args.newvalue.parse()?;
-
-    match ref_type {
-        RefType::Head => {
-            let head = Head::read()?;
-
-            match head {
-                Head::Detached(_) => {
-                    let new_head = Head::Detached(new_hash);
-                    new_head.write()?;
-                }
-                Head::Ref(ref_) => {
-                    ref_.write_hash(&new_hash.to_string())?;
-                }
-            }
+/// `update <ref> <new> [<old>]`, `create <ref> <new>`, `delete <ref> [<old>]`, `verify <ref> [<old>]` の 1 行を読む
+fn parse_command(
+    repo: &Repository,
+    transaction: &mut RefTransaction,
+    line: &str,
+    message: &str,
+) -> Result<()> {
+    let mut words = line.split(' ');
+    let command = words.next().unwrap_or_default();
+    let name = words
+        .next()
+        .filter(|name| !name.is_empty())
+        .ok_or(anyhow!("{}: missing <ref>", command))?;
+    check_name(name)?;
+    let values = words
+        .map(|value| parse_value(repo, value))
+        .collect::<Result<Vec<_>>>()?;
+
+    match (command, values.as_slice()) {
+        ("update", [Some(new)]) => transaction.update(name, new.clone(), None, message),
+        ("update", [Some(new), old]) => {
+            transaction.update(name, new.clone(), Some(old.clone()), message)
         }
-        RefType::Ref(ref_) => {
-            ref_.write_hash(&new_hash.to_string())?;
+        // 新しい値が 0 の update は削除として扱う
+        ("update", [None]) => transaction.delete(name, None, message),
+        ("update", [None, old]) => transaction.delete(name, Some(old.clone()), message),
+        ("create", [Some(new)]) => transaction.update(name, new.clone(), Some(None), message),
+        ("create", [None]) => bail!("create {}: zero <newvalue>", name),
+        ("delete", []) => transaction.delete(name, None, message),
+        ("delete", [old @ Some(_)]) => transaction.delete(name, Some(old.clone()), message),
+        ("delete", [None]) => bail!("delete {}: zero <oldvalue>", name),
+        ("verify", []) => transaction.verify(name, None),
+        ("verify", [old]) => transaction.verify(name, old.clone()),
+        ("update" | "create" | "delete" | "verify", _) => {
+            bail!("{} {}: wrong number of arguments", command, name)
         }
-    };
+        _ => bail!("unknown command: {}", line),
+    }
+
+    Ok(())
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name != "HEAD" {
+        name.parse::<Ref>()?;
+    }
 
     Ok(())
 }
+
+/// 空文字列か 0 が並んだ hash は「存在しない」を表す
+fn parse_value(repo: &Repository, value: &str) -> Result<Option<Hash>> {
+    if value.bytes().all(|b| b == b'0') {
+        return Ok(None);
+    }
+
+    Ok(Some(revision::resolve(repo, value)?))
+}
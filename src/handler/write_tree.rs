@@ -1,17 +1,16 @@
-use crate::{
-    entity::{index::Index, tree::TreeNode},
-    parser::WriteTreeArgs,
-};
-use anyhow::Result;
+use crate::parser::WriteTreeArgs;
+use anyhow::{bail, Result};
+use ssgit::entity::{index::Index, repository::Repository, tree::TreeNode};
 
-pub fn handle(args: &WriteTreeArgs) -> Result<()> {
-    dbg!(args);
+pub fn handle(repo: &Repository, _args: &WriteTreeArgs) -> Result<()> {
+    let index = Index::read(repo)?.unwrap_or_else(Index::new);
+    if !index.conflicted_paths().is_empty() {
+        bail!("Cannot write a tree from an index with unmerged entries");
+    }
 
-    let index = Index::read()?;
+    let node = TreeNode::try_from(index)?;
 
-    let node = TreeNode::from(index.unwrap());
-
-    let hash = node.write_recursive()?;
+    let hash = node.write_recursive(repo)?;
 
     println!("{}", hash);
 
@@ -0,0 +1,17 @@
+//! 自作の git クローン ssgit のライブラリ部分
+//!
+//! [`Repository::discover`] などで repository を開き、各 entity に渡して使う。
+
+pub mod consts;
+pub mod entity;
+pub mod error;
+
+pub use entity::{
+    head::Head,
+    index::Index,
+    object::{Commit, GitObject},
+    refs::Ref,
+    repository::Repository,
+    tree::TreeNode,
+};
+pub use error::{Error, Result};
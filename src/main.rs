@@ -1,5 +1,3 @@
-mod consts;
-mod entity;
 mod handler;
 mod parser;
 
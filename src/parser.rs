@@ -1,8 +1,20 @@
 use clap::{Args, Parser, Subcommand, ValueEnum};
+use ssgit::entity::{diff, merge, object};
+
+const DEFAULT_BATCH_FORMAT: &str = "%(objectname) %(objecttype) %(objectsize)";
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    #[arg(short = 'C', value_name = "path")]
+    pub directory: Vec<String>,
+
+    #[arg(long, value_name = "path")]
+    pub git_dir: Option<String>,
+
+    #[arg(long, value_name = "path")]
+    pub work_tree: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -21,14 +33,42 @@ pub enum Commands {
     Commit(CommitArgs),
     Switch(SwitchArgs),
     Branch(BranchArgs),
+    Repack(RepackArgs),
+    Tag(TagArgs),
+    Status(StatusArgs),
+    Log(LogArgs),
+    RevParse(RevParseArgs),
+    Diff(DiffArgs),
+    Merge(MergeArgs),
+    Config(ConfigArgs),
+    CheckIgnore(CheckIgnoreArgs),
+    Reset(ResetArgs),
+    Rm(RmArgs),
+    Mv(MvArgs),
+    Reflog(ReflogArgs),
+    PackRefs(PackRefsArgs),
+    ForEachRef(ForEachRefArgs),
+    ShowRef(ShowRefArgs),
+    SymbolicRef(SymbolicRefArgs),
+    CheckRefFormat(CheckRefFormatArgs),
 }
 
 #[derive(Args, Debug)]
 pub struct CatFileArgs {
-    pub hash: String,
+    #[arg(required_unless_present_any = ["batch", "batch_check", "batch_command"])]
+    pub hash: Option<String>,
 
     #[command(flatten)]
     pub options: CatFileArgsOptions,
+
+    #[arg(long)]
+    pub batch_all_objects: bool,
+
+    #[arg(long)]
+    pub buffer: bool,
+
+    #[arg(long)]
+    pub follow_symlinks: bool,
 }
 #[derive(Args, Debug)]
 #[group(required = true, multiple = false)]
@@ -42,6 +82,31 @@ pub struct CatFileArgsOptions {
     pub type_: bool,
     #[arg(short)]
     pub size: bool,
+
+    #[arg(
+        long,
+        value_name = "format",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_BATCH_FORMAT
+    )]
+    pub batch: Option<String>,
+    #[arg(
+        long,
+        value_name = "format",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_BATCH_FORMAT
+    )]
+    pub batch_check: Option<String>,
+    #[arg(
+        long,
+        value_name = "format",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_BATCH_FORMAT
+    )]
+    pub batch_command: Option<String>,
 }
 
 #[derive(Args, Debug)]
@@ -65,6 +130,9 @@ pub struct UpdateIndexArgs {
     #[arg(long)]
     pub remove: bool,
 
+    #[arg(long)]
+    pub force_remove: bool,
+
     #[arg(long, num_args(3),value_names(&["mode", "object", "file"]))]
     pub cacheinfo: Option<Vec<String>>,
 }
@@ -79,6 +147,8 @@ pub struct LsFilesArgs {
 
     #[arg(short, long)]
     pub stage: bool,
+
+    pub paths: Vec<String>,
 }
 
 #[derive(Args, Debug)]
@@ -94,14 +164,30 @@ pub struct CommitTreeArgs {
 
 #[derive(Args, Debug)]
 pub struct UpdateRefArgs {
-    pub ref_: String,
-    pub newvalue: String,
+    #[arg(required_unless_present = "stdin", conflicts_with = "stdin")]
+    pub ref_: Option<String>,
+    pub newvalue: Option<String>,
+    pub oldvalue: Option<String>,
+
+    #[arg(short)]
+    pub delete: bool,
+
+    #[arg(long)]
+    pub stdin: bool,
+
+    #[arg(short = 'm', value_name = "reason")]
+    pub message: Option<String>,
 }
 
 #[derive(Args, Debug)]
 pub struct InitArgs {
+    pub directory: Option<String>,
+
     #[arg(short = 'b', long)]
     pub initial_branch: Option<String>,
+
+    #[arg(long)]
+    pub bare: bool,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
@@ -109,11 +195,31 @@ pub enum ObjectType {
     Blob,
     Tree,
     Commit,
+    Tag,
+}
+impl From<ObjectType> for object::ObjectType {
+    fn from(parser_object_type: ObjectType) -> Self {
+        match parser_object_type {
+            ObjectType::Blob => Self::Blob,
+            ObjectType::Tree => Self::Tree,
+            ObjectType::Commit => Self::Commit,
+            ObjectType::Tag => Self::Tag,
+        }
+    }
 }
 
 #[derive(Args, Debug)]
 pub struct AddArgs {
     pub files: Vec<String>,
+
+    #[arg(short = 'A', long = "all", group = "mode")]
+    pub all: bool,
+
+    #[arg(short, long, group = "mode")]
+    pub update: bool,
+
+    #[arg(short, long)]
+    pub force: bool,
 }
 
 #[derive(Args, Debug)]
@@ -163,6 +269,409 @@ pub struct BranchArgsOptions {
     pub delete: Option<String>,
 }
 
+#[derive(Args, Debug)]
+pub struct RepackArgs {}
+
+#[derive(Args, Debug)]
+pub struct TagArgs {
+    pub name: Option<String>,
+
+    #[arg(value_name = "object")]
+    pub object: Option<String>,
+
+    #[arg(short, long)]
+    pub annotate: bool,
+
+    #[arg(short)]
+    pub message: Vec<String>,
+
+    #[arg(short, long, requires = "name", conflicts_with_all = ["annotate", "message", "force"])]
+    pub delete: bool,
+
+    #[arg(short, long, conflicts_with = "name")]
+    pub list: bool,
+
+    #[arg(short, long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    #[arg(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "v1")]
+    pub porcelain: Option<PorcelainVersion>,
+
+    pub paths: Vec<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PorcelainVersion {
+    V1,
+    V2,
+}
+
+#[derive(Args, Debug)]
+pub struct LogArgs {
+    #[arg(value_name = "revision range")]
+    pub revisions: Vec<String>,
+
+    #[arg(last = true, value_name = "path")]
+    pub paths: Vec<String>,
+
+    #[arg(short = 'n', long)]
+    pub max_count: Option<usize>,
+
+    #[arg(long)]
+    pub oneline: bool,
+
+    #[arg(long, visible_alias = "pretty")]
+    pub format: Option<String>,
+
+    #[arg(long)]
+    pub graph: bool,
+
+    #[arg(long)]
+    pub first_parent: bool,
+
+    #[arg(long, conflicts_with = "date_order")]
+    pub topo_order: bool,
+
+    #[arg(long)]
+    pub date_order: bool,
+
+    #[arg(long, visible_alias = "after")]
+    pub since: Option<String>,
+
+    #[arg(long, visible_alias = "before")]
+    pub until: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct RevParseArgs {
+    pub revisions: Vec<String>,
+
+    #[arg(long)]
+    pub verify: bool,
+
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "7")]
+    pub short: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    #[arg(value_name = "commit")]
+    pub revisions: Vec<String>,
+
+    #[arg(last = true, value_name = "path")]
+    pub paths: Vec<String>,
+
+    #[arg(long, visible_alias = "staged")]
+    pub cached: bool,
+
+    #[arg(short = 'U', long, value_name = "n", default_value_t = 3)]
+    pub unified: usize,
+
+    #[arg(long, conflicts_with_all = ["name_status", "name_only"])]
+    pub stat: bool,
+
+    #[arg(long, conflicts_with = "name_only")]
+    pub name_status: bool,
+
+    #[arg(long)]
+    pub name_only: bool,
+
+    #[arg(long, value_enum, default_value_t = DiffAlgorithm::Myers)]
+    pub diff_algorithm: DiffAlgorithm,
+
+    #[arg(long, conflicts_with = "diff_algorithm")]
+    pub patience: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DiffAlgorithm {
+    Myers,
+    Patience,
+}
+impl From<DiffAlgorithm> for diff::Algorithm {
+    fn from(parser_algorithm: DiffAlgorithm) -> Self {
+        match parser_algorithm {
+            DiffAlgorithm::Myers => Self::Myers,
+            DiffAlgorithm::Patience => Self::Patience,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct MergeArgs {
+    pub commit: String,
+
+    #[arg(short)]
+    pub message: Option<String>,
+
+    #[arg(long)]
+    pub no_ff: bool,
+
+    #[arg(long)]
+    pub no_commit: bool,
+
+    #[arg(long, value_enum, default_value_t = ConflictStyle::Merge)]
+    pub conflict: ConflictStyle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ConflictStyle {
+    Merge,
+    Diff3,
+}
+impl From<ConflictStyle> for merge::ConflictStyle {
+    fn from(parser_style: ConflictStyle) -> Self {
+        match parser_style {
+            ConflictStyle::Merge => Self::Merge,
+            ConflictStyle::Diff3 => Self::Diff3,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    pub name: Option<String>,
+
+    pub value: Option<String>,
+
+    #[arg(long, group = "location")]
+    pub global: bool,
+
+    #[arg(long, group = "location")]
+    pub system: bool,
+
+    #[arg(long, group = "location")]
+    pub local: bool,
+
+    #[arg(short, long, group = "location", value_name = "config-file")]
+    pub file: Option<String>,
+
+    #[arg(long, group = "action", requires = "name")]
+    pub get: bool,
+
+    #[arg(long, group = "action", requires = "name")]
+    pub get_all: bool,
+
+    #[arg(long, group = "action", requires = "name")]
+    pub unset: bool,
+
+    #[arg(short, long, group = "action")]
+    pub list: bool,
+}
+
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct ReflogArgs {
+    #[command(subcommand)]
+    pub command: Option<ReflogCommands>,
+
+    #[arg(value_name = "ref")]
+    pub ref_: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ReflogCommands {
+    Show {
+        #[arg(value_name = "ref")]
+        ref_: Option<String>,
+    },
+    Expire {
+        #[arg(long, value_name = "time")]
+        expire: Option<String>,
+
+        #[arg(long)]
+        all: bool,
+
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        #[arg(value_name = "ref")]
+        refs: Vec<String>,
+    },
+    Delete {
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        #[arg(required = true, value_name = "ref@{specifier}")]
+        entries: Vec<String>,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct PackRefsArgs {
+    #[arg(long)]
+    pub all: bool,
+
+    #[arg(long)]
+    pub no_prune: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ForEachRefArgs {
+    #[arg(value_name = "pattern")]
+    pub patterns: Vec<String>,
+
+    #[arg(long)]
+    pub format: Option<String>,
+
+    #[arg(long, value_name = "key")]
+    pub sort: Vec<String>,
+
+    #[arg(long)]
+    pub count: Option<usize>,
+
+    #[arg(long, value_name = "commit", num_args = 0..=1, default_missing_value = "HEAD")]
+    pub contains: Option<String>,
+
+    #[arg(long, value_name = "commit", num_args = 0..=1, default_missing_value = "HEAD")]
+    pub merged: Option<String>,
+
+    #[arg(long, value_name = "object")]
+    pub points_at: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ShowRefArgs {
+    #[arg(value_name = "pattern")]
+    pub patterns: Vec<String>,
+
+    #[arg(long)]
+    pub verify: bool,
+
+    #[arg(long)]
+    pub head: bool,
+
+    #[arg(long)]
+    pub heads: bool,
+
+    #[arg(long)]
+    pub tags: bool,
+
+    #[arg(short, long)]
+    pub dereference: bool,
+
+    #[arg(short = 's', long)]
+    pub hash: bool,
+
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SymbolicRefArgs {
+    pub name: String,
+    #[arg(conflicts_with = "delete")]
+    pub target: Option<String>,
+
+    #[arg(short, long)]
+    pub delete: bool,
+
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    #[arg(long)]
+    pub short: bool,
+
+    #[arg(long)]
+    pub no_recurse: bool,
+
+    #[arg(short = 'm', value_name = "reason")]
+    pub message: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckRefFormatArgs {
+    #[arg(allow_hyphen_values = true)]
+    pub refname: String,
+
+    #[arg(long, visible_alias = "print")]
+    pub normalize: bool,
+
+    #[arg(long, overrides_with = "no_allow_onelevel")]
+    pub allow_onelevel: bool,
+
+    #[arg(long, overrides_with = "allow_onelevel")]
+    pub no_allow_onelevel: bool,
+
+    #[arg(long)]
+    pub refspec_pattern: bool,
+
+    #[arg(long)]
+    pub branch: bool,
+}
+
 pub fn parse() -> Cli {
     Cli::parse()
 }
+
+#[derive(Args, Debug)]
+pub struct CheckIgnoreArgs {
+    #[arg(required = true)]
+    pub paths: Vec<String>,
+
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    #[arg(short, long, requires = "verbose")]
+    pub non_matching: bool,
+
+    #[arg(long)]
+    pub no_index: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ResetArgs {
+    #[arg(long, group = "mode")]
+    pub soft: bool,
+
+    #[arg(long, group = "mode")]
+    pub mixed: bool,
+
+    #[arg(long, group = "mode")]
+    pub hard: bool,
+
+    #[arg(value_name = "commit")]
+    pub revision: Option<String>,
+
+    #[arg(last = true, value_name = "path")]
+    pub paths: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct RmArgs {
+    #[arg(required = true)]
+    pub paths: Vec<String>,
+
+    #[arg(long)]
+    pub cached: bool,
+
+    #[arg(short)]
+    pub recursive: bool,
+
+    #[arg(short, long)]
+    pub force: bool,
+
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct MvArgs {
+    #[arg(required = true)]
+    pub sources: Vec<String>,
+
+    #[arg(required = true)]
+    pub destination: String,
+
+    #[arg(short, long)]
+    pub force: bool,
+
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+}